@@ -0,0 +1,22 @@
+use std::env;
+
+/// Sets `blackbox_arch` to the architecture whose system call numbers are used.
+///
+/// For userspace this is the target architecture. The eBPF program is built for the `bpf`
+/// target, so the architecture of the kernel it is loaded into is taken from the
+/// `bpf_target_arch` cfg, defaulting to the host architecture like aya-bpf does.
+fn main() {
+    println!("cargo:rerun-if-env-changed=CARGO_CFG_BPF_TARGET_ARCH");
+    println!("cargo:rustc-check-cfg=cfg(blackbox_arch, values(\"x86_64\", \"aarch64\"))");
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    let arch = if target_arch == "bpf" {
+        env::var("CARGO_CFG_BPF_TARGET_ARCH").unwrap_or_else(|_| {
+            let host = env::var("HOST").unwrap();
+            host.split_once('-')
+                .map_or(host.clone(), |(arch, _)| arch.to_owned())
+        })
+    } else {
+        target_arch
+    };
+    println!("cargo:rustc-cfg=blackbox_arch=\"{arch}\"");
+}
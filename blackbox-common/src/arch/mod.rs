@@ -0,0 +1,41 @@
+//! System call numbers for each supported architecture.
+//!
+//! The architecture is chosen by the build script: the target architecture for userspace,
+//! and the `bpf_target_arch` cfg for the eBPF program, so both sides agree on the numbers
+//! in a [`SyscallEvent`](crate::SyscallEvent).
+
+/// Defines the conversions between [`SyscallID`](crate::SyscallID)s and the system call numbers of an
+/// architecture. Variants that are left out do not exist on the architecture.
+macro_rules! syscall_numbers {
+    ($($id:ident = $number:literal,)*) => {
+        /// Returns the [`SyscallID`](crate::SyscallID) of the system call numbered `number`
+        pub fn syscall_from_number(number: u64) -> crate::SyscallID {
+            match number {
+                $($number => crate::SyscallID::$id,)*
+                _ => crate::SyscallID::Unhandled,
+            }
+        }
+
+        /// Returns the number of the system call `id`, or `None` if it does not exist on
+        /// this architecture
+        pub fn syscall_number(id: crate::SyscallID) -> Option<u64> {
+            match id {
+                $(crate::SyscallID::$id => Some($number),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+#[cfg(blackbox_arch = "x86_64")]
+mod x86_64;
+#[cfg(blackbox_arch = "x86_64")]
+pub use x86_64::*;
+
+#[cfg(blackbox_arch = "aarch64")]
+mod aarch64;
+#[cfg(blackbox_arch = "aarch64")]
+pub use aarch64::*;
+
+#[cfg(not(any(blackbox_arch = "x86_64", blackbox_arch = "aarch64")))]
+compile_error!("blackbox only supports x86_64 and aarch64");
@@ -2,8 +2,61 @@
 
 use core::num::NonZeroUsize;
 
-/// The maximum amount of data that can be read into the buffer to pass to userspace
-pub const BUFFER_SIZE: usize = 1024;
+pub mod arch;
+pub mod syscall_names;
+
+/// The maximum amount of data that can be read into a single buffer to pass to userspace.
+/// Larger data is split across multiple [`EventBuffer`]s.
+pub const BUFFER_SIZE: usize = 4096;
+
+/// The maximum number of [`EventBuffer`]s sent for the data of a single event
+pub const MAX_BUFFER_CHUNKS: usize = 256;
+
+/// The maximum amount of data that can be captured for a single event
+pub const MAX_CAPTURE_SIZE: usize = BUFFER_SIZE * MAX_BUFFER_CHUNKS;
+
+/// The number of entries in the syscall filter; every system call ID must be lower than this
+pub const MAX_SYSCALL_ID: u32 = 512;
+
+/// The maximum number of path prefixes used to filter open calls
+pub const MAX_PATH_PREFIXES: usize = 8;
+
+/// The maximum length of a path prefix used to filter open calls
+pub const PATH_PREFIX_SIZE: usize = 128;
+
+/// Tracing options set by userspace before tracing starts
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TraceConfig {
+    /// The maximum number of bytes of read or write data to capture for each call,
+    /// up to [`MAX_CAPTURE_SIZE`]. Zero captures no data.
+    pub capture_limit: u64,
+    /// The number of [`PathPrefix`]es set. If this is zero, open calls are not filtered by path
+    pub path_prefix_count: u64,
+    /// The offset of `exit_code` in the kernel's `struct task_struct`, or zero if it is not
+    /// known and exit statuses cannot be read
+    pub exit_code_offset: u64,
+    /// The offset of `signal` in the kernel's `struct task_struct`, or zero if it is not known
+    pub signal_offset: u64,
+    /// The offset of `live` in the kernel's `struct signal_struct`, or zero if it is not known.
+    /// Without this and `signal_offset`, a process is taken to exit when its main thread does
+    pub live_offset: u64,
+}
+
+/// A path prefix used to filter open calls. Only calls opening a path that
+/// starts with one of the prefixes are sent to userspace.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct PathPrefix {
+    /// The number of bytes used in `prefix`
+    pub length: u64,
+    pub prefix: [u8; PATH_PREFIX_SIZE],
+}
+
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for TraceConfig {}
+#[cfg(feature = "user")]
+unsafe impl aya::Pod for PathPrefix {}
 
 /// A syscall event from sys_enter or sys_exit. It contains all
 /// of the relevant data from the event for reconstructing the call,
@@ -11,7 +64,7 @@ pub const BUFFER_SIZE: usize = 1024;
 ///
 /// If an event does not have data associated, `data_size` will be
 /// `None`. Otherwise, `data_size` will be the size of the data read,
-/// and an associated [`EventBuffer`] object will be sent to userspace.
+/// and the associated [`EventBuffer`] objects will be sent to userspace.
 ///
 /// The [`EventID`] struct is used to connect [`EventBuffer`] object
 /// with this event.
@@ -24,9 +77,9 @@ pub struct SyscallEvent {
     pub tgid: u32,
     /// the process id (user-space thread id)
     pub pid: u32,
-    /// The value from rax
+    /// The system call number, which depends on the architecture
     pub syscall_id: u64,
-    // argument values from rdi, etc.
+    // argument values from the argument registers
     pub arg_0: u64,
     pub arg_1: u64,
     pub arg_2: u64,
@@ -42,6 +95,9 @@ pub struct SyscallEvent {
 /// A static-sized buffer that contains data read in from memory
 /// that is associated with a [`SyscallEvent`]. The [`EventID`]
 /// struct is used to correlate the two.
+///
+/// Data larger than [`BUFFER_SIZE`] is sent as several buffers with the
+/// same [`EventID`], each containing the chunk starting at `offset`.
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct EventBuffer {
@@ -51,8 +107,12 @@ pub struct EventBuffer {
     pub tgid: u32,
     /// the process id (user-space thread id)
     pub pid: u32,
-    /// The value from rax
+    /// The system call number, which depends on the architecture
     pub syscall_id: u64,
+    /// the offset of this chunk in the data of the event
+    pub offset: u64,
+    /// the number of bytes of `data_buffer` used by this chunk
+    pub length: u64,
     /// a constant-size buffer for data to be read from
     pub data_buffer: [u8; BUFFER_SIZE],
 }
@@ -82,6 +142,28 @@ impl SyscallEvent {
     }
 }
 
+/// An event sent when a traced process terminates, from the `sched_process_exit`
+/// tracepoint of its last thread. The exit status is collected while the process runs: from the
+/// argument to `exit` or `exit_group`, or from a signal sent to one of its threads.
+///
+/// If neither is known when the process exits, both `exit_code` and `signal`
+/// will be `None`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ProcessExitEvent {
+    /// a timestamp from the montonic system clock
+    pub timestamp: u64,
+    /// the thread global ID, or PID
+    pub tgid: u32,
+    /// the process id (user-space thread id)
+    pub pid: u32,
+    /// The status the process exited with. None if it was terminated by a signal, or its
+    /// status could not be read
+    pub exit_code: Option<i32>,
+    /// The signal that terminated the process. None if it exited normally
+    pub signal: Option<i32>,
+}
+
 /// A utility trait for getting an [`EventID`] from a struct.
 pub trait GetEventId {
     fn get_event_id(&self) -> EventID;
@@ -107,23 +189,68 @@ impl GetEventId for SyscallEvent {
     }
 }
 
-#[repr(u16)]
+/// The system calls handled by blackbox. The numbers of each call depend on the architecture;
+/// see [`arch`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SyscallID {
-    Read = 0,
-    Write = 1,
-    Open = 2,
-    OpenAt = 257,
-    Creat = 85,
-    Close = 3,
-    Socket = 41,
-    Shutdown = 48,
-    Clone = 56,
-    Fork = 57,
-    Execve = 59,
-    ExecveAt = 322,
-    Exit = 60,
-    ExitGroup = 231,
+    Read,
+    Write,
+    PRead64,
+    PWrite64,
+    ReadV,
+    WriteV,
+    PReadV,
+    PWriteV,
+    PReadV2,
+    PWriteV2,
+    SendFile,
+    Splice,
+    CopyFileRange,
+    Open,
+    OpenAt,
+    Creat,
+    Close,
+    Socket,
+    Shutdown,
+    Pipe,
+    Pipe2,
+    SocketPair,
+    EventFd,
+    EventFd2,
+    Dup,
+    Dup2,
+    Dup3,
+    GetDents,
+    GetDents64,
+    Stat,
+    LStat,
+    NewFStatAt,
+    Statx,
+    Access,
+    FAccessAt,
+    FAccessAt2,
+    ReadLink,
+    ReadLinkAt,
+    InitModule,
+    FInitModule,
+    DeleteModule,
+    Bpf,
+    Mount,
+    Umount2,
+    PivotRoot,
+    Chroot,
+    Unshare,
+    SetNs,
+    KexecLoad,
+    KexecFileLoad,
+    Clone,
+    Clone3,
+    Fork,
+    VFork,
+    Execve,
+    ExecveAt,
+    Exit,
+    ExitGroup,
     Unhandled,
 }
 
@@ -134,31 +261,24 @@ impl SyscallID {
             || self == Self::Exit
             || self == Self::ExitGroup
     }
+
+    /// Whether this call writes data from a user-space buffer
+    pub fn is_write(self) -> bool {
+        matches!(
+            self,
+            Self::Write | Self::PWrite64 | Self::WriteV | Self::PWriteV | Self::PWriteV2
+        )
+    }
 }
 
 impl<T> From<T> for SyscallID
 where
-    T: TryInto<u16>,
+    T: TryInto<u64>,
 {
     fn from(value: T) -> Self {
-        let Ok(num): Result<u16, _> = value.try_into() else {
+        let Ok(number): Result<u64, _> = value.try_into() else {
             return Self::Unhandled;
         };
-        match num {
-            0 => Self::Read,
-            1 => Self::Write,
-            2 => Self::Open,
-            257 => Self::OpenAt,
-            85 => Self::Creat,
-            3 => Self::Close,
-            41 => Self::Socket,
-            48 => Self::Shutdown,
-            56 => Self::Clone,
-            57 => Self::Fork,
-            59 => Self::Execve,
-            60 => Self::Exit,
-            231 => Self::ExitGroup,
-            _ => Self::Unhandled,
-        }
+        arch::syscall_from_number(number)
     }
 }
@@ -0,0 +1,20 @@
+//! Names of the system calls, used for filtering and displaying events
+//! in userspace
+
+pub use crate::arch::SYSCALL_TABLE;
+
+/// Returns the name of the system call with the ID `id`
+pub fn syscall_name(id: u64) -> Option<&'static str> {
+    SYSCALL_TABLE
+        .iter()
+        .find(|(_, syscall_id)| *syscall_id == id)
+        .map(|(name, _)| *name)
+}
+
+/// Returns the ID of the system call named `name`
+pub fn syscall_id(name: &str) -> Option<u64> {
+    SYSCALL_TABLE
+        .iter()
+        .find(|(syscall_name, _)| *syscall_name == name)
+        .map(|(_, id)| *id)
+}
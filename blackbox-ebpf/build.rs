@@ -0,0 +1,16 @@
+use std::env;
+
+/// Sets `bpf_target_arch` to the host architecture if it was not set, as aya-bpf does, so that
+/// the registers of the right architecture are read. Set it with
+/// `RUSTFLAGS='--cfg bpf_target_arch="aarch64"'` to build for another architecture.
+fn main() {
+    println!("cargo:rerun-if-env-changed=CARGO_CFG_BPF_TARGET_ARCH");
+    println!(
+        "cargo:rustc-check-cfg=cfg(bpf_target_arch, values(\"x86_64\", \"arm\", \"aarch64\", \"riscv64\"))"
+    );
+    if env::var("CARGO_CFG_BPF_TARGET_ARCH").is_err() {
+        let host = env::var("HOST").unwrap();
+        let arch = host.split_once('-').map_or(host.as_str(), |(arch, _)| arch);
+        println!("cargo:rustc-cfg=bpf_target_arch=\"{arch}\"");
+    }
+}
@@ -0,0 +1,94 @@
+//! Reading the registers saved when a process makes a system call. The layout of the
+//! registers and the calling convention depend on the architecture being traced.
+
+use aya_bpf::{cty::c_ulong, helpers::bpf_probe_read};
+
+use crate::types::EbpfError;
+
+#[cfg(bpf_target_arch = "x86_64")]
+pub use aya_bpf::bindings::pt_regs as SyscallRegs;
+
+/// `struct pt_regs` on aarch64, up to the fields used for system calls
+#[cfg(bpf_target_arch = "aarch64")]
+#[repr(C)]
+pub struct SyscallRegs {
+    pub regs: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub pstate: u64,
+    /// the first argument. x0 is overwritten by the return value
+    pub orig_x0: u64,
+    pub syscallno: i32,
+}
+
+#[cfg(not(any(bpf_target_arch = "x86_64", bpf_target_arch = "aarch64")))]
+compile_error!("blackbox only supports x86_64 and aarch64");
+
+/// Reads argument `n` of a system call on sys_enter
+#[cfg(bpf_target_arch = "x86_64")]
+pub unsafe fn enter_arg(regs: *const SyscallRegs, n: usize) -> Result<c_ulong, EbpfError> {
+    let ctx = &*regs;
+    let register = match n {
+        0 => &ctx.rdi,
+        1 => &ctx.rsi,
+        2 => &ctx.rdx,
+        // the syscall ABI uses r10 instead of rcx for the fourth argument
+        3 => &ctx.r10,
+        4 => &ctx.r8,
+        5 => &ctx.r9,
+        _ => return Err(EbpfError::Logic),
+    };
+    bpf_probe_read(register).map_err(|_| EbpfError::Arg(n))
+}
+
+/// Reads argument `n` of a system call on sys_exit. The argument registers are
+/// preserved across the call
+#[cfg(bpf_target_arch = "x86_64")]
+pub unsafe fn exit_arg(regs: *const SyscallRegs, n: usize) -> Result<c_ulong, EbpfError> {
+    enter_arg(regs, n)
+}
+
+/// Reads the number of the system call on sys_exit
+#[cfg(bpf_target_arch = "x86_64")]
+pub unsafe fn syscall_number(regs: *const SyscallRegs) -> Result<c_ulong, EbpfError> {
+    bpf_probe_read(&(*regs).orig_rax).map_err(EbpfError::Read)
+}
+
+/// Reads the return value of a system call on sys_exit
+#[cfg(bpf_target_arch = "x86_64")]
+pub unsafe fn return_value(regs: *const SyscallRegs) -> Result<c_ulong, EbpfError> {
+    bpf_probe_read(&(*regs).rax).map_err(EbpfError::Read)
+}
+
+/// Reads argument `n` of a system call on sys_enter
+#[cfg(bpf_target_arch = "aarch64")]
+pub unsafe fn enter_arg(regs: *const SyscallRegs, n: usize) -> Result<c_ulong, EbpfError> {
+    if n > 5 {
+        return Err(EbpfError::Logic);
+    }
+    bpf_probe_read(&(*regs).regs[n]).map_err(|_| EbpfError::Arg(n))
+}
+
+/// Reads argument `n` of a system call on sys_exit. x0 holds the return value by then,
+/// so the first argument is read from the copy the kernel saved
+#[cfg(bpf_target_arch = "aarch64")]
+pub unsafe fn exit_arg(regs: *const SyscallRegs, n: usize) -> Result<c_ulong, EbpfError> {
+    match n {
+        0 => bpf_probe_read(&(*regs).orig_x0).map_err(|_| EbpfError::Arg(n)),
+        _ => enter_arg(regs, n),
+    }
+}
+
+/// Reads the number of the system call on sys_exit
+#[cfg(bpf_target_arch = "aarch64")]
+pub unsafe fn syscall_number(regs: *const SyscallRegs) -> Result<c_ulong, EbpfError> {
+    bpf_probe_read(&(*regs).syscallno)
+        .map(|number| number as c_ulong)
+        .map_err(EbpfError::Read)
+}
+
+/// Reads the return value of a system call on sys_exit
+#[cfg(bpf_target_arch = "aarch64")]
+pub unsafe fn return_value(regs: *const SyscallRegs) -> Result<c_ulong, EbpfError> {
+    bpf_probe_read(&(*regs).regs[0]).map_err(EbpfError::Read)
+}
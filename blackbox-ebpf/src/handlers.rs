@@ -1,12 +1,17 @@
 use core::num::NonZeroUsize;
 
-use aya_bpf::helpers::{bpf_probe_read_user_buf, bpf_probe_read_user_str_bytes};
+use aya_bpf::helpers::{
+    bpf_probe_read_user, bpf_probe_read_user_buf, bpf_probe_read_user_str_bytes,
+};
 use aya_bpf::maps::PerCpuArray;
 use aya_bpf::{macros::map, programs::RawTracePointContext};
 
-use crate::types::EbpfError;
-use crate::{send_event, BUFFER_OUTPUT};
-use blackbox_common::{EventBuffer, EventID, GetEventId, SyscallEvent, SyscallID, BUFFER_SIZE};
+use crate::types::{EbpfError, IoVec, MAX_IOVECS};
+use crate::{send_event, BUFFER_OUTPUT, CONFIG, PATH_PREFIXES, SKIPPED};
+use blackbox_common::{
+    EventBuffer, EventID, GetEventId, PathPrefix, SyscallEvent, SyscallID, BUFFER_SIZE,
+    MAX_BUFFER_CHUNKS, MAX_CAPTURE_SIZE, MAX_PATH_PREFIXES, PATH_PREFIX_SIZE,
+};
 
 #[map]
 static mut DATA_BUFFER: PerCpuArray<EventBuffer> = PerCpuArray::with_max_entries(1, 0);
@@ -19,7 +24,7 @@ pub fn sys_read_write_handler(
     ctx: &RawTracePointContext,
     mut event: SyscallEvent,
 ) -> Result<u128, EbpfError> {
-    let is_write = event.syscall_id == 1;
+    let is_write = SyscallID::from(event.syscall_id).is_write();
 
     let length = if is_write {
         NonZeroUsize::new(event.arg_2 as usize)
@@ -33,8 +38,11 @@ pub fn sys_read_write_handler(
             NonZeroUsize::new(ret as usize)
         }
     };
+    let capture_limit = unsafe { CONFIG.get(0) }
+        .ok_or(EbpfError::Map)?
+        .capture_limit as usize;
     let mut length =
-        length.map(|l| unsafe { NonZeroUsize::new_unchecked(BUFFER_SIZE.min(l.get())) });
+        length.and_then(|l| NonZeroUsize::new(l.get().min(capture_limit).min(MAX_CAPTURE_SIZE)));
     let data_ptr = event.arg_1 as *const u8;
 
     if data_ptr.is_null() {
@@ -62,25 +70,336 @@ pub fn sys_read_write_handler(
     Ok(0)
 }
 
-pub fn filename_handler(
+/// Handle a vectored read or write syscall (readv, writev, preadv, ...), gathering
+/// the data of each `struct iovec` into one buffer
+///
+/// Returns a u128 as the ok value to prevent an llvm error
+/// with tuple returns. It should be treated as a () return
+pub fn iovec_handler(
+    ctx: &RawTracePointContext,
+    mut event: SyscallEvent,
+) -> Result<u128, EbpfError> {
+    let capture_limit = unsafe { CONFIG.get(0) }
+        .ok_or(EbpfError::Map)?
+        .capture_limit as usize;
+    let limit = capture_limit.min(MAX_CAPTURE_SIZE);
+    let length = if SyscallID::from(event.syscall_id).is_write() {
+        // the total length of the iovecs is found while reading them
+        limit
+    } else {
+        // read returns a ssize_t, which should be isize
+        let ret = event.return_val.ok_or(EbpfError::Logic)? as isize;
+        if ret < 0 {
+            0
+        } else {
+            (ret as usize).min(limit)
+        }
+    };
+    let iov = event.arg_1 as *const IoVec;
+    let iov_count = event.arg_2 as usize;
+
+    event.data_size = None;
+    if length > 0 && !iov.is_null() {
+        let result = read_iovecs_and_send(
+            ctx,
+            iov,
+            iov_count,
+            length,
+            event.get_event_id(),
+            event.syscall_id,
+        );
+        match result {
+            Ok(captured) => event.data_size = NonZeroUsize::new(captured),
+            Err(error) => {
+                send_event(ctx, &event)?;
+                return Err(error);
+            }
+        }
+    }
+
+    send_event(ctx, &event)?;
+    Ok(0)
+}
+
+/// Handle a syscall that moves data between file descriptors in the kernel
+/// (sendfile, splice, copy_file_range). The data itself never passes through
+/// user-space, so only the offsets passed to the call are read. They are sent
+/// as two i64s: the input offset and the output offset, where -1 means that
+/// the current file position is used.
+///
+/// Returns a u128 as the ok value to prevent an llvm error
+/// with tuple returns. It should be treated as a () return
+pub fn offsets_handler(
+    ctx: &RawTracePointContext,
+    mut event: SyscallEvent,
+) -> Result<u128, EbpfError> {
+    let (in_offset_ptr, out_offset_ptr) =
+        if SyscallID::from(event.syscall_id) == SyscallID::SendFile {
+            // sendfile(out_fd, in_fd, offset, count)
+            (event.arg_2 as *const i64, core::ptr::null())
+        } else {
+            // splice and copy_file_range(fd_in, off_in, fd_out, off_out, len, flags)
+            (event.arg_1 as *const i64, event.arg_3 as *const i64)
+        };
+    let read_offset = |ptr: *const i64| -> Result<i64, EbpfError> {
+        if ptr.is_null() {
+            Ok(-1)
+        } else {
+            unsafe { bpf_probe_read_user(ptr).map_err(EbpfError::Read) }
+        }
+    };
+    let offsets = [read_offset(in_offset_ptr), read_offset(out_offset_ptr)];
+    if let [Ok(in_offset), Ok(out_offset)] = offsets {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&in_offset.to_ne_bytes());
+        bytes[8..].copy_from_slice(&out_offset.to_ne_bytes());
+        send_bytes(ctx, &event, &bytes)?;
+        event.data_size = NonZeroUsize::new(bytes.len());
+    }
+    send_event(ctx, &event)?;
+    for offset in offsets {
+        offset?;
+    }
+    Ok(0)
+}
+
+/// Handle a syscall that returns two file descriptors in an `int[2]` array (pipe, pipe2,
+/// socketpair). This must be called on sys_exit, once the array has been filled in.
+/// The file descriptors are sent as two i32s.
+///
+/// Returns a u128 as the ok value to prevent an llvm error
+/// with tuple returns. It should be treated as a () return
+pub fn fd_pair_handler(
+    ctx: &RawTracePointContext,
+    mut event: SyscallEvent,
+) -> Result<u128, EbpfError> {
+    let ptr = if SyscallID::from(event.syscall_id) == SyscallID::SocketPair {
+        // socketpair(domain, type, protocol, sv)
+        event.arg_3 as *const [i32; 2]
+    } else {
+        // pipe(pipefd) and pipe2(pipefd, flags)
+        event.arg_0 as *const [i32; 2]
+    };
+    if event.return_val != Some(0) || ptr.is_null() {
+        // the call failed, so the array was not filled in
+        send_event(ctx, &event)?;
+        return Ok(0);
+    }
+    let fds = unsafe { bpf_probe_read_user(ptr).map_err(EbpfError::Read) };
+    if let Ok(fds) = fds {
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&fds[0].to_ne_bytes());
+        bytes[4..].copy_from_slice(&fds[1].to_ne_bytes());
+        send_bytes(ctx, &event, &bytes)?;
+        event.data_size = NonZeroUsize::new(bytes.len());
+    }
+    send_event(ctx, &event)?;
+    fds?;
+    Ok(0)
+}
+
+/// Handle clone3, which passes its flags in a `struct clone_args`. The flags, the first
+/// field of the struct, are sent as a u64.
+///
+/// Returns a u128 as the ok value to prevent an llvm error
+/// with tuple returns. It should be treated as a () return
+pub fn clone3_handler(
     ctx: &RawTracePointContext,
     mut event: SyscallEvent,
 ) -> Result<u128, EbpfError> {
-    let ptr = if event.syscall_id == SyscallID::OpenAt as u64
-        || event.syscall_id == SyscallID::ExecveAt as u64
-    {
-        event.arg_1 as *const u8
+    let ptr = event.arg_0 as *const u64;
+    let flags = if ptr.is_null() {
+        Ok(None)
     } else {
-        // open or creat or execve
-        event.arg_0 as *const u8
+        unsafe { bpf_probe_read_user(ptr).map(Some).map_err(EbpfError::Read) }
     };
-    let length = read_string_and_send(ctx, ptr, event.get_event_id(), event.syscall_id);
+    if let Ok(Some(flags)) = flags {
+        let bytes = flags.to_ne_bytes();
+        send_bytes(ctx, &event, &bytes)?;
+        event.data_size = NonZeroUsize::new(bytes.len());
+    }
+    send_event(ctx, &event)?;
+    flags?;
+    Ok(0)
+}
+
+/// Sends a small value read from the arguments of `event` to userspace as a single chunk
+fn send_bytes<const N: usize>(
+    ctx: &RawTracePointContext,
+    event: &SyscallEvent,
+    bytes: &[u8; N],
+) -> Result<(), EbpfError> {
+    let data_buffer = unsafe {
+        let ptr = DATA_BUFFER.get_ptr_mut(0).ok_or(EbpfError::Map)?;
+        &mut *ptr
+    };
+    let event_id = event.get_event_id();
+    data_buffer.pid = event_id.pid;
+    data_buffer.tgid = event_id.tgid;
+    data_buffer.timestamp = event_id.timestamp;
+    data_buffer.syscall_id = event.syscall_id;
+    data_buffer.offset = 0;
+    data_buffer.length = N as u64;
+    data_buffer.data_buffer[..N].copy_from_slice(bytes);
+    unsafe {
+        BUFFER_OUTPUT.output(ctx, data_buffer, 0);
+    }
+    Ok(())
+}
+
+/// The most strings read by [`strings_handler`] for one call
+const MAX_STRINGS: usize = 3;
+
+/// Handle a privileged syscall whose arguments include strings (module parameters, mount
+/// paths, ...). The strings are sent null-terminated, one after the other, so that
+/// userspace can split them again. A null pointer is sent as an empty string.
+///
+/// Returns a u128 as the ok value to prevent an llvm error
+/// with tuple returns. It should be treated as a () return
+pub fn strings_handler(
+    ctx: &RawTracePointContext,
+    mut event: SyscallEvent,
+) -> Result<u128, EbpfError> {
+    let (args, count): ([u64; MAX_STRINGS], usize) = match SyscallID::from(event.syscall_id) {
+        // init_module(image, len, params)
+        SyscallID::InitModule => ([event.arg_2, 0, 0], 1),
+        // finit_module(fd, params, flags)
+        SyscallID::FInitModule => ([event.arg_1, 0, 0], 1),
+        // mount(source, target, fstype, flags, data)
+        SyscallID::Mount => ([event.arg_0, event.arg_1, event.arg_2], 3),
+        // pivot_root(new_root, put_old)
+        SyscallID::PivotRoot => ([event.arg_0, event.arg_1, 0], 2),
+        // kexec_file_load(kernel_fd, initrd_fd, cmdline_len, cmdline, flags)
+        SyscallID::KexecFileLoad => ([event.arg_3, 0, 0], 1),
+        // delete_module(name, flags), umount2(target, flags) or chroot(path)
+        _ => ([event.arg_0, 0, 0], 1),
+    };
+    let result = read_strings_and_send(ctx, &args, count, event.get_event_id(), event.syscall_id);
+    event.data_size = match result {
+        Ok(length) | Err((length, _)) => NonZeroUsize::new(length),
+    };
+    send_event(ctx, &event)?;
+    result.map_err(|(_, error)| error)?;
+    Ok(0)
+}
+
+/// The most arguments of an execve call sent to userspace
+const MAX_EXEC_ARGS: usize = 16;
+
+/// Handle execve and execveat. The filename and then the arguments are sent like the
+/// strings of [`strings_handler`]: null-terminated, one after the other.
+///
+/// Returns a u128 as the ok value to prevent an llvm error
+/// with tuple returns. It should be treated as a () return
+pub fn execve_handler(
+    ctx: &RawTracePointContext,
+    mut event: SyscallEvent,
+) -> Result<u128, EbpfError> {
+    let (filename, argv) = match SyscallID::from(event.syscall_id) {
+        // execveat(dirfd, pathname, argv, envp, flags)
+        SyscallID::ExecveAt => (event.arg_1, event.arg_2 as *const u64),
+        // execve(pathname, argv, envp)
+        _ => (event.arg_0, event.arg_1 as *const u64),
+    };
+    let mut args = [0; MAX_EXEC_ARGS + 1];
+    args[0] = filename;
+    let mut count = 1;
+    if !argv.is_null() {
+        // argv is terminated by a null pointer
+        for i in 0..MAX_EXEC_ARGS {
+            let arg = unsafe { bpf_probe_read_user(argv.add(i)) }.unwrap_or(0);
+            if arg == 0 {
+                break;
+            }
+            args[i + 1] = arg;
+            count += 1;
+        }
+    }
+    let result = read_strings_and_send(ctx, &args, count, event.get_event_id(), event.syscall_id);
+    event.data_size = match result {
+        Ok(length) | Err((length, _)) => NonZeroUsize::new(length),
+    };
+    send_event(ctx, &event)?;
+    result.map_err(|(_, error)| error)?;
+    Ok(0)
+}
+
+pub fn filename_handler(
+    ctx: &RawTracePointContext,
+    mut event: SyscallEvent,
+) -> Result<u128, EbpfError> {
+    let ptr = match SyscallID::from(event.syscall_id) {
+        // the *at calls take a directory file descriptor first
+        SyscallID::OpenAt
+        | SyscallID::NewFStatAt
+        | SyscallID::Statx
+        | SyscallID::FAccessAt
+        | SyscallID::FAccessAt2
+        | SyscallID::ReadLinkAt => event.arg_1 as *const u8,
+        // open, creat, stat, lstat, access or readlink
+        _ => event.arg_0 as *const u8,
+    };
+    let is_open = matches!(
+        SyscallID::from(event.syscall_id),
+        SyscallID::Open | SyscallID::OpenAt | SyscallID::Creat
+    );
+    let length = read_string(ptr, event.get_event_id(), event.syscall_id);
+    if let Ok(Some(length)) = length {
+        if is_open && !matches_path_filter(length.get())? {
+            // the sys_exit event of this call is skipped as well
+            unsafe { SKIPPED.insert(&event.pid, &event.syscall_id, 0) }
+                .map_err(|_| EbpfError::Map)?;
+            return Ok(0);
+        }
+        send_buffer(ctx)?;
+    }
     event.data_size = length.unwrap_or(None);
     send_event(ctx, &event)?;
     length?;
     Ok(0)
 }
 
+/// Checks the path in the data buffer with the length `length` against the path
+/// prefixes set by userspace. Returns true if no prefixes are set.
+fn matches_path_filter(length: usize) -> Result<bool, EbpfError> {
+    let prefix_count = unsafe { CONFIG.get(0) }
+        .ok_or(EbpfError::Map)?
+        .path_prefix_count as u32;
+    if prefix_count == 0 {
+        return Ok(true);
+    }
+    let path = unsafe { &(*DATA_BUFFER.get_ptr(0).ok_or(EbpfError::Map)?).data_buffer };
+    for i in 0..MAX_PATH_PREFIXES as u32 {
+        if i >= prefix_count {
+            break;
+        }
+        let prefix = unsafe { PATH_PREFIXES.get(i) }.ok_or(EbpfError::Map)?;
+        if has_prefix(path, length, prefix) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn has_prefix(path: &[u8; BUFFER_SIZE], length: usize, prefix: &PathPrefix) -> bool {
+    let prefix_length = prefix.length as usize;
+    if prefix_length > length {
+        return false;
+    }
+    // the loop must be bounded for the verifier
+    for i in 0..PATH_PREFIX_SIZE {
+        if i >= prefix_length {
+            break;
+        }
+        if path[i] != prefix.prefix[i] {
+            return false;
+        }
+    }
+    true
+}
+
+/// Reads `length` bytes from `ptr` and sends them to userspace, split
+/// into chunks of at most [`BUFFER_SIZE`] bytes
 fn read_bytes_and_send(
     ctx: &RawTracePointContext,
     ptr: *const u8,
@@ -99,20 +418,150 @@ fn read_bytes_and_send(
 
     let buf_ptr = data_buffer.data_buffer.as_mut_ptr();
 
-    let limited_length = length.min(BUFFER_SIZE);
-    let dest = unsafe { core::slice::from_raw_parts_mut(buf_ptr, limited_length) };
+    // the loop must be bounded for the verifier
+    for chunk in 0..MAX_BUFFER_CHUNKS {
+        let offset = chunk * BUFFER_SIZE;
+        if offset >= length {
+            break;
+        }
+        data_buffer.offset = offset as u64;
 
-    unsafe {
-        bpf_probe_read_user_buf(ptr, dest).map_err(EbpfError::Read)?;
-    };
-    unsafe {
-        BUFFER_OUTPUT.output(ctx, data_buffer, 0);
+        let limited_length = (length - offset).min(BUFFER_SIZE);
+        data_buffer.length = limited_length as u64;
+        let dest = unsafe { core::slice::from_raw_parts_mut(buf_ptr, limited_length) };
+
+        unsafe {
+            bpf_probe_read_user_buf(ptr.add(offset), dest).map_err(EbpfError::Read)?;
+        };
+        unsafe {
+            BUFFER_OUTPUT.output(ctx, data_buffer, 0);
+        }
     }
     Ok(())
 }
 
-fn read_string_and_send(
+/// Reads the data of up to `iov_count` iovecs, up to a total of `length` bytes,
+/// and sends it to userspace in chunks of at most [`BUFFER_SIZE`] bytes. The
+/// chunks are numbered as if the iovecs were one contiguous buffer.
+///
+/// Returns the number of bytes sent
+fn read_iovecs_and_send(
     ctx: &RawTracePointContext,
+    iov: *const IoVec,
+    iov_count: usize,
+    length: usize,
+    event_id: EventID,
+    syscall_id: u64,
+) -> Result<usize, EbpfError> {
+    let data_buffer = unsafe {
+        let ptr = DATA_BUFFER.get_ptr_mut(0).ok_or(EbpfError::Map)?;
+        &mut *ptr
+    };
+    data_buffer.pid = event_id.pid;
+    data_buffer.tgid = event_id.tgid;
+    data_buffer.timestamp = event_id.timestamp;
+    data_buffer.syscall_id = syscall_id;
+
+    let buf_ptr = data_buffer.data_buffer.as_mut_ptr();
+
+    let iov_count = iov_count.min(MAX_IOVECS);
+    let mut iov_index = 0;
+    // the offset into the current iovec
+    let mut iov_offset = 0;
+    let mut total = 0;
+    // the loop must be bounded for the verifier; each iteration either sends a chunk
+    // or moves on to the next iovec
+    for _ in 0..(MAX_BUFFER_CHUNKS + MAX_IOVECS) {
+        if total >= length || iov_index >= iov_count {
+            break;
+        }
+        let current: IoVec =
+            unsafe { bpf_probe_read_user(iov.add(iov_index)).map_err(EbpfError::Read)? };
+        if iov_offset >= current.length || current.base.is_null() {
+            iov_index += 1;
+            iov_offset = 0;
+            continue;
+        }
+        let limited_length = (current.length - iov_offset)
+            .min(length - total)
+            .min(BUFFER_SIZE);
+        data_buffer.offset = total as u64;
+        data_buffer.length = limited_length as u64;
+        let dest = unsafe { core::slice::from_raw_parts_mut(buf_ptr, limited_length) };
+        unsafe {
+            bpf_probe_read_user_buf(current.base.add(iov_offset), dest).map_err(EbpfError::Read)?;
+        };
+        unsafe {
+            BUFFER_OUTPUT.output(ctx, data_buffer, 0);
+        }
+        total += limited_length;
+        iov_offset += limited_length;
+    }
+    Ok(total)
+}
+
+/// Reads the first `count` strings pointed to by `args` and sends each to userspace as its own
+/// chunk, including the null terminator. A string that could not be read is sent empty.
+///
+/// Returns the number of bytes sent, and the first read error if there was one
+fn read_strings_and_send<const N: usize>(
+    ctx: &RawTracePointContext,
+    args: &[u64; N],
+    count: usize,
+    event_id: EventID,
+    syscall_id: u64,
+) -> Result<usize, (usize, EbpfError)> {
+    let data_buffer = unsafe {
+        match DATA_BUFFER.get_ptr_mut(0) {
+            Some(ptr) => &mut *ptr,
+            None => return Err((0, EbpfError::Map)),
+        }
+    };
+    data_buffer.pid = event_id.pid;
+    data_buffer.tgid = event_id.tgid;
+    data_buffer.timestamp = event_id.timestamp;
+    data_buffer.syscall_id = syscall_id;
+
+    let buf_ptr = data_buffer.data_buffer.as_mut_ptr();
+
+    let mut total = 0;
+    let mut error = None;
+    for (i, &arg) in args.iter().enumerate() {
+        if i >= count {
+            break;
+        }
+        let ptr = arg as *const u8;
+        let length = if ptr.is_null() {
+            0
+        } else {
+            // leave room for the terminator
+            let dest = unsafe { core::slice::from_raw_parts_mut(buf_ptr, BUFFER_SIZE - 1) };
+            match unsafe { bpf_probe_read_user_str_bytes(ptr, dest) } {
+                Ok(string) => string.len(),
+                Err(errno) => {
+                    error.get_or_insert(EbpfError::Read(errno));
+                    0
+                }
+            }
+        };
+        let length = length.min(BUFFER_SIZE - 1);
+        data_buffer.data_buffer[length] = 0;
+        data_buffer.offset = total as u64;
+        data_buffer.length = (length + 1) as u64;
+        unsafe {
+            BUFFER_OUTPUT.output(ctx, data_buffer, 0);
+        }
+        total += length + 1;
+    }
+    match error {
+        Some(error) => Err((total, error)),
+        None => Ok(total),
+    }
+}
+
+/// Reads a null-terminated string from `ptr` into the data buffer. It is sent to
+/// userspace with [`send_buffer`].
+fn read_string(
     ptr: *const u8,
     event_id: EventID,
     syscall_id: u64,
@@ -128,17 +577,27 @@ fn read_string_and_send(
     data_buffer.tgid = event_id.tgid;
     data_buffer.timestamp = event_id.timestamp;
     data_buffer.syscall_id = syscall_id;
+    data_buffer.offset = 0;
 
     let buf_ptr = data_buffer.data_buffer.as_mut_ptr();
 
     let dest = unsafe { core::slice::from_raw_parts_mut(buf_ptr, BUFFER_SIZE) };
     let result_slice =
         unsafe { bpf_probe_read_user_str_bytes(ptr, dest).map_err(EbpfError::Read)? };
+    let length = result_slice.len();
+    data_buffer.length = length as u64;
 
-    if !result_slice.is_empty() {
-        unsafe {
-            BUFFER_OUTPUT.output(ctx, data_buffer, 0);
-        }
+    Ok(NonZeroUsize::new(length))
+}
+
+/// Sends the data buffer filled by [`read_string`] to userspace
+fn send_buffer(ctx: &RawTracePointContext) -> Result<(), EbpfError> {
+    let data_buffer = unsafe {
+        let ptr = DATA_BUFFER.get_ptr_mut(0).ok_or(EbpfError::Map)?;
+        &mut *ptr
+    };
+    unsafe {
+        BUFFER_OUTPUT.output(ctx, data_buffer, 0);
     }
-    Ok(NonZeroUsize::new(result_slice.len()))
+    Ok(())
 }
@@ -1,28 +1,71 @@
 #![no_std]
 #![no_main]
 
+mod arch;
 mod handlers;
 mod types;
 
-use blackbox_common::{EventBuffer, SyscallEvent, SyscallID};
-use handlers::sys_read_write_handler;
+use blackbox_common::{
+    EventBuffer, PathPrefix, ProcessExitEvent, SyscallEvent, SyscallID, TraceConfig,
+    MAX_PATH_PREFIXES, MAX_SYSCALL_ID,
+};
+use handlers::{
+    clone3_handler, execve_handler, fd_pair_handler, iovec_handler, offsets_handler,
+    strings_handler, sys_read_write_handler,
+};
 use types::{EbpfError, SysExitCtx};
 
 use aya_bpf::{
-    helpers::bpf_get_current_pid_tgid,
-    macros::{map, raw_tracepoint},
-    maps::{Array, PerfEventArray},
-    programs::RawTracePointContext,
+    helpers::{
+        bpf_get_current_pid_tgid, bpf_get_current_task, bpf_ktime_get_ns, bpf_probe_read_kernel,
+    },
+    macros::{map, raw_tracepoint, tracepoint},
+    maps::{Array, HashMap, PerfEventArray},
+    programs::{RawTracePointContext, TracePointContext},
 };
 
 use crate::{handlers::filename_handler, types::SysEnterCtx};
 
+/// Options set by user-space before tracing starts
+#[map]
+static mut CONFIG: Array<TraceConfig> = Array::with_max_entries(1, 0);
+/// Set to 1 for each system call ID that should not be sent to userspace
+#[map]
+static mut SYSCALL_FILTER: Array<u8> = Array::with_max_entries(MAX_SYSCALL_ID, 0);
+/// Path prefixes for filtering open calls; see [`TraceConfig::path_prefix_count`]
 #[map]
-static mut PIDS: Array<u32> = Array::with_max_entries(128, 0);
+static mut PATH_PREFIXES: Array<PathPrefix> = Array::with_max_entries(MAX_PATH_PREFIXES as u32, 0);
+/// The system call ID of each thread whose current system call was filtered out on sys_enter,
+/// so that its sys_exit event can be skipped as well
+#[map]
+static mut SKIPPED: HashMap<u32, u64> = HashMap::with_max_entries(4096, 0);
+/// The thread group IDs (user-space PIDs) of the traced processes. This is
+/// populated with the initial process by user-space, and children are added
+/// when they are forked. Entries are removed when the last thread of a process exits.
+#[map]
+static mut TRACED: HashMap<u32, u8> = HashMap::with_max_entries(1024, 0);
 #[map]
 static mut EVENT_OUTPUT: PerfEventArray<SyscallEvent> = PerfEventArray::new(0);
 #[map]
 static mut BUFFER_OUTPUT: PerfEventArray<EventBuffer> = PerfEventArray::new(0);
+#[map]
+static mut PROCESS_OUTPUT: PerfEventArray<ProcessExitEvent> = PerfEventArray::new(0);
+
+/// The offset of `child_pid` in the sched_process_fork tracepoint format
+const SCHED_PROCESS_FORK_CHILD_PID_OFFSET: usize = 44;
+
+/// Returns whether the thread group `tgid` is being traced
+fn is_traced(tgid: u32) -> bool {
+    unsafe { TRACED.get(&tgid) }.is_some()
+}
+
+/// Returns whether events for the system call `id` should not be sent to userspace
+fn is_filtered(id: u64) -> bool {
+    match unsafe { SYSCALL_FILTER.get(id as u32) } {
+        Some(filtered) => *filtered != 0,
+        None => false,
+    }
+}
 
 #[raw_tracepoint(tracepoint = "sys_enter")]
 pub fn handle_sys_enter(ctx: RawTracePointContext) -> i32 {
@@ -40,29 +83,74 @@ fn try_handle_sys_enter(ctx: &RawTracePointContext) -> Result<(), EbpfError> {
     // this is more like the PID in user-space
     let tgid = (pid_tgid >> 32) as u32;
 
-    // TODO: handle multiple PIDS
-    let traced_pid = *unsafe { PIDS.get(0) }.ok_or(EbpfError::Map)?;
-
-    if tgid != traced_pid {
+    if !is_traced(tgid) {
         return Ok(());
     }
 
     let typed_ctx = SysEnterCtx::try_from(ctx)?;
     let syscall_event = SyscallEvent::try_from(&typed_ctx)?;
 
+    if is_filtered(typed_ctx.id) {
+        return Ok(());
+    }
+
     match typed_ctx.id.into() {
         SyscallID::Read => send_event(ctx, &syscall_event),
         SyscallID::Write => sys_read_write_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::PRead64 => send_event(ctx, &syscall_event),
+        SyscallID::PWrite64 => sys_read_write_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::ReadV => send_event(ctx, &syscall_event),
+        SyscallID::WriteV => iovec_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::PReadV => send_event(ctx, &syscall_event),
+        SyscallID::PWriteV => iovec_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::PReadV2 => send_event(ctx, &syscall_event),
+        SyscallID::PWriteV2 => iovec_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::SendFile => offsets_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Splice => offsets_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::CopyFileRange => offsets_handler(ctx, syscall_event).map(|_| ()),
         SyscallID::Open => filename_handler(ctx, syscall_event).map(|_| ()),
         SyscallID::OpenAt => filename_handler(ctx, syscall_event).map(|_| ()),
         SyscallID::Creat => filename_handler(ctx, syscall_event).map(|_| ()),
         SyscallID::Close => send_event(ctx, &syscall_event),
         SyscallID::Socket => send_event(ctx, &syscall_event),
         SyscallID::Shutdown => send_event(ctx, &syscall_event),
+        SyscallID::Pipe => send_event(ctx, &syscall_event),
+        SyscallID::Pipe2 => send_event(ctx, &syscall_event),
+        SyscallID::SocketPair => send_event(ctx, &syscall_event),
+        SyscallID::EventFd => send_event(ctx, &syscall_event),
+        SyscallID::EventFd2 => send_event(ctx, &syscall_event),
+        SyscallID::Dup => send_event(ctx, &syscall_event),
+        SyscallID::Dup2 => send_event(ctx, &syscall_event),
+        SyscallID::Dup3 => send_event(ctx, &syscall_event),
+        SyscallID::GetDents => send_event(ctx, &syscall_event),
+        SyscallID::GetDents64 => send_event(ctx, &syscall_event),
+        SyscallID::Stat => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::LStat => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::NewFStatAt => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Statx => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Access => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::FAccessAt => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::FAccessAt2 => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::ReadLink => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::ReadLinkAt => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::InitModule => strings_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::FInitModule => strings_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::DeleteModule => strings_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Bpf => send_event(ctx, &syscall_event),
+        SyscallID::Mount => strings_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Umount2 => strings_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::PivotRoot => strings_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Chroot => strings_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Unshare => send_event(ctx, &syscall_event),
+        SyscallID::SetNs => send_event(ctx, &syscall_event),
+        SyscallID::KexecLoad => send_event(ctx, &syscall_event),
+        SyscallID::KexecFileLoad => strings_handler(ctx, syscall_event).map(|_| ()),
         SyscallID::Fork => send_event(ctx, &syscall_event),
         SyscallID::Clone => send_event(ctx, &syscall_event),
-        SyscallID::Execve => filename_handler(ctx, syscall_event).map(|_| ()),
-        SyscallID::ExecveAt => filename_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Clone3 => clone3_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::VFork => send_event(ctx, &syscall_event),
+        SyscallID::Execve => execve_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::ExecveAt => execve_handler(ctx, syscall_event).map(|_| ()),
         SyscallID::Exit => send_event(ctx, &syscall_event),
         SyscallID::ExitGroup => send_event(ctx, &syscall_event),
         SyscallID::Unhandled => send_event(ctx, &syscall_event),
@@ -85,27 +173,79 @@ fn try_handle_sys_exit(ctx: &RawTracePointContext) -> Result<(), EbpfError> {
     let pid_tgid = bpf_get_current_pid_tgid();
     // this is more like the PID in user-space
     let tgid = (pid_tgid >> 32) as u32;
+    // this is more like the thread ID in user-space
+    let pid = pid_tgid as u32;
 
-    // TODO: handle multiple PIDS
-    let traced_pid = *unsafe { PIDS.get(0) }.ok_or(EbpfError::Map)?;
-
-    if tgid != traced_pid {
+    if !is_traced(tgid) {
         return Ok(());
     }
     let typed_ctx = SysExitCtx::try_from(ctx)?;
+    if is_filtered(typed_ctx.id) {
+        return Ok(());
+    }
+    if let Some(&skipped_id) = unsafe { SKIPPED.get(&pid) } {
+        let _ = unsafe { SKIPPED.remove(&pid) };
+        if skipped_id == typed_ctx.id {
+            return Ok(());
+        }
+    }
     let syscall_event = SyscallEvent::try_from(&typed_ctx)?;
 
     match typed_ctx.id.into() {
         SyscallID::Read => sys_read_write_handler(ctx, syscall_event).map(|_| ()),
         SyscallID::Write => send_event(ctx, &syscall_event),
+        SyscallID::PRead64 => sys_read_write_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::PWrite64 => send_event(ctx, &syscall_event),
+        SyscallID::ReadV => iovec_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::WriteV => send_event(ctx, &syscall_event),
+        SyscallID::PReadV => iovec_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::PWriteV => send_event(ctx, &syscall_event),
+        SyscallID::PReadV2 => iovec_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::PWriteV2 => send_event(ctx, &syscall_event),
+        SyscallID::SendFile => send_event(ctx, &syscall_event),
+        SyscallID::Splice => send_event(ctx, &syscall_event),
+        SyscallID::CopyFileRange => send_event(ctx, &syscall_event),
         SyscallID::Open => send_event(ctx, &syscall_event),
         SyscallID::OpenAt => send_event(ctx, &syscall_event),
         SyscallID::Creat => send_event(ctx, &syscall_event),
         SyscallID::Close => send_event(ctx, &syscall_event),
         SyscallID::Socket => send_event(ctx, &syscall_event),
         SyscallID::Shutdown => send_event(ctx, &syscall_event),
+        SyscallID::Pipe => fd_pair_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::Pipe2 => fd_pair_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::SocketPair => fd_pair_handler(ctx, syscall_event).map(|_| ()),
+        SyscallID::EventFd => send_event(ctx, &syscall_event),
+        SyscallID::EventFd2 => send_event(ctx, &syscall_event),
+        SyscallID::Dup => send_event(ctx, &syscall_event),
+        SyscallID::Dup2 => send_event(ctx, &syscall_event),
+        SyscallID::Dup3 => send_event(ctx, &syscall_event),
+        SyscallID::GetDents => send_event(ctx, &syscall_event),
+        SyscallID::GetDents64 => send_event(ctx, &syscall_event),
+        SyscallID::Stat => send_event(ctx, &syscall_event),
+        SyscallID::LStat => send_event(ctx, &syscall_event),
+        SyscallID::NewFStatAt => send_event(ctx, &syscall_event),
+        SyscallID::Statx => send_event(ctx, &syscall_event),
+        SyscallID::Access => send_event(ctx, &syscall_event),
+        SyscallID::FAccessAt => send_event(ctx, &syscall_event),
+        SyscallID::FAccessAt2 => send_event(ctx, &syscall_event),
+        SyscallID::ReadLink => send_event(ctx, &syscall_event),
+        SyscallID::ReadLinkAt => send_event(ctx, &syscall_event),
+        SyscallID::InitModule => send_event(ctx, &syscall_event),
+        SyscallID::FInitModule => send_event(ctx, &syscall_event),
+        SyscallID::DeleteModule => send_event(ctx, &syscall_event),
+        SyscallID::Bpf => send_event(ctx, &syscall_event),
+        SyscallID::Mount => send_event(ctx, &syscall_event),
+        SyscallID::Umount2 => send_event(ctx, &syscall_event),
+        SyscallID::PivotRoot => send_event(ctx, &syscall_event),
+        SyscallID::Chroot => send_event(ctx, &syscall_event),
+        SyscallID::Unshare => send_event(ctx, &syscall_event),
+        SyscallID::SetNs => send_event(ctx, &syscall_event),
+        SyscallID::KexecLoad => send_event(ctx, &syscall_event),
+        SyscallID::KexecFileLoad => send_event(ctx, &syscall_event),
         SyscallID::Fork => send_event(ctx, &syscall_event),
         SyscallID::Clone => send_event(ctx, &syscall_event),
+        SyscallID::Clone3 => send_event(ctx, &syscall_event),
+        SyscallID::VFork => send_event(ctx, &syscall_event),
         SyscallID::Execve => send_event(ctx, &syscall_event),
         SyscallID::ExecveAt => send_event(ctx, &syscall_event),
         SyscallID::Exit => send_event(ctx, &syscall_event),
@@ -120,6 +260,108 @@ fn send_event(ctx: &RawTracePointContext, event: &SyscallEvent) -> Result<(), Eb
     Ok(())
 }
 
+#[tracepoint(name = "sched_process_fork")]
+pub fn handle_sched_process_fork(ctx: TracePointContext) -> u32 {
+    match try_handle_sched_process_fork(&ctx) {
+        Ok(()) => 0,
+        Err(err) => {
+            err.log(&ctx);
+            1
+        }
+    }
+}
+
+fn try_handle_sched_process_fork(ctx: &TracePointContext) -> Result<(), EbpfError> {
+    // this runs in the context of the parent
+    let tgid = (bpf_get_current_pid_tgid() >> 32) as u32;
+    if !is_traced(tgid) {
+        return Ok(());
+    }
+    let child_pid: u32 =
+        unsafe { ctx.read_at(SCHED_PROCESS_FORK_CHILD_PID_OFFSET) }.map_err(EbpfError::Read)?;
+    // new threads are added as well, and removed again when they exit
+    unsafe { TRACED.insert(&child_pid, &1, 0) }.map_err(|_| EbpfError::Map)
+}
+
+#[tracepoint(name = "sched_process_exit")]
+pub fn handle_sched_process_exit(ctx: TracePointContext) -> u32 {
+    match try_handle_sched_process_exit(&ctx) {
+        Ok(()) => 0,
+        Err(err) => {
+            err.log(&ctx);
+            1
+        }
+    }
+}
+
+fn try_handle_sched_process_exit(ctx: &TracePointContext) -> Result<(), EbpfError> {
+    let pid_tgid = bpf_get_current_pid_tgid();
+    // this is more like the PID in user-space
+    let tgid = (pid_tgid >> 32) as u32;
+    // this is more like the thread ID in user-space
+    let pid = pid_tgid as u32;
+
+    if !is_traced(tgid) {
+        return Ok(());
+    }
+    if pid != tgid {
+        let _ = unsafe { TRACED.remove(&pid) };
+    }
+    let config = unsafe { CONFIG.get(0) }.ok_or(EbpfError::Map)?;
+    // the exiting task is the current one
+    let task = unsafe { bpf_get_current_task() } as *const u8;
+    if !is_group_dead(task, pid == tgid, config)? {
+        return Ok(());
+    }
+
+    let mut event = ProcessExitEvent {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        tgid,
+        pid,
+        exit_code: None,
+        signal: None,
+    };
+    let exit_code_offset = config.exit_code_offset as usize;
+    if exit_code_offset != 0 {
+        // the exit code has been set by now. It is encoded like a wait status: the status in
+        // bits 8-15 or the signal in bits 0-6
+        let exit_code: i32 = unsafe {
+            bpf_probe_read_kernel(task.add(exit_code_offset) as *const i32)
+                .map_err(EbpfError::Read)?
+        };
+        match exit_code & 0x7f {
+            0 => event.exit_code = Some((exit_code >> 8) & 0xff),
+            signal => event.signal = Some(signal),
+        }
+    }
+    unsafe { PROCESS_OUTPUT.output(ctx, &event, 0) };
+
+    let _ = unsafe { TRACED.remove(&tgid) };
+    Ok(())
+}
+
+/// Returns whether the exiting `task` is the last thread of its thread group. The kernel
+/// decrements `signal->live` before the `sched_process_exit` tracepoint, so it is zero once
+/// every thread has reached it. If the offsets needed are not known, whether the task is the
+/// group leader is returned instead
+fn is_group_dead(
+    task: *const u8,
+    is_leader: bool,
+    config: &TraceConfig,
+) -> Result<bool, EbpfError> {
+    if config.signal_offset == 0 || config.live_offset == 0 {
+        return Ok(is_leader);
+    }
+    let live: i32 = unsafe {
+        let signal: *const u8 =
+            bpf_probe_read_kernel(task.add(config.signal_offset as usize) as *const *const u8)
+                .map_err(EbpfError::Read)?;
+        bpf_probe_read_kernel(signal.add(config.live_offset as usize) as *const i32)
+            .map_err(EbpfError::Read)?
+    };
+    Ok(live == 0)
+}
+
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     unsafe { core::hint::unreachable_unchecked() }
@@ -1,14 +1,15 @@
 #![allow(dead_code)]
 use aya_bpf::{
-    bindings::pt_regs,
     cty::{c_uint, c_ulong},
-    helpers::{bpf_get_current_pid_tgid, bpf_ktime_get_ns, bpf_probe_read, bpf_probe_read_kernel},
+    helpers::{bpf_get_current_pid_tgid, bpf_ktime_get_ns, bpf_probe_read_kernel},
     programs::RawTracePointContext,
     BpfContext,
 };
 use aya_log_ebpf::error;
 use blackbox_common::SyscallEvent;
 
+use crate::arch::{self, SyscallRegs};
+
 /// A general error enum for error handling
 #[derive(Clone, Copy)]
 pub enum EbpfError {
@@ -25,7 +26,7 @@ pub enum EbpfError {
 }
 
 impl EbpfError {
-    pub fn log(self, ctx: &RawTracePointContext) {
+    pub fn log<C: BpfContext>(self, ctx: &C) {
         match self {
             EbpfError::Read(errno) => {
                 error!(ctx, "Failed to read address in eBPF handler: {}", errno)
@@ -45,7 +46,7 @@ impl EbpfError {
 
 /// Parsed arguments passed to the sys_enter raw tracepoint
 pub struct SysEnterCtx {
-    pub regs: *mut pt_regs,
+    pub regs: *mut SyscallRegs,
     pub id: c_ulong,
 }
 
@@ -53,7 +54,7 @@ impl TryFrom<&RawTracePointContext> for SysEnterCtx {
     type Error = EbpfError;
     fn try_from(ctx: &RawTracePointContext) -> Result<Self, Self::Error> {
         let pt_regs_ptr = unsafe {
-            bpf_probe_read_kernel(ctx.as_ptr() as *const *mut pt_regs).map_err(EbpfError::Read)
+            bpf_probe_read_kernel(ctx.as_ptr() as *const *mut SyscallRegs).map_err(EbpfError::Read)
         }?;
 
         let address = unsafe { (ctx.as_ptr() as *const c_ulong).offset(1) };
@@ -68,22 +69,13 @@ impl TryFrom<&RawTracePointContext> for SysEnterCtx {
 impl SysEnterCtx {
     /// gets a function argument from the registers with an index `n`
     pub fn arg(&self, n: usize) -> Result<c_ulong, EbpfError> {
-        let ctx = unsafe { &*self.regs };
-        match n {
-            0 => unsafe { bpf_probe_read(&ctx.rdi).map_err(|_| EbpfError::Arg(n)) },
-            1 => unsafe { bpf_probe_read(&ctx.rsi).map_err(|_| EbpfError::Arg(n)) },
-            2 => unsafe { bpf_probe_read(&ctx.rdx).map_err(|_| EbpfError::Arg(n)) },
-            3 => unsafe { bpf_probe_read(&ctx.rcx).map_err(|_| EbpfError::Arg(n)) },
-            4 => unsafe { bpf_probe_read(&ctx.r8).map_err(|_| EbpfError::Arg(n)) },
-            5 => unsafe { bpf_probe_read(&ctx.r9).map_err(|_| EbpfError::Arg(n)) },
-            _ => Err(EbpfError::Logic),
-        }
+        unsafe { arch::enter_arg(self.regs, n) }
     }
 }
 
 /// Parsed arguments passed to the sys_exit raw tracepoint
 pub struct SysExitCtx {
-    pub regs: *mut pt_regs,
+    pub regs: *mut SyscallRegs,
     pub id: c_ulong,
     pub ret: c_ulong,
 }
@@ -92,11 +84,10 @@ impl TryFrom<&RawTracePointContext> for SysExitCtx {
     type Error = EbpfError;
     fn try_from(ctx: &RawTracePointContext) -> Result<Self, Self::Error> {
         let pt_regs_ptr = unsafe {
-            bpf_probe_read_kernel(ctx.as_ptr() as *const *mut pt_regs).map_err(EbpfError::Read)
+            bpf_probe_read_kernel(ctx.as_ptr() as *const *mut SyscallRegs).map_err(EbpfError::Read)
         }?;
 
-        let id = unsafe { bpf_probe_read(&(*pt_regs_ptr).orig_rax).map_err(EbpfError::Read)? }
-            as c_ulong;
+        let id = unsafe { arch::syscall_number(pt_regs_ptr)? };
 
         let address = unsafe { (ctx.as_ptr() as *const c_ulong).offset(1) };
         let ret = unsafe { bpf_probe_read_kernel(address).map_err(EbpfError::Read) }?;
@@ -111,25 +102,26 @@ impl TryFrom<&RawTracePointContext> for SysExitCtx {
 impl SysExitCtx {
     /// gets a function argument from the registers with an index `n`
     pub fn arg(&self, n: usize) -> Result<c_ulong, EbpfError> {
-        let ctx = unsafe { &*self.regs };
-        match n {
-            0 => unsafe { bpf_probe_read(&ctx.rdi).map_err(|_| EbpfError::Arg(n)) },
-            1 => unsafe { bpf_probe_read(&ctx.rsi).map_err(|_| EbpfError::Arg(n)) },
-            2 => unsafe { bpf_probe_read(&ctx.rdx).map_err(|_| EbpfError::Arg(n)) },
-            3 => unsafe { bpf_probe_read(&ctx.rcx).map_err(|_| EbpfError::Arg(n)) },
-            4 => unsafe { bpf_probe_read(&ctx.r8).map_err(|_| EbpfError::Arg(n)) },
-            5 => unsafe { bpf_probe_read(&ctx.r9).map_err(|_| EbpfError::Arg(n)) },
-            _ => Err(EbpfError::Logic),
-        }
+        unsafe { arch::exit_arg(self.regs, n) }
     }
 
     /// gets a function return value from the arguments
     pub fn ret(&self) -> Result<c_ulong, EbpfError> {
-        let ctx = unsafe { &*self.regs };
-        unsafe { bpf_probe_read(&ctx.rax).map_err(EbpfError::Read) }
+        unsafe { arch::return_value(self.regs) }
     }
 }
 
+/// The maximum number of iovecs read for a single readv or writev call
+pub const MAX_IOVECS: usize = 64;
+
+/// A `struct iovec`, as passed to readv and writev
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct IoVec {
+    pub base: *const u8,
+    pub length: usize,
+}
+
 /// Arguments for the sys_read and sys_write args
 pub struct SysReadArgs {
     pub fd: c_uint,
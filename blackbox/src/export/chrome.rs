@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use blackbox_common::syscall_names::syscall_name;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{self, Receiver};
+use tokio::task::JoinHandle;
+
+use crate::processing::collect_commands;
+use crate::types::{ProcessingData, SyscallData, TraceEvent};
+
+use super::strace::strace_call;
+
+/// Forwards the events from `rx` to the returned receiver, keeping a copy of each, because
+/// processing does not keep the individual system calls. The task returns the copies once
+/// `rx` is closed
+pub fn record_events(
+    mut rx: Receiver<TraceEvent>,
+) -> (Receiver<TraceEvent>, JoinHandle<Vec<TraceEvent>>) {
+    let (tx, forwarded) = mpsc::channel(100);
+    let recorder = tokio::spawn(async move {
+        let mut events = vec![];
+        while let Some(event) = rx.recv().await {
+            events.push(event.clone());
+            // keep recording even if the events are no longer processed
+            let _ = tx.send(event).await;
+        }
+        events
+    });
+    (forwarded, recorder)
+}
+
+/// The trace in the Chrome Trace Event format, which can be opened in ui.perfetto.dev or
+/// chrome://tracing.
+///
+/// Each system call is a slice on the track of its thread, and each file access and
+/// connection is an async slice for as long as it was open. Process exits and alerts are
+/// instant events. Timestamps are the monotonic time in microseconds
+pub fn chrome_trace(events: &[TraceEvent], data: &ProcessingData) -> Value {
+    let mut trace_events = vec![];
+
+    let mut commands = HashMap::new();
+    collect_commands(&data.process_tree, &mut commands);
+    let mut pids = events
+        .iter()
+        .filter(|event| !matches!(event.data, SyscallData::LostEvents(_)))
+        .map(|event| event.pid)
+        .collect::<Vec<_>>();
+    pids.sort();
+    pids.dedup();
+    for pid in pids {
+        let name = match commands.get(&pid) {
+            Some(command) => format!("{command} ({pid})"),
+            None => format!("Process {pid}"),
+        };
+        trace_events.push(json!({
+            "name": "process_name",
+            "ph": "M",
+            "pid": pid,
+            "args": { "name": name },
+        }));
+    }
+
+    for event in events {
+        let name = syscall_name(event.syscall_id).unwrap_or("unknown");
+        let start = microseconds(event.monotonic_enter_timestamp);
+        let trace_event = match &event.data {
+            SyscallData::ProcessExit(_) => json!({
+                "name": "exit",
+                "cat": "process",
+                "ph": "i",
+                "s": "t",
+                "ts": start,
+                "pid": event.pid,
+                "tid": event.thread_id,
+                "args": { "status": strace_call(event) },
+            }),
+            SyscallData::Unpaired(_) => json!({
+                "name": format!("{name} (unpaired)"),
+                "cat": "syscall",
+                "ph": "i",
+                "s": "t",
+                "ts": start,
+                "pid": event.pid,
+                "tid": event.thread_id,
+                "args": { "call": strace_call(event) },
+            }),
+            // lost events have no time; they are shown at the start of the trace
+            SyscallData::LostEvents(lost) => json!({
+                "name": format!("{} events lost", lost.count),
+                "cat": "trace",
+                "ph": "i",
+                "s": "g",
+                "ts": microseconds(data.trace_start),
+                "pid": 0,
+                "tid": 0,
+                "args": { "map": lost.map, "cpu": lost.cpu },
+            }),
+            _ => json!({
+                "name": name,
+                "cat": "syscall",
+                "ph": "X",
+                "ts": start,
+                "dur": microseconds(
+                    event
+                        .monotonic_exit_timestamp
+                        .saturating_sub(event.monotonic_enter_timestamp)
+                ),
+                "pid": event.pid,
+                "tid": event.thread_id,
+                "args": { "call": strace_call(event) },
+            }),
+        };
+        trace_events.push(trace_event);
+    }
+
+    for (id, access) in data.file_events.iter().enumerate() {
+        let name = match &access.file_name {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => format!("File descriptor {}", access.file_descriptor),
+        };
+        let args = json!({
+            "file_descriptor": access.file_descriptor,
+            "data_length": access.data_length,
+            "errors": access.error_count,
+        });
+        async_slice(
+            &mut trace_events,
+            "file",
+            format!("file-{id}"),
+            &name,
+            access.pid,
+            (access.start_time, access.end_time),
+            args,
+        );
+    }
+    for (id, conn) in data.network_events.iter().enumerate() {
+        let name = format!("{} {}", conn.protocol.text(), conn.domain.text());
+        async_slice(
+            &mut trace_events,
+            "network",
+            format!("connection-{id}"),
+            &name,
+            conn.pid,
+            (conn.start_time, conn.end_time),
+            json!({}),
+        );
+    }
+
+    for alert in &data.alerts {
+        let (Some(pid), Some(time)) = (alert.pid, alert.time) else {
+            continue;
+        };
+        trace_events.push(json!({
+            "name": alert.message,
+            "cat": "alert",
+            "ph": "i",
+            "s": "p",
+            "ts": microseconds(time),
+            "pid": pid,
+            "tid": pid,
+            "args": { "rule": alert.rule.id() },
+        }));
+    }
+
+    json!({
+        "traceEvents": trace_events,
+        "displayTimeUnit": "ns",
+    })
+}
+
+/// Adds the begin and end events of an async slice. Slices that were never recorded as
+/// ending take no time
+fn async_slice(
+    trace_events: &mut Vec<Value>,
+    category: &str,
+    id: String,
+    name: &str,
+    pid: u32,
+    (start, end): (u64, u64),
+    args: Value,
+) {
+    if start == 0 {
+        return;
+    }
+    trace_events.push(json!({
+        "name": name,
+        "cat": category,
+        "ph": "b",
+        "id": id,
+        "ts": microseconds(start),
+        "pid": pid,
+        "tid": pid,
+        "args": args,
+    }));
+    trace_events.push(json!({
+        "name": name,
+        "cat": category,
+        "ph": "e",
+        "id": id,
+        "ts": microseconds(end.max(start)),
+        "pid": pid,
+        "tid": pid,
+    }));
+}
+
+/// Converts nanoseconds to the microseconds used by the format, keeping the precision
+fn microseconds(nanoseconds: u64) -> f64 {
+    nanoseconds as f64 / 1000.0
+}
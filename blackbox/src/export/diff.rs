@@ -0,0 +1,90 @@
+use std::ffi::OsString;
+use std::fmt::Write;
+
+use crate::types::{Alert, ConnectionDomain, ConnectionProtocol, TraceDiff};
+
+/// The differences between two traces as text, in the style of a unified diff: lines starting
+/// with `+` are only in the newer trace `after`, and lines starting with `-` only in the older
+/// trace `before`
+pub fn diff_text(diff: &TraceDiff, before: &str, after: &str) -> String {
+    let mut out = format!("--- {before}\n+++ {after}\n");
+    if diff.is_empty() {
+        out.push_str("No differences in files, programs, connections or alerts\n");
+    }
+    write_paths(&mut out, "Files", &diff.added_files, &diff.removed_files);
+    write_paths(
+        &mut out,
+        "Directories",
+        &diff.added_directories,
+        &diff.removed_directories,
+    );
+    write_paths(
+        &mut out,
+        "Programs executed",
+        &diff.added_programs,
+        &diff.removed_programs,
+    );
+    let endpoint_lines = |endpoints: &[(ConnectionDomain, ConnectionProtocol)], sign: char| {
+        endpoints
+            .iter()
+            .map(|(domain, protocol)| format!("{sign} {} {}", protocol.text(), domain.text()))
+            .collect()
+    };
+    write_section(
+        &mut out,
+        "Connections",
+        endpoint_lines(&diff.added_endpoints, '+'),
+        endpoint_lines(&diff.removed_endpoints, '-'),
+    );
+    let alert_lines = |alerts: &[Alert], sign: char| {
+        alerts
+            .iter()
+            .map(|alert| format!("{sign} [{}] {}", alert.rule.id(), alert.message))
+            .collect()
+    };
+    write_section(
+        &mut out,
+        "Alerts",
+        alert_lines(&diff.added_alerts, '+'),
+        alert_lines(&diff.removed_alerts, '-'),
+    );
+
+    let _ = writeln!(out, "\nBytes transferred:");
+    for change in &diff.byte_changes {
+        let _ = writeln!(
+            out,
+            "  {}: {} -> {} ({:+})",
+            change.path.to_string_lossy(),
+            change.before,
+            change.after,
+            change.after as i64 - change.before as i64
+        );
+    }
+    let _ = writeln!(
+        out,
+        "  Total read: {} -> {}\n  Total written: {} -> {}",
+        diff.bytes_read.0, diff.bytes_read.1, diff.bytes_written.0, diff.bytes_written.1
+    );
+    out
+}
+
+fn write_paths(out: &mut String, title: &str, added: &[OsString], removed: &[OsString]) {
+    let lines = |paths: &[OsString], sign: char| {
+        paths
+            .iter()
+            .map(|path| format!("{sign} {}", path.to_string_lossy()))
+            .collect()
+    };
+    write_section(out, title, lines(added, '+'), lines(removed, '-'));
+}
+
+/// Writes the added lines, then the removed lines. Sections without changes are left out
+fn write_section(out: &mut String, title: &str, added: Vec<String>, removed: Vec<String>) {
+    if added.is_empty() && removed.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "\n{title}:");
+    for line in added.iter().chain(&removed) {
+        let _ = writeln!(out, "  {line}");
+    }
+}
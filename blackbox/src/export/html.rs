@@ -0,0 +1,487 @@
+use std::fmt::Write;
+
+use crate::processing::describe_payload;
+use crate::types::{
+    duration, namespace_flags_text, AccessType, Alert, Connection, ExitStatus, FileAccess,
+    FileSummary, IpcEndpoint, NetworkSummary, PayloadFormat, ProcessNode, ProcessSummary,
+    ProcessingData, TraceQuality, WallClock,
+};
+
+/// Styles for the report, following the colors of the UI
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 1100px; color: #222; }
+h1 { margin-bottom: 0; }
+h2 { border-bottom: 1px solid #ccc; padding-bottom: 3px; margin-top: 1.5em; }
+.card { background: #f3f3f3; border-radius: 8px; padding: 8px 12px; margin: 8px 0; }
+.alert { border-radius: 5px; padding: 5px 10px; margin: 4px 0; }
+.severity-0 { background: #f4a3a3; }
+.severity-1 { background: #f7c48f; }
+.severity-2 { background: #f3e08f; }
+.severity-3 { background: #e6e6e6; }
+.severity-other { background: #b5e3a8; }
+.warning { background: #f7c48f; }
+.chip { display: inline-block; font-size: 0.8em; font-weight: bold; background: #ddd;
+    border-radius: 10px; padding: 2px 8px; margin-right: 4px; }
+code, pre { font-family: monospace; }
+pre { background: #fff; border: 1px solid #ddd; border-radius: 4px; padding: 6px;
+    overflow-x: auto; white-space: pre-wrap; word-break: break-all; }
+summary { cursor: pointer; }
+table { border-collapse: collapse; }
+td { padding: 2px 12px 2px 0; vertical-align: top; }
+.children { margin-left: 30px; }
+";
+
+/// A self-contained HTML page with the same sections as the UI: the alerts, then the
+/// summary, file, network and process details. Details are collapsed with `<details>`, so
+/// the report needs no scripts
+pub fn html_report(data: &ProcessingData) -> String {
+    let mut html = String::new();
+    html += "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n";
+    html += "<title>Blackbox Report</title>\n";
+    let _ = writeln!(html, "<style>{STYLE}</style>\n</head>\n<body>");
+    html += "<h1>Blackbox Report</h1>\n";
+    if data.trace_start != 0 {
+        let _ = writeln!(
+            html,
+            "<p>Trace started {}</p>",
+            escape(&data.clock.format(data.trace_start))
+        );
+    }
+
+    html += "<h2>Alerts</h2>\n";
+    if data.alerts.is_empty() {
+        html += "<p>No alerts</p>\n";
+    }
+    for alert in &data.alerts {
+        write_alert(&mut html, alert, data.clock);
+    }
+
+    html += "<h2>Summary</h2>\n";
+    write_file_summary(&mut html, &data.file_summary);
+    write_network_summary(&mut html, &data.network_summary);
+    write_process_summary(&mut html, &data.process_summary);
+    write_trace_quality(&mut html, &data.trace_quality);
+
+    let mut files = data.file_events.iter().collect::<Vec<_>>();
+    files.sort_by_key(|access| access.start_time);
+    let _ = writeln!(html, "<h2>File Access ({})</h2>", files.len());
+    for access in files {
+        write_file_access(&mut html, access, data.clock);
+    }
+
+    let _ = writeln!(html, "<h2>Network ({})</h2>", data.network_events.len());
+    for conn in &data.network_events {
+        write_connection(&mut html, conn, data.clock);
+    }
+
+    html += "<h2>Processes</h2>\n";
+    if !data.ipc_channels.is_empty() {
+        html += "<h3>IPC Channels</h3>\n";
+        for channel in &data.ipc_channels {
+            let endpoints = |endpoints: &[IpcEndpoint]| {
+                if endpoints.is_empty() {
+                    String::from("??")
+                } else {
+                    endpoints
+                        .iter()
+                        .map(|endpoint| escape(&endpoint.text()))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }
+            };
+            let _ = writeln!(
+                html,
+                "<div class=\"card\">{} <code>{}</code> {} → {} ({} bytes written)</div>",
+                chip(&channel.kind.text(), &channel.kind.tooltip()),
+                escape(&channel.name),
+                endpoints(&channel.writers),
+                endpoints(&channel.readers),
+                channel.bytes_written
+            );
+        }
+    }
+    html += "<h3>Process Tree</h3>\n";
+    for node in &data.process_tree {
+        write_process_node(&mut html, node, data.clock);
+    }
+
+    html += "</body>\n</html>\n";
+    html
+}
+
+fn write_alert(html: &mut String, alert: &Alert, clock: WallClock) {
+    let class = match alert.severity {
+        0..=3 => format!("severity-{}", alert.severity),
+        _ => String::from("severity-other"),
+    };
+    let mut source = vec![];
+    if let Some(pid) = alert.pid {
+        source.push(format!("process {pid}"));
+    }
+    if let Some(time) = alert.time {
+        source.push(clock.format(time));
+    }
+    let _ = write!(
+        html,
+        "<div class=\"alert {class}\">{} {}",
+        chip(alert.rule.id(), &alert.rule.tooltip()),
+        escape(&alert.message)
+    );
+    if !source.is_empty() {
+        let _ = write!(html, " <small>({})</small>", escape(&source.join(", ")));
+    }
+    if !alert.paths.is_empty() {
+        let _ = write!(
+            html,
+            "<details><summary>Files ({})</summary>",
+            alert.paths.len()
+        );
+        write_list(
+            html,
+            alert
+                .paths
+                .iter()
+                .map(|path| format!("<code>{}</code>", escape(&path.to_string_lossy()))),
+        );
+        html.push_str("</details>");
+    }
+    html.push_str("</div>\n");
+}
+
+fn write_file_summary(html: &mut String, summary: &FileSummary) {
+    let behavior = [
+        ("Standard I/O", summary.behavior.stdio),
+        ("Current Directory", summary.behavior.current_dir),
+        ("Home Directory", summary.behavior.home_dir),
+        ("System Files", summary.behavior.system),
+        ("Runtime Directories", summary.behavior.runtime),
+    ];
+    html.push_str("<div class=\"card\"><h3>File Access</h3><table>\n");
+    for (name, access) in behavior {
+        let _ = writeln!(
+            html,
+            "<tr><td>{name}</td><td>{}</td></tr>",
+            access_text(access)
+        );
+    }
+    let _ = writeln!(
+        html,
+        "</table><p>Files Accessed: {}<br>Total Bytes Read: {}<br>Total Bytes Written: {}<br>\
+         Paths Probed: {}</p>",
+        summary.access_count, summary.bytes_read, summary.bytes_written, summary.probe_count
+    );
+    let _ = write!(
+        html,
+        "<details><summary>Directories accessed ({})</summary>",
+        summary.directories.len()
+    );
+    write_list(
+        html,
+        summary
+            .directories
+            .iter()
+            .map(|dir| format!("<code>{}</code>", escape(&dir.to_string_lossy()))),
+    );
+    html.push_str("</details></div>\n");
+}
+
+fn write_network_summary(html: &mut String, summary: &NetworkSummary) {
+    let domains = summary
+        .domains
+        .iter()
+        .map(|domain| chip(&domain.text(), &domain.tooltip()))
+        .collect::<String>();
+    let protocols = summary
+        .protocols
+        .iter()
+        .map(|protocol| chip(&protocol.text(), &protocol.tooltip()))
+        .collect::<String>();
+    let _ = writeln!(
+        html,
+        "<div class=\"card\"><h3>Network</h3><p>Total Connections: {}<br>Domains: {}<br>\
+         Protocols: {}</p></div>",
+        summary.connection_count, domains, protocols
+    );
+}
+
+fn write_process_summary(html: &mut String, summary: &ProcessSummary) {
+    let _ = write!(
+        html,
+        "<div class=\"card\"><h3>Processes</h3><p>Exit Status: {}<br>Processes Created: {}<br>\
+         Threads Created: {}<br>Most Common Spawn Type: {}</p>",
+        exit_status_chip(summary.exit_status),
+        summary.processes_created,
+        summary.threads_created,
+        chip(
+            &summary.most_common_spawn_type.text(),
+            &summary.most_common_spawn_type.tooltip()
+        )
+    );
+    let _ = write!(
+        html,
+        "<details><summary>Processes executed ({})</summary>",
+        summary.programs.len()
+    );
+    write_list(
+        html,
+        summary
+            .programs
+            .iter()
+            .map(|program| format!("<code>{}</code>", escape(&program.to_string_lossy()))),
+    );
+    html.push_str("</details></div>\n");
+}
+
+fn write_trace_quality(html: &mut String, quality: &TraceQuality) {
+    if quality.is_complete() {
+        html.push_str("<div class=\"card\"><h3>Trace Quality</h3>All events were received</div>\n");
+        return;
+    }
+    let _ = write!(
+        html,
+        "<div class=\"card warning\"><h3>Trace Quality</h3>Lost Events: {}",
+        quality.lost_count()
+    );
+    write_list(
+        html,
+        quality.lost_events.iter().map(|lost| {
+            format!(
+                "<code>{} on CPU {}: {} lost</code>",
+                escape(&lost.map),
+                lost.cpu,
+                lost.count
+            )
+        }),
+    );
+    let _ = writeln!(
+        html,
+        "Unpaired System Call Events: {}</div>",
+        quality.unpaired_events.len()
+    );
+}
+
+fn write_file_access(html: &mut String, access: &FileAccess, clock: WallClock) {
+    let name = match (access.file_descriptor, &access.file_name) {
+        (0, _) => String::from("Standard In"),
+        (1, _) => String::from("Standard Out"),
+        (2, _) => String::from("Standard Error"),
+        (_, Some(name)) => format!("<code>{}</code>", escape(&name.to_string_lossy())),
+        (_, None) => String::from("No file name recorded"),
+    };
+    let _ = writeln!(
+        html,
+        "<details class=\"card\"><summary>{} {} {} <small>process {}</small></summary>",
+        chip(
+            &access.file_descriptor.to_string(),
+            "The Unix file descriptor for this file"
+        ),
+        name,
+        access_text(access.access_type),
+        access.pid
+    );
+    let _ = writeln!(
+        html,
+        "<p>Total Data Length: {}{}<br>Opened: {}<br>Duration: {}<br>Errors: {}</p>",
+        access.data_length,
+        if access.data_truncated {
+            " (captured data is truncated)"
+        } else {
+            ""
+        },
+        escape(&clock.format(access.start_time)),
+        duration(access.start_time, access.end_time),
+        access.error_count
+    );
+    if access.read_data.is_empty() && access.write_data.is_empty() {
+        html.push_str("<p>No file data recorded.</p>\n");
+    }
+    if let (false, Some(format)) = (access.read_data.is_empty(), access.read_format) {
+        write_payload(html, "Read data", &access.read_data, format);
+    }
+    if let (false, Some(format)) = (access.write_data.is_empty(), access.write_format) {
+        write_payload(html, "Write data", &access.write_data, format);
+    }
+    html.push_str("</details>\n");
+}
+
+/// Embeds captured data as a hex dump, and as text or its description if it has one
+fn write_payload(html: &mut String, label: &str, bytes: &[u8], format: PayloadFormat) {
+    let _ = writeln!(
+        html,
+        "<p>{label}: {} {} bytes</p>",
+        chip(&format.text(), &format.tooltip()),
+        bytes.len()
+    );
+    let readable = match format {
+        PayloadFormat::Binary => describe_payload(bytes, format),
+        _ => Some(
+            describe_payload(bytes, format)
+                .unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned()),
+        ),
+    };
+    if let Some(readable) = readable {
+        let _ = writeln!(html, "<pre>{}</pre>", escape(&readable));
+    }
+    let _ = writeln!(
+        html,
+        "<details><summary>Hex</summary><pre>{}</pre></details>",
+        escape(&hex_dump(bytes))
+    );
+}
+
+fn write_connection(html: &mut String, conn: &Connection, clock: WallClock) {
+    let _ = writeln!(
+        html,
+        "<div class=\"card\">{} {} <small>process {}</small><br>Opened: {}<br>Duration: {}</div>",
+        chip(&conn.protocol.text(), &conn.protocol.tooltip()),
+        chip(&conn.domain.text(), &conn.domain.tooltip()),
+        conn.pid,
+        escape(&clock.format(conn.start_time)),
+        duration(conn.start_time, conn.end_time)
+    );
+}
+
+/// Writes `node` and its children, nested so that each subtree can be collapsed
+fn write_process_node(html: &mut String, node: &ProcessNode, clock: WallClock) {
+    let command = match &node.command {
+        Some(command) => format!("<code>{}</code>", escape(&command.to_string_lossy())),
+        None => format!("Process {}", node.pid),
+    };
+    let spawn_type = match node.spawn_type {
+        Some(spawn_type) => chip(&spawn_type.text(), &spawn_type.tooltip()),
+        None => chip("Traced", "The program that was started by Blackbox"),
+    };
+    let _ = writeln!(
+        html,
+        "<details class=\"card\" open><summary>{} {} {}</summary>",
+        command,
+        chip(&node.pid.to_string(), "The Process ID (PID)"),
+        spawn_type
+    );
+    if !node.arguments.is_empty() {
+        let arguments = node
+            .arguments
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(html, "<pre>{}</pre>", escape(&arguments));
+    }
+    let _ = write!(
+        html,
+        "<p>Started: {} / Lifetime: {}<br>Exit Status: {}<br>",
+        escape(&clock.format(node.start_time)),
+        match node.end_time {
+            Some(end_time) => duration(node.start_time, end_time),
+            None => String::from("??"),
+        },
+        exit_status_chip(node.exit_status)
+    );
+    if node.namespaces != 0 {
+        let _ = write!(
+            html,
+            "New Namespaces: {}<br>",
+            escape(&namespace_flags_text(node.namespaces))
+        );
+    }
+    let _ = writeln!(
+        html,
+        "Files: {} ({} bytes) / Connections: {} / Alerts: {}</p>",
+        node.file_count, node.bytes_transferred, node.connection_count, node.alert_count
+    );
+    if !node.children.is_empty() {
+        html.push_str("<div class=\"children\">\n");
+        for child in &node.children {
+            write_process_node(html, child, clock);
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</details>\n");
+}
+
+fn write_list(html: &mut String, items: impl Iterator<Item = String>) {
+    html.push_str("<ul>");
+    for item in items {
+        let _ = write!(html, "<li>{item}</li>");
+    }
+    html.push_str("</ul>\n");
+}
+
+fn chip(text: &str, tooltip: &str) -> String {
+    format!(
+        "<span class=\"chip\" title=\"{}\">{}</span>",
+        escape(tooltip),
+        escape(text)
+    )
+}
+
+fn exit_status_chip(status: Option<ExitStatus>) -> String {
+    match status {
+        Some(status) => chip(&status.text(), &status.tooltip()),
+        None => chip(
+            "Not recorded",
+            "The process did not exit while it was traced",
+        ),
+    }
+}
+
+/// The access types, such as `read, write`, or `none`
+fn access_text(access: AccessType) -> String {
+    let names = [
+        (access.read, "read"),
+        (access.write, "write"),
+        (access.execute, "execute"),
+        (access.list, "list"),
+    ]
+    .into_iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| name)
+    .collect::<Vec<_>>();
+    if names.is_empty() {
+        String::from("none")
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Lines of 16 bytes, with the offset, the bytes in hex and the printable ASCII characters
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|b| {
+                    if b.is_ascii_graphic() {
+                        *b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            format!("{:08x}  {hex:<47}  {ascii}\n", line * 16)
+        })
+        .collect()
+}
+
+/// Escapes text to be placed in HTML elements and quoted attributes
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,13 @@
+mod chrome;
+mod diff;
+mod html;
+mod sarif;
+mod sqlite;
+mod strace;
+
+pub use chrome::{chrome_trace, record_events};
+pub use diff::diff_text;
+pub use html::html_report;
+pub use sarif::sarif_report;
+pub use sqlite::write_database;
+pub use strace::strace_line;
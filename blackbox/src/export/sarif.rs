@@ -0,0 +1,178 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::processing::find_node;
+use crate::types::{Alert, AlertRule, ProcessingData};
+
+/// The version of the SARIF format written, and its schema
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+/// The base of relative paths, which are relative to the directory the command was run in
+const WORKING_DIRECTORY: &str = "WORKINGDIR";
+
+/// A SARIF log with a result for each alert, so that traces can be shown by code scanning
+/// tools. `command` is the traced command, which ran in `working_dir`.
+///
+/// Results are located at the files involved in the alert, or else at the program run by
+/// the process that caused it, or else at the traced command
+pub fn sarif_report(data: &ProcessingData, command: &str, working_dir: &Path) -> Value {
+    let alerts = data
+        .alerts
+        .iter()
+        .filter(|alert| alert.rule != AlertRule::NoFindings)
+        .collect::<Vec<_>>();
+    let mut rules: Vec<AlertRule> = vec![];
+    for alert in &alerts {
+        if !rules.contains(&alert.rule) {
+            rules.push(alert.rule);
+        }
+    }
+    let rule_descriptors = rules
+        .iter()
+        .map(|rule| {
+            // a rule always raises alerts of the same severity
+            let severity = alerts
+                .iter()
+                .filter(|alert| alert.rule == *rule)
+                .map(|alert| alert.severity)
+                .min()
+                .unwrap_or(u8::MAX);
+            json!({
+                "id": rule.id(),
+                "name": rule.text(),
+                "shortDescription": { "text": rule.tooltip() },
+                "defaultConfiguration": { "level": level(severity) },
+                "properties": {
+                    "tags": ["security"],
+                    "security-severity": security_severity(severity),
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = alerts
+        .iter()
+        .map(|alert| {
+            let mut result = json!({
+                "ruleId": alert.rule.id(),
+                "ruleIndex": rules.iter().position(|rule| *rule == alert.rule),
+                "level": level(alert.severity),
+                "message": { "text": alert.message },
+                "locations": locations(alert, data, command),
+            });
+            let mut properties = serde_json::Map::new();
+            if let Some(pid) = alert.pid {
+                properties.insert(String::from("pid"), json!(pid));
+            }
+            if let Some(time) = alert.time {
+                properties.insert(String::from("time"), json!(data.clock.format(time)));
+            }
+            if !properties.is_empty() {
+                result["properties"] = Value::Object(properties);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+    let mut invocation = json!({
+        "commandLine": command,
+        "executionSuccessful": data.trace_quality.is_complete(),
+        "workingDirectory": { "uri": directory_uri(working_dir) },
+    });
+    if data.trace_start != 0 {
+        invocation["startTimeUtc"] = json!(data.clock.format(data.trace_start));
+    }
+
+    json!({
+        "version": SARIF_VERSION,
+        "$schema": SARIF_SCHEMA,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "Blackbox",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rule_descriptors,
+                },
+            },
+            "originalUriBaseIds": {
+                WORKING_DIRECTORY: { "uri": directory_uri(working_dir) },
+            },
+            "invocations": [invocation],
+            "results": results,
+        }],
+    })
+}
+
+fn locations(alert: &Alert, data: &ProcessingData, command: &str) -> Vec<Value> {
+    if !alert.paths.is_empty() {
+        return alert
+            .paths
+            .iter()
+            .map(|path| physical_location(path))
+            .collect();
+    }
+    let program = alert
+        .pid
+        .and_then(|pid| find_node(&data.process_tree, pid))
+        .and_then(|node| node.command.as_ref());
+    match program {
+        Some(program) => vec![physical_location(program)],
+        None => vec![json!({
+            "logicalLocations": [{ "fullyQualifiedName": command, "kind": "process" }],
+        })],
+    }
+}
+
+/// A location at a file. Relative paths are relative to the working directory
+fn physical_location(path: &OsStr) -> Value {
+    let artifact = if Path::new(path).is_absolute() {
+        json!({ "uri": format!("file://{}", encode_path(path)) })
+    } else {
+        json!({ "uri": encode_path(path), "uriBaseId": WORKING_DIRECTORY })
+    };
+    json!({ "physicalLocation": { "artifactLocation": artifact } })
+}
+
+/// A `file://` URI for a directory, which ends in a slash so that it can be a base
+fn directory_uri(dir: &Path) -> String {
+    let mut uri = format!("file://{}", encode_path(dir.as_os_str()));
+    if !uri.ends_with('/') {
+        uri.push('/');
+    }
+    uri
+}
+
+/// Percent-encodes a path for a URI, keeping the slashes that separate its components
+fn encode_path(path: &OsStr) -> String {
+    path.as_bytes()
+        .iter()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (*b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The SARIF level of an alert severity
+fn level(severity: u8) -> &'static str {
+    match severity {
+        0 | 1 => "error",
+        2 => "warning",
+        3 => "note",
+        _ => "none",
+    }
+}
+
+/// The score used by code scanning tools to rank security results, from 0 to 10
+fn security_severity(severity: u8) -> &'static str {
+    match severity {
+        0 => "9.0",
+        1 => "7.0",
+        2 => "5.0",
+        3 => "2.0",
+        _ => "0.0",
+    }
+}
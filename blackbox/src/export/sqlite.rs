@@ -0,0 +1,316 @@
+use std::path::Path;
+
+use blackbox_common::syscall_names::syscall_name;
+use rusqlite::{params, Connection, Transaction};
+
+use crate::types::{FileAccess, PayloadFormat, ProcessNode, ProcessingData, TraceEvent};
+
+use super::strace::strace_call;
+
+/// The tables of the database. Every row belongs to a run, so that many traces can be
+/// stored in the same database and compared. Timestamps are monotonic nanoseconds, which
+/// can be compared within a run; `runs.start_time` is the wall-clock time of the first event
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    command TEXT NOT NULL,
+    working_dir TEXT NOT NULL,
+    start_time TEXT,
+    blackbox_version TEXT NOT NULL,
+    lost_events INTEGER NOT NULL,
+    unpaired_events INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS syscalls (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    pid INTEGER NOT NULL,
+    thread_id INTEGER NOT NULL,
+    syscall_id INTEGER NOT NULL,
+    name TEXT,
+    enter_time INTEGER NOT NULL,
+    exit_time INTEGER NOT NULL,
+    call TEXT NOT NULL,
+    data_truncated INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS processes (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    pid INTEGER NOT NULL,
+    parent_pid INTEGER,
+    spawn_type TEXT,
+    command TEXT,
+    arguments TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER,
+    exit_status TEXT,
+    namespaces INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS file_accesses (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    pid INTEGER NOT NULL,
+    file_name TEXT,
+    file_descriptor INTEGER NOT NULL,
+    data_length INTEGER NOT NULL,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    error_count INTEGER NOT NULL,
+    read INTEGER NOT NULL,
+    write INTEGER NOT NULL,
+    execute INTEGER NOT NULL,
+    list INTEGER NOT NULL,
+    ipc_kind TEXT
+);
+CREATE TABLE IF NOT EXISTS payloads (
+    file_access_id INTEGER NOT NULL REFERENCES file_accesses(id),
+    direction TEXT NOT NULL,
+    format TEXT,
+    truncated INTEGER NOT NULL,
+    data BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS connections (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    pid INTEGER NOT NULL,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    domain TEXT NOT NULL,
+    protocol TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS alerts (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    rule TEXT NOT NULL,
+    severity INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    pid INTEGER,
+    time INTEGER,
+    paths TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS syscalls_run ON syscalls(run_id, pid);
+CREATE INDEX IF NOT EXISTS file_accesses_run ON file_accesses(run_id, file_name);
+";
+
+/// Adds a run of `command`, which ran in `working_dir`, to the SQLite database at `path`,
+/// creating the database if it does not exist. `events` are the system calls recorded while
+/// tracing. Returns the ID of the run
+pub fn write_database(
+    path: &Path,
+    events: &[TraceEvent],
+    data: &ProcessingData,
+    command: &str,
+    working_dir: &Path,
+) -> rusqlite::Result<i64> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+
+    let start_time = match data.trace_start {
+        0 => None,
+        start => Some(data.clock.format(start)),
+    };
+    tx.execute(
+        "INSERT INTO runs (command, working_dir, start_time, blackbox_version, lost_events, \
+         unpaired_events) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            command,
+            working_dir.to_string_lossy(),
+            start_time,
+            env!("CARGO_PKG_VERSION"),
+            data.trace_quality.lost_count(),
+            data.trace_quality.unpaired_events.len(),
+        ],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    write_syscalls(&tx, run_id, events)?;
+    write_processes(&tx, run_id, &data.process_tree, None)?;
+    for access in &data.file_events {
+        write_file_access(&tx, run_id, access)?;
+    }
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO connections (run_id, pid, start_time, end_time, domain, protocol) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for conn in &data.network_events {
+            insert.execute(params![
+                run_id,
+                conn.pid,
+                conn.start_time,
+                conn.end_time,
+                conn.domain.text(),
+                conn.protocol.text(),
+            ])?;
+        }
+        let mut insert = tx.prepare(
+            "INSERT INTO alerts (run_id, rule, severity, message, pid, time, paths) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for alert in &data.alerts {
+            let paths = alert
+                .paths
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("\n");
+            insert.execute(params![
+                run_id,
+                alert.rule.id(),
+                alert.severity,
+                alert.message,
+                alert.pid,
+                alert.time,
+                paths,
+            ])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(run_id)
+}
+
+fn write_syscalls(tx: &Transaction, run_id: i64, events: &[TraceEvent]) -> rusqlite::Result<()> {
+    let mut insert = tx.prepare(
+        "INSERT INTO syscalls (run_id, pid, thread_id, syscall_id, name, enter_time, exit_time, \
+         call, data_truncated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for event in events {
+        // SQLite integers are signed, so the IDs of events that are not system calls, such as
+        // PROCESS_EXIT_ID, are stored as negative numbers
+        insert.execute(params![
+            run_id,
+            event.pid,
+            event.thread_id,
+            event.syscall_id as i64,
+            syscall_name(event.syscall_id),
+            event.monotonic_enter_timestamp,
+            event.monotonic_exit_timestamp,
+            strace_call(event),
+            event.data_truncated,
+        ])?;
+    }
+    Ok(())
+}
+
+fn write_processes(
+    tx: &Transaction,
+    run_id: i64,
+    nodes: &[ProcessNode],
+    parent_pid: Option<u32>,
+) -> rusqlite::Result<()> {
+    for node in nodes {
+        // the arguments are separated by newlines, since they rarely contain them
+        let arguments = node
+            .arguments
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("\n");
+        tx.execute(
+            "INSERT INTO processes (run_id, pid, parent_pid, spawn_type, command, arguments, \
+             start_time, end_time, exit_status, namespaces) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                run_id,
+                node.pid,
+                parent_pid,
+                node.spawn_type.map(|spawn_type| spawn_type.text()),
+                node.command
+                    .as_ref()
+                    .map(|command| command.to_string_lossy()),
+                arguments,
+                node.start_time,
+                node.end_time,
+                node.exit_status.map(|status| status.text()),
+                node.namespaces,
+            ],
+        )?;
+        write_processes(tx, run_id, &node.children, Some(node.pid))?;
+    }
+    Ok(())
+}
+
+fn write_file_access(tx: &Transaction, run_id: i64, access: &FileAccess) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO file_accesses (run_id, pid, file_name, file_descriptor, data_length, \
+         start_time, end_time, error_count, read, write, execute, list, ipc_kind) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            run_id,
+            access.pid,
+            access.file_name.as_ref().map(|name| name.to_string_lossy()),
+            access.file_descriptor,
+            access.data_length,
+            access.start_time,
+            access.end_time,
+            access.error_count,
+            access.access_type.read,
+            access.access_type.write,
+            access.access_type.execute,
+            access.access_type.list,
+            access.ipc_kind.map(|kind| kind.text()),
+        ],
+    )?;
+    let access_id = tx.last_insert_rowid();
+    let payloads = [
+        ("read", &access.read_data, access.read_format),
+        ("write", &access.write_data, access.write_format),
+    ];
+    for (direction, payload, format) in payloads {
+        if payload.is_empty() {
+            continue;
+        }
+        tx.execute(
+            "INSERT INTO payloads (file_access_id, direction, format, truncated, data) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                access_id,
+                direction,
+                format.map(PayloadFormat::text),
+                access.data_truncated,
+                payload,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use crate::types::{ExitStatus, ProcessExitData, SyscallData, PROCESS_EXIT_ID};
+
+    use super::*;
+
+    #[test]
+    fn writes_process_exits() {
+        let path = std::env::temp_dir().join(format!("blackbox-test-{}.db", std::process::id()));
+        let events = [TraceEvent {
+            pid: 100,
+            thread_id: 100,
+            syscall_id: PROCESS_EXIT_ID,
+            monotonic_enter_timestamp: 1,
+            monotonic_exit_timestamp: 1,
+            enter_time: SystemTime::UNIX_EPOCH,
+            exit_time: SystemTime::UNIX_EPOCH,
+            data_truncated: false,
+            data: SyscallData::ProcessExit(ProcessExitData {
+                status: ExitStatus::Exited(0),
+            }),
+        }];
+        let result = write_database(
+            &path,
+            &events,
+            &ProcessingData::default(),
+            "true",
+            Path::new("/"),
+        );
+        let stored = Connection::open(&path).and_then(|conn| {
+            conn.query_row("SELECT syscall_id, name FROM syscalls", [], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+            })
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        result.unwrap();
+        assert_eq!(stored.unwrap(), (-1, None));
+    }
+}
@@ -0,0 +1,729 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::time::SystemTime;
+
+use blackbox_common::syscall_names::syscall_name;
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::signal::Signal;
+
+use crate::types::{
+    ExitStatus, IpcKind, PrivilegedOperation, SyscallData, TraceEvent, UnpairedReason,
+};
+
+/// The number of bytes of read and written data shown, as with strace's default `-s 32`
+const STRING_LIMIT: usize = 32;
+/// Shown for arguments that were not recorded
+const UNKNOWN: &str = "?";
+
+/// The open(2) flags other than the access mode. O_SYNC and O_TMPFILE include other flags,
+/// so they come before them
+const OPEN_FLAGS: [(i32, &str); 16] = [
+    (libc::O_CREAT, "O_CREAT"),
+    (libc::O_EXCL, "O_EXCL"),
+    (libc::O_NOCTTY, "O_NOCTTY"),
+    (libc::O_TRUNC, "O_TRUNC"),
+    (libc::O_APPEND, "O_APPEND"),
+    (libc::O_NONBLOCK, "O_NONBLOCK"),
+    (libc::O_SYNC, "O_SYNC"),
+    (libc::O_DSYNC, "O_DSYNC"),
+    (libc::O_ASYNC, "O_ASYNC"),
+    (libc::O_DIRECT, "O_DIRECT"),
+    (libc::O_TMPFILE, "O_TMPFILE"),
+    (libc::O_DIRECTORY, "O_DIRECTORY"),
+    (libc::O_NOFOLLOW, "O_NOFOLLOW"),
+    (libc::O_NOATIME, "O_NOATIME"),
+    (libc::O_CLOEXEC, "O_CLOEXEC"),
+    (libc::O_PATH, "O_PATH"),
+];
+
+/// The clone(2) flags, without the exit signal in the low byte
+const CLONE_FLAGS: [(i32, &str); 24] = [
+    (libc::CLONE_VM, "CLONE_VM"),
+    (libc::CLONE_FS, "CLONE_FS"),
+    (libc::CLONE_FILES, "CLONE_FILES"),
+    (libc::CLONE_SIGHAND, "CLONE_SIGHAND"),
+    (libc::CLONE_PIDFD, "CLONE_PIDFD"),
+    (libc::CLONE_PTRACE, "CLONE_PTRACE"),
+    (libc::CLONE_VFORK, "CLONE_VFORK"),
+    (libc::CLONE_PARENT, "CLONE_PARENT"),
+    (libc::CLONE_THREAD, "CLONE_THREAD"),
+    (libc::CLONE_NEWNS, "CLONE_NEWNS"),
+    (libc::CLONE_SYSVSEM, "CLONE_SYSVSEM"),
+    (libc::CLONE_SETTLS, "CLONE_SETTLS"),
+    (libc::CLONE_PARENT_SETTID, "CLONE_PARENT_SETTID"),
+    (libc::CLONE_CHILD_CLEARTID, "CLONE_CHILD_CLEARTID"),
+    (libc::CLONE_DETACHED, "CLONE_DETACHED"),
+    (libc::CLONE_UNTRACED, "CLONE_UNTRACED"),
+    (libc::CLONE_CHILD_SETTID, "CLONE_CHILD_SETTID"),
+    (libc::CLONE_NEWCGROUP, "CLONE_NEWCGROUP"),
+    (libc::CLONE_NEWUTS, "CLONE_NEWUTS"),
+    (libc::CLONE_NEWIPC, "CLONE_NEWIPC"),
+    (libc::CLONE_NEWUSER, "CLONE_NEWUSER"),
+    (libc::CLONE_NEWPID, "CLONE_NEWPID"),
+    (libc::CLONE_NEWNET, "CLONE_NEWNET"),
+    (libc::CLONE_IO, "CLONE_IO"),
+];
+
+const SOCKET_FLAGS: [(i32, &str); 2] = [
+    (libc::SOCK_NONBLOCK, "SOCK_NONBLOCK"),
+    (libc::SOCK_CLOEXEC, "SOCK_CLOEXEC"),
+];
+
+const EVENTFD_FLAGS: [(i32, &str); 3] = [
+    (libc::EFD_SEMAPHORE, "EFD_SEMAPHORE"),
+    (libc::EFD_NONBLOCK, "EFD_NONBLOCK"),
+    (libc::EFD_CLOEXEC, "EFD_CLOEXEC"),
+];
+
+/// Renders an event as a line of `strace -f -ttt` output, e.g.
+/// `1234  1700000000.123456 openat(AT_FDCWD, "/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3`.
+/// The prefix is the thread ID, which strace calls the PID
+pub fn strace_line(event: &TraceEvent) -> String {
+    format!(
+        "{:<5} {} {}",
+        event.thread_id,
+        timestamp_text(event.enter_time),
+        strace_call(event)
+    )
+}
+
+/// Renders an event as a call in strace's format, without the thread ID and timestamp.
+/// Arguments that are not recorded are shown as `?`, and events that are not system calls
+/// are shown like strace's `+++` messages
+pub fn strace_call(event: &TraceEvent) -> String {
+    let name = match syscall_name(event.syscall_id) {
+        Some(name) => name.to_owned(),
+        None => format!("syscall_{:#x}", event.syscall_id),
+    };
+    match &event.data {
+        SyscallData::Open(open) => {
+            let mut args = vec![];
+            if let Some(directory_fd) = open.directory_fd {
+                args.push(directory_fd_text(directory_fd));
+            }
+            args.push(path_text(open.filename.as_deref()));
+            // creat only takes a mode
+            if name != "creat" {
+                args.push(open_flags_text(open.flags));
+            }
+            if name == "creat"
+                || open.flags & libc::O_CREAT != 0
+                || open.flags & libc::O_TMPFILE == libc::O_TMPFILE
+            {
+                args.push(format!("{:#o}", open.mode));
+            }
+            syscall(&name, &args, return_text(result(open.file_descriptor)))
+        }
+        SyscallData::Read(read) => {
+            // nothing is read if the call fails; 0 is the end of the file
+            let buffer = match (&read.data_read, read.bytes_read) {
+                (_, Err(error)) if error < 0 => String::from(UNKNOWN),
+                (Some(data), _) => data_text(data, event.data_truncated),
+                (None, _) => String::from(UNKNOWN),
+            };
+            let bytes_read = match read.bytes_read {
+                Ok(count) => count as i64,
+                Err(error) => error as i64,
+            };
+            let mut args = vec![read.file_descriptor.to_string()];
+            if is_vectored(&name) {
+                args.push(format!("[{{iov_base={}, iov_len={}}}]", buffer, read.count));
+                args.push(UNKNOWN.to_owned());
+            } else {
+                args.push(buffer);
+                args.push(read.count.to_string());
+            }
+            if let Some(offset) = read.offset {
+                args.push(offset.to_string());
+            }
+            syscall(&name, &args, return_text(bytes_read))
+        }
+        SyscallData::Write(write) => {
+            let buffer = match &write.data_written {
+                Some(data) => data_text(data, event.data_truncated),
+                None => String::from(UNKNOWN),
+            };
+            let bytes_written = match write.bytes_written {
+                Ok(count) => count as i64,
+                Err(error) => error as i64,
+            };
+            let mut args = vec![write.file_descriptor.to_string()];
+            if is_vectored(&name) {
+                args.push(format!(
+                    "[{{iov_base={}, iov_len={}}}]",
+                    buffer, write.count
+                ));
+                args.push(UNKNOWN.to_owned());
+            } else {
+                args.push(buffer);
+                args.push(write.count.to_string());
+            }
+            if let Some(offset) = write.offset {
+                args.push(offset.to_string());
+            }
+            syscall(&name, &args, return_text(bytes_written))
+        }
+        SyscallData::Transfer(transfer) => {
+            let offset = |offset: Option<u64>| match offset {
+                Some(offset) => format!("[{offset}]"),
+                None => String::from("NULL"),
+            };
+            let args = if name == "sendfile" {
+                vec![
+                    transfer.out_fd.to_string(),
+                    transfer.in_fd.to_string(),
+                    offset(transfer.in_offset),
+                    transfer.count.to_string(),
+                ]
+            } else {
+                // splice and copy_file_range
+                vec![
+                    transfer.in_fd.to_string(),
+                    offset(transfer.in_offset),
+                    transfer.out_fd.to_string(),
+                    offset(transfer.out_offset),
+                    transfer.count.to_string(),
+                    UNKNOWN.to_owned(),
+                ]
+            };
+            let transferred = match transfer.bytes_transferred {
+                Ok(count) => count as i64,
+                Err(error) => error as i64,
+            };
+            syscall(&name, &args, return_text(transferred))
+        }
+        SyscallData::ListDirectory(list) => {
+            let bytes_read = match list.bytes_read {
+                Ok(count) => count as i64,
+                Err(error) => error as i64,
+            };
+            let args = [
+                list.file_descriptor.to_string(),
+                UNKNOWN.to_owned(),
+                list.count.to_string(),
+            ];
+            syscall(&name, &args, return_text(bytes_read))
+        }
+        SyscallData::Probe(probe) => {
+            let mut args = vec![];
+            if let Some(directory_fd) = probe.directory_fd {
+                args.push(directory_fd_text(directory_fd));
+            }
+            args.push(path_text(probe.filename.as_deref()));
+            // the stat buffer, access mode, or readlink buffer and size
+            args.push(UNKNOWN.to_owned());
+            if name == "readlink" || name == "readlinkat" || name == "newfstatat" {
+                args.push(UNKNOWN.to_owned());
+            }
+            let return_val = match probe.return_val {
+                Ok(()) => String::from("0"),
+                Err(error) => return_text(error.into()),
+            };
+            syscall(&name, &args, return_val)
+        }
+        SyscallData::Close(close) => syscall(
+            &name,
+            &[close.file_descriptor.to_string()],
+            unit_return_text(close.return_val),
+        ),
+        SyscallData::Socket(socket) => {
+            let args = [
+                socket_domain_text(socket.domain),
+                socket_type_text(socket.r#type),
+                match socket.protocol {
+                    libc::IPPROTO_TCP if socket.domain != libc::AF_UNIX => {
+                        String::from("IPPROTO_TCP")
+                    }
+                    libc::IPPROTO_UDP if socket.domain != libc::AF_UNIX => {
+                        String::from("IPPROTO_UDP")
+                    }
+                    protocol => protocol.to_string(),
+                },
+            ];
+            syscall(&name, &args, return_text(result(socket.file_descriptor)))
+        }
+        SyscallData::Shutdown(shutdown) => {
+            let how = match shutdown.how {
+                libc::SHUT_RD => String::from("SHUT_RD"),
+                libc::SHUT_WR => String::from("SHUT_WR"),
+                libc::SHUT_RDWR => String::from("SHUT_RDWR"),
+                how => how.to_string(),
+            };
+            syscall(
+                &name,
+                &[shutdown.file_descriptor.to_string(), how],
+                unit_return_text(shutdown.return_val),
+            )
+        }
+        SyscallData::Ipc(ipc) => {
+            let (file_descriptors, return_val) = match &ipc.file_descriptors {
+                Ok(fds) => (
+                    format!(
+                        "[{}]",
+                        fds.iter()
+                            .map(|fd| fd.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    match ipc.kind {
+                        IpcKind::EventFd => fds.first().map_or(0, |fd| *fd as i64),
+                        IpcKind::Pipe | IpcKind::SocketPair => 0,
+                    },
+                ),
+                Err(error) => (String::from(UNKNOWN), *error as i64),
+            };
+            let args = match ipc.kind {
+                IpcKind::Pipe if name == "pipe" => vec![file_descriptors],
+                IpcKind::Pipe => vec![file_descriptors, flags_text(ipc.flags, &OPEN_FLAGS)],
+                // only Unix domain sockets can be created in pairs
+                IpcKind::SocketPair => vec![
+                    String::from("AF_UNIX"),
+                    socket_type_text(ipc.flags),
+                    String::from("0"),
+                    file_descriptors,
+                ],
+                IpcKind::EventFd if name == "eventfd" => vec![UNKNOWN.to_owned()],
+                IpcKind::EventFd => {
+                    vec![UNKNOWN.to_owned(), flags_text(ipc.flags, &EVENTFD_FLAGS)]
+                }
+            };
+            syscall(&name, &args, return_text(return_val))
+        }
+        SyscallData::Dup(dup) => {
+            let mut args = vec![dup.old_fd.to_string()];
+            if name != "dup" {
+                args.push(match dup.new_fd {
+                    Ok(fd) => fd.to_string(),
+                    Err(_) => UNKNOWN.to_owned(),
+                });
+            }
+            if name == "dup3" {
+                args.push(UNKNOWN.to_owned());
+            }
+            syscall(&name, &args, return_text(result(dup.new_fd)))
+        }
+        SyscallData::Privileged(privileged) => syscall(
+            &name,
+            &privileged_args(&privileged.operation),
+            unit_return_text(privileged.return_val),
+        ),
+        SyscallData::Fork(fork) => {
+            let return_val = return_text(match fork.pid {
+                Ok(pid) => pid as i64,
+                Err(error) => error as i64,
+            });
+            let flags = clone_flags_text(fork.flags);
+            match name.as_str() {
+                "fork" | "vfork" => syscall(&name, &[], return_val),
+                "clone3" => syscall(
+                    &name,
+                    &[format!("{{flags={flags}, ...}}"), UNKNOWN.to_owned()],
+                    return_val,
+                ),
+                _ => syscall(
+                    &name,
+                    &[
+                        format!("child_stack={UNKNOWN}"),
+                        format!("flags={flags}"),
+                        UNKNOWN.to_owned(),
+                    ],
+                    return_val,
+                ),
+            }
+        }
+        SyscallData::Execve(execve) => {
+            let mut args = vec![];
+            if let Some(directory_fd) = execve.directory_fd {
+                args.push(directory_fd_text(directory_fd));
+            }
+            args.push(path_text(execve.filename.as_deref()));
+            args.push(format!(
+                "[{}]",
+                execve
+                    .arguments
+                    .iter()
+                    .map(|arg| string_text(arg.as_bytes()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            args.push(format!("{:#x}", execve.environment));
+            if let Some(flags) = execve.flags {
+                args.push(flags.to_string());
+            }
+            let return_val = match execve.return_val {
+                Some(return_val) => unit_return_text(return_val),
+                None => String::from("?"),
+            };
+            syscall(&name, &args, return_val)
+        }
+        SyscallData::Exit(exit) => syscall(&name, &[exit.status.to_string()], String::from("?")),
+        SyscallData::ProcessExit(exit) => match exit.status {
+            ExitStatus::Exited(code) => format!("+++ exited with {code} +++"),
+            ExitStatus::Signaled(signal) => format!("+++ killed by {} +++", signal_text(signal)),
+            ExitStatus::Unknown => String::from("+++ exited +++"),
+        },
+        SyscallData::Unpaired(unpaired) => match unpaired.reason {
+            UnpairedReason::MissingExit => format!("{name}({UNKNOWN} <unfinished ...>"),
+            UnpairedReason::MissingEnter => format!(
+                "<... {name} resumed>) = {}",
+                match unpaired.return_val {
+                    Some(return_val) => return_text(return_val),
+                    None => String::from("?"),
+                }
+            ),
+        },
+        SyscallData::LostEvents(lost) => format!(
+            "+++ {} events lost from {} on CPU {} +++",
+            lost.count, lost.map, lost.cpu
+        ),
+        SyscallData::Unhandled(unhandled) => {
+            let args = [
+                unhandled.arg_0,
+                unhandled.arg_1,
+                unhandled.arg_2,
+                unhandled.arg_3,
+                unhandled.arg_4,
+                unhandled.arg_5,
+            ]
+            .map(|arg| format!("{arg:#x}"));
+            syscall(&name, &args, return_text(unhandled.return_val as i64))
+        }
+    }
+}
+
+fn syscall(name: &str, args: &[String], return_val: String) -> String {
+    format!("{}({}) = {}", name, args.join(", "), return_val)
+}
+
+/// Seconds since the Unix epoch, to the microsecond
+fn timestamp_text(time: SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!(
+        "{}.{:06}",
+        since_epoch.as_secs(),
+        since_epoch.subsec_micros()
+    )
+}
+
+/// A return value, or `-1` and the name and description of the error if it is one
+fn return_text(return_val: i64) -> String {
+    if !(-4095..0).contains(&return_val) {
+        return return_val.to_string();
+    }
+    match Errno::from_i32(-return_val as i32) {
+        Errno::UnknownErrno => format!("-1 (errno {})", -return_val),
+        errno => format!("-1 {:?} ({})", errno, errno.desc()),
+    }
+}
+
+fn unit_return_text(return_val: Result<(), i32>) -> String {
+    match return_val {
+        Ok(()) => String::from("0"),
+        Err(error) => return_text(error.into()),
+    }
+}
+
+/// The return value of a call that returns a file descriptor or an error
+fn result(return_val: Result<i32, i32>) -> i64 {
+    match return_val {
+        Ok(value) | Err(value) => value.into(),
+    }
+}
+
+fn is_vectored(name: &str) -> bool {
+    name.ends_with('v') || name.ends_with("v2")
+}
+
+fn directory_fd_text(directory_fd: i32) -> String {
+    if directory_fd == libc::AT_FDCWD {
+        String::from("AT_FDCWD")
+    } else {
+        directory_fd.to_string()
+    }
+}
+
+fn path_text(path: Option<&OsStr>) -> String {
+    match path {
+        Some(path) => string_text(path.as_bytes()),
+        None => UNKNOWN.to_owned(),
+    }
+}
+
+/// Captured data as a string, cut off at [`STRING_LIMIT`] bytes. strace marks strings that
+/// are cut off with `...`
+fn data_text(data: &[u8], truncated: bool) -> String {
+    let shown = &data[..data.len().min(STRING_LIMIT)];
+    let mut text = string_text(shown);
+    if truncated || shown.len() < data.len() {
+        text.push_str("...");
+    }
+    text
+}
+
+/// Quotes and escapes a string as strace does: C escapes for common control characters, and
+/// octal escapes for other bytes that are not printable ASCII
+fn string_text(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    for (i, b) in bytes.iter().enumerate() {
+        match b {
+            b'"' => text.push_str("\\\""),
+            b'\\' => text.push_str("\\\\"),
+            b'\t' => text.push_str("\\t"),
+            b'\n' => text.push_str("\\n"),
+            0x0b => text.push_str("\\v"),
+            0x0c => text.push_str("\\f"),
+            b'\r' => text.push_str("\\r"),
+            b' '..=b'~' => text.push(*b as char),
+            b => {
+                // a shorter escape would run into a following octal digit
+                if matches!(bytes.get(i + 1), Some(b'0'..=b'7')) {
+                    text.push_str(&format!("\\{b:03o}"));
+                } else {
+                    text.push_str(&format!("\\{b:o}"));
+                }
+            }
+        }
+    }
+    text.push('"');
+    text
+}
+
+/// Names the flags set in `flags`, e.g. `O_CREAT|O_CLOEXEC`, followed by any flags without
+/// names in hex. `0` if none are set
+fn flags_text(flags: i32, names: &[(i32, &str)]) -> String {
+    let mut remaining = flags;
+    let mut parts = vec![];
+    for (flag, name) in names {
+        if *flag != 0 && remaining & flag == *flag {
+            parts.push(name.to_string());
+            remaining &= !flag;
+        }
+    }
+    if remaining != 0 {
+        parts.push(format!("{remaining:#x}"));
+    }
+    if parts.is_empty() {
+        String::from("0")
+    } else {
+        parts.join("|")
+    }
+}
+
+fn open_flags_text(flags: i32) -> String {
+    let access_mode = match flags & libc::O_ACCMODE {
+        libc::O_RDONLY => "O_RDONLY",
+        libc::O_WRONLY => "O_WRONLY",
+        _ => "O_RDWR",
+    };
+    match flags & !libc::O_ACCMODE {
+        0 => access_mode.to_owned(),
+        other => format!("{}|{}", access_mode, flags_text(other, &OPEN_FLAGS)),
+    }
+}
+
+fn clone_flags_text(flags: u64) -> String {
+    let exit_signal = (flags & 0xff) as i32;
+    let mut parts = vec![];
+    if flags as i32 & !0xff != 0 {
+        parts.push(flags_text(flags as i32 & !0xff, &CLONE_FLAGS));
+    }
+    if exit_signal != 0 {
+        parts.push(signal_text(exit_signal));
+    }
+    if parts.is_empty() {
+        String::from("0")
+    } else {
+        parts.join("|")
+    }
+}
+
+fn signal_text(signal: i32) -> String {
+    match Signal::try_from(signal) {
+        Ok(signal) => signal.as_str().to_owned(),
+        Err(_) => signal.to_string(),
+    }
+}
+
+fn socket_domain_text(domain: i32) -> String {
+    match domain {
+        libc::AF_UNIX => String::from("AF_UNIX"),
+        libc::AF_INET => String::from("AF_INET"),
+        libc::AF_INET6 => String::from("AF_INET6"),
+        libc::AF_NETLINK => String::from("AF_NETLINK"),
+        libc::AF_PACKET => String::from("AF_PACKET"),
+        domain => domain.to_string(),
+    }
+}
+
+/// The socket type and the SOCK_NONBLOCK and SOCK_CLOEXEC flags
+fn socket_type_text(socket_type: i32) -> String {
+    let flags = libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+    let base = match socket_type & !flags {
+        libc::SOCK_STREAM => String::from("SOCK_STREAM"),
+        libc::SOCK_DGRAM => String::from("SOCK_DGRAM"),
+        libc::SOCK_RAW => String::from("SOCK_RAW"),
+        libc::SOCK_SEQPACKET => String::from("SOCK_SEQPACKET"),
+        other => other.to_string(),
+    };
+    match socket_type & flags {
+        0 => base,
+        set => format!("{}|{}", base, flags_text(set, &SOCKET_FLAGS)),
+    }
+}
+
+fn privileged_args(operation: &PrivilegedOperation) -> Vec<String> {
+    let string = |s: &Option<std::ffi::OsString>| path_text(s.as_deref());
+    let unknown = || UNKNOWN.to_owned();
+    match operation {
+        PrivilegedOperation::LoadModule {
+            file_descriptor: Some(file_descriptor),
+            params,
+        } => vec![file_descriptor.to_string(), string(params), unknown()],
+        PrivilegedOperation::LoadModule {
+            file_descriptor: None,
+            params,
+        } => vec![unknown(), unknown(), string(params)],
+        PrivilegedOperation::UnloadModule { name } => vec![string(name), unknown()],
+        PrivilegedOperation::Bpf { command } => vec![command.to_string(), unknown(), unknown()],
+        PrivilegedOperation::Mount {
+            source,
+            target,
+            filesystem_type,
+            flags,
+        } => vec![
+            string(source),
+            string(target),
+            string(filesystem_type),
+            format!("{flags:#x}"),
+            unknown(),
+        ],
+        PrivilegedOperation::Unmount { target, flags } => {
+            vec![string(target), format!("{flags:#x}")]
+        }
+        PrivilegedOperation::PivotRoot { new_root, put_old } => {
+            vec![string(new_root), string(put_old)]
+        }
+        PrivilegedOperation::Chroot { path } => vec![string(path)],
+        PrivilegedOperation::Unshare { flags } => vec![flags_text(*flags, &CLONE_FLAGS)],
+        PrivilegedOperation::SetNs {
+            file_descriptor,
+            namespace_type,
+        } => vec![
+            file_descriptor.to_string(),
+            flags_text(*namespace_type, &CLONE_FLAGS),
+        ],
+        PrivilegedOperation::KexecLoad {
+            kernel_fd: Some(kernel_fd),
+            cmdline,
+        } => vec![
+            kernel_fd.to_string(),
+            unknown(),
+            unknown(),
+            string(cmdline),
+            unknown(),
+        ],
+        PrivilegedOperation::KexecLoad {
+            kernel_fd: None, ..
+        } => vec![unknown(), unknown(), unknown(), unknown()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use blackbox_common::syscall_names::syscall_id;
+
+    use crate::types::OpenData;
+
+    use super::*;
+
+    fn openat(filename: &str, flags: i32, file_descriptor: Result<i32, i32>) -> TraceEvent {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+        TraceEvent {
+            pid: 1234,
+            thread_id: 1234,
+            syscall_id: syscall_id("openat").unwrap(),
+            monotonic_enter_timestamp: 0,
+            monotonic_exit_timestamp: 0,
+            enter_time: time,
+            exit_time: time,
+            data_truncated: false,
+            data: SyscallData::Open(OpenData {
+                filename: Some(filename.into()),
+                flags,
+                file_descriptor,
+                directory_fd: Some(libc::AT_FDCWD),
+                mode: 0,
+            }),
+        }
+    }
+
+    #[test]
+    fn renders_lines_like_strace() {
+        let event = openat("/etc/ld.so.cache", libc::O_RDONLY | libc::O_CLOEXEC, Ok(3));
+        assert_eq!(
+            strace_line(&event),
+            "1234  1700000000.123456 openat(AT_FDCWD, \"/etc/ld.so.cache\", \
+             O_RDONLY|O_CLOEXEC) = 3"
+        );
+    }
+
+    #[test]
+    fn renders_errors_by_name() {
+        let event = openat("/missing", libc::O_RDONLY, Err(-libc::ENOENT));
+        assert_eq!(
+            strace_call(&event),
+            "openat(AT_FDCWD, \"/missing\", O_RDONLY) = -1 ENOENT (No such file or directory)"
+        );
+        assert_eq!(return_text(-4095), "-1 (errno 4095)");
+        assert_eq!(return_text(-4096), "-4096");
+    }
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(string_text(b"say \"hi\"\\\n"), "\"say \\\"hi\\\"\\\\\\n\"");
+        assert_eq!(string_text(b"\x01a\x7f"), "\"\\1a\\177\"");
+        // an octal digit after an escape needs the full three digits
+        assert_eq!(string_text(b"\x001"), "\"\\0001\"");
+        assert_eq!(string_text(b"\xff\x00"), "\"\\377\\0\"");
+    }
+
+    #[test]
+    fn cuts_off_long_data() {
+        let data = [b'a'; STRING_LIMIT + 1];
+        let shown = format!("\"{}\"...", "a".repeat(STRING_LIMIT));
+        assert_eq!(data_text(&data, false), shown);
+        assert_eq!(
+            data_text(&data[..STRING_LIMIT], false),
+            format!("\"{}\"", "a".repeat(STRING_LIMIT))
+        );
+        assert_eq!(data_text(b"ab", true), "\"ab\"...");
+    }
+
+    #[test]
+    fn names_overlapping_flags_once() {
+        assert_eq!(flags_text(libc::O_SYNC, &OPEN_FLAGS), "O_SYNC");
+        assert_eq!(flags_text(libc::O_DSYNC, &OPEN_FLAGS), "O_DSYNC");
+        assert_eq!(
+            open_flags_text(libc::O_RDWR | libc::O_TMPFILE),
+            "O_RDWR|O_TMPFILE"
+        );
+        assert_eq!(
+            open_flags_text(libc::O_RDONLY | libc::O_DIRECTORY),
+            "O_RDONLY|O_DIRECTORY"
+        );
+    }
+
+    #[test]
+    fn shows_unnamed_flags_in_hex() {
+        assert_eq!(
+            flags_text(libc::SOCK_CLOEXEC | 0x1, &SOCKET_FLAGS),
+            "SOCK_CLOEXEC|0x1"
+        );
+        assert_eq!(flags_text(0, &SOCKET_FLAGS), "0");
+    }
+}
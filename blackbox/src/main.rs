@@ -1,35 +1,59 @@
+mod export;
 mod processing;
+mod recording;
 mod tracing;
 mod types;
 mod ui;
 
 use std::{
-    fs::OpenOptions,
-    io::Write,
+    fs::{File, OpenOptions},
+    io::{BufReader, Write},
     path::{Path, PathBuf},
     process::Command,
     sync::Arc,
 };
 
+use blackbox_common::syscall_names::{syscall_id, SYSCALL_TABLE};
+use blackbox_common::{MAX_CAPTURE_SIZE, MAX_PATH_PREFIXES, PATH_PREFIX_SIZE};
 use clap::Parser;
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, eyre, Result};
 use log::{debug, warn};
-use tokio::sync::{Mutex, Semaphore};
-use types::ProcessingData;
+use recording::{RecordingHeader, TraceReader, TraceWriter};
+use tokio::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex, Semaphore,
+};
+use tokio::task::JoinHandle;
+use tracing::TraceOptions;
+use types::{Policy, ProcessingData, TraceEvent, WallClock};
 
 /// Blackbox: a kernel-level process analyzer. Collects
 /// system call data about the traced process similarly to
 /// strace, but creates a simple, understandable report of
 /// the process's behavior.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
+    #[command(subcommand)]
+    mode: Option<Mode>,
     /// Command of the program to run and trace
-    #[arg(short, long)]
-    command: String,
+    #[arg(short, long, required_unless_present = "replay")]
+    command: Option<String>,
     /// User to run the process as
-    #[arg(short, long)]
-    user: String,
+    #[arg(short, long, required_unless_present = "replay")]
+    user: Option<String>,
+    /// Recording to read the events from instead of tracing a program, as written by
+    /// `--file-to-write` with `--format binary`. The UI or reports are shown as if the recorded
+    /// command had just been traced.
+    #[arg(long, default_value = None, conflicts_with_all = ["command", "file_to_write"])]
+    replay: Option<PathBuf>,
     /// File to read the process's stdin from [default: blackbox's stdin]
     #[arg(short='i', long, default_value=None)]
     stdin_file: Option<PathBuf>,
@@ -43,10 +67,85 @@ struct Args {
     /// only tracing will occur.
     #[arg(short, long, default_value=None)]
     file_to_write: Option<PathBuf>,
+    /// The format of the events written to `--file-to-write`: a JSON object per line,
+    /// strace-style lines that can be compared with the output of `strace -f -ttt`, or a
+    /// compressed binary recording that can be read with `--replay`
+    #[arg(long, value_enum, default_value_t = EventFormat::Json, requires = "file_to_write")]
+    format: EventFormat,
+    /// File to write a standalone HTML report to once the traced process exits. If this is set,
+    /// no UI will be started.
+    #[arg(long, default_value = None, conflicts_with = "file_to_write")]
+    report_html: Option<PathBuf>,
+    /// File to write the alerts to in SARIF format once the traced process exits, for code
+    /// scanning tools. If this is set, no UI will be started.
+    #[arg(long, default_value = None, conflicts_with = "file_to_write")]
+    report_sarif: Option<PathBuf>,
+    /// File to write the system calls to as Chrome trace events once the traced process exits,
+    /// to view their timeline in ui.perfetto.dev. If this is set, no UI will be started.
+    #[arg(long, default_value = None, conflicts_with = "file_to_write")]
+    report_chrome_trace: Option<PathBuf>,
+    /// SQLite database to add the trace to once the traced process exits, with tables for the
+    /// system calls, processes, files, connections and alerts. Each run is added to the existing
+    /// tables, so one database can hold many traces. If this is set, no UI will be started.
+    #[arg(long, default_value = None, conflicts_with = "file_to_write")]
+    report_sqlite: Option<PathBuf>,
+    /// Policy file listing the files, programs and kinds of connections the command is expected
+    /// to use, in JSON. Alerts about them are suppressed, and anything else the command does is
+    /// reported as a deviation.
+    #[arg(long, default_value = None)]
+    policy: Option<PathBuf>,
+    /// File to write a policy to once the traced process exits, listing everything the command
+    /// did. Later runs with `--policy` then only report what changed. If this is set, no UI will
+    /// be started.
+    #[arg(long, default_value = None, conflicts_with_all = ["file_to_write", "policy"])]
+    write_policy: Option<PathBuf>,
     /// Include the initial execve of the program being traced. This is turned off by default to
     /// make the output more intuitive.
     #[arg(long, action = clap::ArgAction::SetTrue)]
     include_initial_execve: bool,
+    /// The maximum number of bytes of data to capture for each read or write call. Set this to 0
+    /// to only collect metadata, or to a large value to capture everything (up to 1 MiB per call).
+    /// Larger values make it more likely that events are lost.
+    #[arg(long, default_value_t = 1024)]
+    capture_limit: usize,
+    /// Only trace these system calls, e.g. `--syscalls open,connect,execve`. Other system calls
+    /// are filtered out in the kernel. execve, rt_sigreturn and restart_syscall are always
+    /// traced.
+    #[arg(long, value_delimiter = ',', conflicts_with = "exclude_syscalls")]
+    syscalls: Vec<String>,
+    /// Do not trace these system calls, e.g. `--exclude-syscalls read,write`
+    #[arg(long, value_delimiter = ',')]
+    exclude_syscalls: Vec<String>,
+    /// Only trace open calls on paths starting with one of these prefixes, e.g.
+    /// `--open-path-prefixes /etc,/home`. Relative paths are compared as they were passed to the
+    /// call.
+    #[arg(long, value_delimiter = ',')]
+    open_path_prefixes: Vec<String>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Mode {
+    /// Compare two recordings of the same command, written with `--format binary`. The files,
+    /// directories, programs, kinds of connections and alerts that were added or removed in the
+    /// second recording are printed, and shown in the UI
+    Diff {
+        /// The older recording. Only binary recordings can be compared, not the JSON or
+        /// strace output of `--file-to-write`
+        before: PathBuf,
+        /// The newer recording, also written with `--format binary`
+        after: PathBuf,
+        /// Only print the differences, without starting the UI
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_ui: bool,
+    },
+}
+
+/// How `--file-to-write` writes events
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum EventFormat {
+    Json,
+    Strace,
+    Binary,
 }
 
 #[tokio::main]
@@ -63,6 +162,55 @@ async fn main() -> Result<()> {
         .init();
     color_eyre::install()?;
 
+    if let Some(Mode::Diff {
+        before,
+        after,
+        no_ui,
+    }) = &args.mode
+    {
+        return diff(before, after, *no_ui).await;
+    }
+    if let Some(path) = &args.replay {
+        return replay(&args, path).await;
+    }
+    // clap requires both unless replaying
+    let (Some(user), Some(command_line)) = (args.user.clone(), args.command.clone()) else {
+        bail!("--command and --user are required to trace a program");
+    };
+
+    if args.capture_limit > MAX_CAPTURE_SIZE {
+        warn!(
+            "Capture limit {} is larger than the maximum; only {} bytes will be captured",
+            args.capture_limit, MAX_CAPTURE_SIZE
+        );
+    }
+    if args.open_path_prefixes.len() > MAX_PATH_PREFIXES {
+        bail!("At most {} path prefixes can be used", MAX_PATH_PREFIXES);
+    }
+    if let Some(prefix) = args
+        .open_path_prefixes
+        .iter()
+        .find(|prefix| prefix.len() > PATH_PREFIX_SIZE)
+    {
+        bail!(
+            "Path prefix {} is longer than {} bytes",
+            prefix,
+            PATH_PREFIX_SIZE
+        );
+    }
+    // read before the command is started, so that an invalid policy does not leave it stopped
+    let policy = read_policy(&args)?;
+    let options = TraceOptions {
+        include_initial_execve: args.include_initial_execve,
+        capture_limit: args.capture_limit.min(MAX_CAPTURE_SIZE),
+        excluded_syscalls: excluded_syscalls(&args.syscalls, &args.exclude_syscalls)?,
+        open_path_prefixes: args
+            .open_path_prefixes
+            .iter()
+            .map(|prefix| prefix.as_bytes().to_vec())
+            .collect(),
+    };
+
     // Bump the memlock rlimit. This is needed for older kernels that don't use the
     // new memcg based accounting, see https://lwn.net/Articles/837122/
     let rlim = nix::libc::rlimit {
@@ -74,17 +222,18 @@ async fn main() -> Result<()> {
         debug!("remove limit on locked memory failed, ret is: {}", ret);
     }
 
+    let working_dir = std::env::current_dir()?;
     let mut command = Command::new("/bin/su");
     command
-        .arg(args.user)
+        .arg(user)
         .arg("--preserve-environment")
         .arg("--shell")
         .arg("/bin/bash")
         .arg("--command")
         .arg(format!(
             "cd {}; kill -STOP $$; exec {}",
-            std::env::current_dir()?.to_string_lossy(),
-            args.command
+            working_dir.to_string_lossy(),
+            command_line
         ));
     if let Some(stdout) = args.stdout_file.clone() {
         let stdout = OpenOptions::new()
@@ -130,46 +279,292 @@ async fn main() -> Result<()> {
         .expect("expected a single valid PID");
 
     // create message queue
-    let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+    let (tx, rx) = tokio::sync::mpsc::channel(100);
     // let (progress_tx, progress_rx) = tokio::sync::mpsc::channel(10);
     let done_wait = Arc::new(Semaphore::new(0));
     let shared_state: Arc<Mutex<Option<ProcessingData>>> = Arc::new(Mutex::new(None));
 
     // spawn the processes in parallel
     // the child of su will be one pid greater, unless there is an extreme race condition
-    let tracing_job = tokio::spawn(tracing::start_tracing(
-        child_pid,
-        tx,
-        args.include_initial_execve,
-    ));
+    let clock = WallClock::now()?;
+    let tracing_job = tokio::spawn(tracing::start_tracing(child_pid, tx, options, clock));
 
     if args.file_to_write.is_none() {
+        let (rx, recorder) = record_for_reports(&args, rx);
         let processing_job = tokio::spawn(processing::start_processing(
             rx,
+            clock,
+            policy,
             Arc::clone(&done_wait),
             Arc::clone(&shared_state),
         ));
 
+        if reports_requested(&args) {
+            write_reports(
+                &args,
+                &done_wait,
+                &shared_state,
+                recorder,
+                &command_line,
+                &working_dir,
+            )
+            .await?;
+            let _ = tokio::try_join!(tracing_job, processing_job)?;
+            let status = child.wait()?;
+            debug!("su exited with {}", status);
+            return Ok(());
+        }
+
         // display info to UI
-        ui::run(done_wait, shared_state)?;
+        ui::run(done_wait, shared_state, None)?;
         child.kill()?;
+        child.wait()?;
 
         // wait for both processes; we only care about errors
         let _ = tokio::try_join!(tracing_job, processing_job)?;
         Ok(())
     } else {
+        let header = RecordingHeader::new(&command_line, working_dir, clock);
         let consumer = tokio::spawn(async move {
+            let mut rx = rx;
             let mut output_file = OpenOptions::new()
                 .write(true)
                 .truncate(true)
                 .create(true)
                 .open(args.file_to_write.unwrap())?;
+            // recordings are compressed as a whole, after a header
+            if let EventFormat::Binary = args.format {
+                let mut writer = TraceWriter::new(output_file, &header)?;
+                while let Some(i) = rx.recv().await {
+                    writer.write(&i)?;
+                }
+                writer.finish()?;
+                return Ok(());
+            }
             while let Some(i) = rx.recv().await {
-                write!(output_file, "{}\n", serde_json::to_string(&i)?)?;
+                match args.format {
+                    EventFormat::Json => write!(output_file, "{}\n", serde_json::to_string(&i)?)?,
+                    EventFormat::Strace => writeln!(output_file, "{}", export::strace_line(&i))?,
+                    EventFormat::Binary => unreachable!("recordings are written above"),
+                }
             }
             Ok::<(), color_eyre::Report>(())
         });
         let _ = tokio::try_join!(tracing_job, consumer)?;
+        // reap `su`; the exit statuses of the traced processes are recorded by the tracer
+        let status = child.wait()?;
+        debug!("su exited with {}", status);
         Ok(())
     }
 }
+
+/// Processes the events of a recording, then shows the UI or writes the reports as if the
+/// recorded command had just been traced
+async fn replay(args: &Args, path: &Path) -> Result<()> {
+    let policy = read_policy(args)?;
+    let reader = TraceReader::new(BufReader::new(File::open(path)?))?;
+    let header = reader.header().clone();
+    debug!(
+        "replaying `{}`, recorded by blackbox {} on {} ({})",
+        header.command, header.blackbox_version, header.kernel, header.arch
+    );
+
+    let (tx, rx) = tokio::sync::mpsc::channel(100);
+    let reading_job = read_recording(reader, tx);
+    let done_wait = Arc::new(Semaphore::new(0));
+    let shared_state: Arc<Mutex<Option<ProcessingData>>> = Arc::new(Mutex::new(None));
+    let (rx, recorder) = record_for_reports(args, rx);
+    let processing_job = tokio::spawn(processing::start_processing(
+        rx,
+        header.clock,
+        policy,
+        Arc::clone(&done_wait),
+        Arc::clone(&shared_state),
+    ));
+
+    if reports_requested(args) {
+        write_reports(
+            args,
+            &done_wait,
+            &shared_state,
+            recorder,
+            &header.command,
+            &header.working_dir,
+        )
+        .await?;
+    } else {
+        ui::run(done_wait, shared_state, None)?;
+    }
+    let (reading, _) = tokio::try_join!(reading_job, processing_job)?;
+    reading
+}
+
+/// Compares two recordings, prints the differences and shows them in the UI
+async fn diff(before: &Path, after: &Path, no_ui: bool) -> Result<()> {
+    let (before_header, before_data) = process_recording(before).await?;
+    let (after_header, after_data) = process_recording(after).await?;
+    if before_header.command != after_header.command {
+        warn!(
+            "Comparing recordings of different commands: `{}` and `{}`",
+            before_header.command, after_header.command
+        );
+    }
+    let diff = processing::diff_traces(&before_data, &after_data);
+    print!(
+        "{}",
+        export::diff_text(&diff, &before.to_string_lossy(), &after.to_string_lossy())
+    );
+    if !no_ui {
+        // the other tabs show the newer trace, which is already processed
+        let done_wait = Arc::new(Semaphore::new(1));
+        let shared_state = Arc::new(Mutex::new(Some(after_data)));
+        ui::run(done_wait, shared_state, Some(diff))?;
+    }
+    Ok(())
+}
+
+/// Processes all the events of a recording
+async fn process_recording(path: &Path) -> Result<(RecordingHeader, ProcessingData)> {
+    let reader = TraceReader::new(BufReader::new(File::open(path)?))?;
+    let header = reader.header().clone();
+    let (tx, rx) = tokio::sync::mpsc::channel(100);
+    let reading_job = read_recording(reader, tx);
+    let done_wait = Arc::new(Semaphore::new(0));
+    let shared_state: Arc<Mutex<Option<ProcessingData>>> = Arc::new(Mutex::new(None));
+    let processing_job = tokio::spawn(processing::start_processing(
+        rx,
+        header.clock,
+        None,
+        Arc::clone(&done_wait),
+        Arc::clone(&shared_state),
+    ));
+    let (reading, _) = tokio::try_join!(reading_job, processing_job)?;
+    reading?;
+    let data = shared_state
+        .lock()
+        .await
+        .take()
+        .ok_or_else(|| eyre!("No data was processed in {}", path.display()))?;
+    Ok((header, data))
+}
+
+/// Sends the events of a recording to `tx` from a blocking task, since the recording is read
+/// from a file
+fn read_recording(
+    reader: TraceReader<BufReader<File>>,
+    tx: Sender<TraceEvent>,
+) -> JoinHandle<Result<()>> {
+    tokio::task::spawn_blocking(move || {
+        for event in reader {
+            // the receiver is dropped if processing stops early
+            if tx.blocking_send(event?).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Whether any report is written instead of starting the UI
+fn reports_requested(args: &Args) -> bool {
+    args.report_html.is_some()
+        || args.report_sarif.is_some()
+        || args.report_chrome_trace.is_some()
+        || args.report_sqlite.is_some()
+        || args.write_policy.is_some()
+}
+
+/// Reads the policy given with `--policy`, if any
+fn read_policy(args: &Args) -> Result<Option<Policy>> {
+    let Some(path) = &args.policy else {
+        return Ok(None);
+    };
+    let policy = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|err| eyre!("Invalid policy {}: {}", path.display(), err))?;
+    Ok(Some(policy))
+}
+
+/// Processing does not keep the system calls, so they are recorded for the reports that include
+/// them. Returns the receiver to process the events from, and the task that records them
+fn record_for_reports(
+    args: &Args,
+    rx: Receiver<TraceEvent>,
+) -> (Receiver<TraceEvent>, Option<JoinHandle<Vec<TraceEvent>>>) {
+    if args.report_chrome_trace.is_some() || args.report_sqlite.is_some() {
+        let (rx, recorder) = export::record_events(rx);
+        (rx, Some(recorder))
+    } else {
+        (rx, None)
+    }
+}
+
+/// Waits for processing to finish, then writes the requested reports about the trace of
+/// `command`, which ran in `working_dir`
+async fn write_reports(
+    args: &Args,
+    done_wait: &Semaphore,
+    shared_state: &Mutex<Option<ProcessingData>>,
+    recorder: Option<JoinHandle<Vec<TraceEvent>>>,
+    command: &str,
+    working_dir: &Path,
+) -> Result<()> {
+    let _ = done_wait.acquire().await?;
+    let data = shared_state
+        .lock()
+        .await
+        .take()
+        .ok_or_else(|| eyre!("No data was processed"))?;
+    if let Some(path) = &args.report_html {
+        std::fs::write(path, export::html_report(&data))?;
+    }
+    if let Some(path) = &args.report_sarif {
+        let sarif = export::sarif_report(&data, command, working_dir);
+        std::fs::write(path, serde_json::to_string_pretty(&sarif)?)?;
+    }
+    let events = match recorder {
+        Some(recorder) => recorder.await?,
+        None => vec![],
+    };
+    if let Some(path) = &args.report_chrome_trace {
+        let trace = export::chrome_trace(&events, &data);
+        std::fs::write(path, serde_json::to_string(&trace)?)?;
+    }
+    if let Some(path) = &args.report_sqlite {
+        let run_id = export::write_database(path, &events, &data, command, working_dir)?;
+        debug!("added run {} to {}", run_id, path.display());
+    }
+    if let Some(path) = &args.write_policy {
+        let policy = processing::baseline_policy(&data);
+        std::fs::write(path, serde_json::to_string_pretty(&policy)?)?;
+    }
+    Ok(())
+}
+
+/// Gets the IDs of the system calls to filter out from the `--syscalls` allowlist or the
+/// `--exclude-syscalls` denylist
+fn excluded_syscalls(allowed: &[String], excluded: &[String]) -> Result<Vec<u64>> {
+    let to_ids = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| syscall_id(name).ok_or_else(|| eyre!("Unknown system call: {}", name)))
+            .collect::<Result<Vec<_>>>()
+    };
+    // execve marks the start of the traced program, and rt_sigreturn and restart_syscall show
+    // which interrupted calls were restarted when pairing events, so they cannot be filtered out
+    let required = ["execve", "execveat", "rt_sigreturn", "restart_syscall"]
+        .into_iter()
+        .filter_map(syscall_id)
+        .collect::<Vec<_>>();
+    if allowed.is_empty() {
+        let mut excluded = to_ids(excluded)?;
+        excluded.retain(|id| !required.contains(id));
+        return Ok(excluded);
+    }
+    let mut allowed = to_ids(allowed)?;
+    allowed.extend(required);
+    Ok(SYSCALL_TABLE
+        .iter()
+        .map(|(_, id)| *id)
+        .filter(|id| !allowed.contains(id))
+        .collect())
+}
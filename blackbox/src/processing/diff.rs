@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+
+use crate::types::{Alert, AlertRule, ByteChange, ProcessingData, TraceDiff};
+
+use super::sorted;
+
+/// An alert's rule ID, operation and sorted files
+type AlertKey = (&'static str, Option<String>, Vec<OsString>);
+
+/// Compares the trace `after` to the older trace `before` of the same command.
+///
+/// Processes, file descriptors and times differ between any two runs, so only what the
+/// command did is compared: the files, programs, kinds of connections and alerts
+pub fn diff_traces(before: &ProcessingData, after: &ProcessingData) -> TraceDiff {
+    let files_before = file_bytes(before);
+    let files_after = file_bytes(after);
+    let (added_files, removed_files) = compare(
+        &files_before.keys().cloned().collect(),
+        &files_after.keys().cloned().collect(),
+    );
+    let (added_directories, removed_directories) =
+        compare(&directories(before), &directories(after));
+    let (added_programs, removed_programs) = compare(
+        &before.process_summary.programs.iter().cloned().collect(),
+        &after.process_summary.programs.iter().cloned().collect(),
+    );
+    let endpoints = |data: &ProcessingData| {
+        data.network_events
+            .iter()
+            .map(|conn| (conn.domain, conn.protocol))
+            .collect::<BTreeSet<_>>()
+    };
+    let (added_endpoints, removed_endpoints) = compare(&endpoints(before), &endpoints(after));
+
+    let alerts_before = alerts(before);
+    let alerts_after = alerts(after);
+    let (added_alerts, removed_alerts) = compare(
+        &alerts_before.keys().cloned().collect(),
+        &alerts_after.keys().cloned().collect(),
+    );
+
+    let byte_changes = files_before
+        .iter()
+        .filter_map(|(path, before)| {
+            let after = *files_after.get(path)?;
+            (after != *before).then(|| ByteChange {
+                path: path.clone(),
+                before: *before,
+                after,
+            })
+        })
+        .collect();
+
+    TraceDiff {
+        added_files,
+        removed_files,
+        added_directories,
+        removed_directories,
+        added_programs,
+        removed_programs,
+        added_endpoints,
+        removed_endpoints,
+        added_alerts: added_alerts
+            .iter()
+            .map(|key| alerts_after[key].clone())
+            .collect(),
+        removed_alerts: removed_alerts
+            .iter()
+            .map(|key| alerts_before[key].clone())
+            .collect(),
+        byte_changes,
+        bytes_read: (
+            before.file_summary.bytes_read,
+            after.file_summary.bytes_read,
+        ),
+        bytes_written: (
+            before.file_summary.bytes_written,
+            after.file_summary.bytes_written,
+        ),
+    }
+}
+
+/// The items only in `after`, and the items only in `before`, in order
+fn compare<T: Ord + Clone>(before: &BTreeSet<T>, after: &BTreeSet<T>) -> (Vec<T>, Vec<T>) {
+    (
+        after.difference(before).cloned().collect(),
+        before.difference(after).cloned().collect(),
+    )
+}
+
+/// The bytes read from or written to each file. IPC channels have no path, so they are left out
+fn file_bytes(data: &ProcessingData) -> BTreeMap<OsString, usize> {
+    let mut files = BTreeMap::new();
+    for access in &data.file_events {
+        if let (Some(name), None) = (&access.file_name, access.ipc_kind) {
+            *files.entry(name.clone()).or_default() += access.data_length;
+        }
+    }
+    files
+}
+
+fn directories(data: &ProcessingData) -> BTreeSet<OsString> {
+    data.file_summary
+        .directories
+        .iter()
+        .filter(|dir| !dir.is_empty())
+        .cloned()
+        .collect()
+}
+
+/// The alerts of a trace by what they are about: their rule, operation and files. Their
+/// messages are not compared, since they name processes and count events, which differ
+/// between any two runs. The alert that no checks found anything is left out, since it only
+/// says that the others are missing
+fn alerts(data: &ProcessingData) -> BTreeMap<AlertKey, Alert> {
+    data.alerts
+        .iter()
+        .filter(|alert| alert.rule != AlertRule::NoFindings)
+        .map(|alert| {
+            let key = (
+                alert.rule.id(),
+                alert.operation.clone(),
+                sorted(alert.paths.clone()),
+            );
+            (key, alert.clone())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{AccessType, FileAccess, IpcKind};
+
+    use super::*;
+
+    fn access(pid: u32, file_name: &str, data_length: usize) -> FileAccess {
+        FileAccess {
+            pid,
+            file_name: Some(OsString::from(file_name)),
+            file_descriptor: 3,
+            data_length,
+            read_data: vec![],
+            write_data: vec![],
+            start_time: 0,
+            end_time: 0,
+            error_count: 0,
+            access_type: AccessType {
+                read: true,
+                ..Default::default()
+            },
+            data_truncated: false,
+            ipc_kind: None,
+            read_format: None,
+            write_format: None,
+        }
+    }
+
+    fn alert(rule: AlertRule, pid: u32, operation: Option<&str>, paths: &[&str]) -> Alert {
+        Alert {
+            rule,
+            severity: 1,
+            message: format!("{} by process {pid}", rule.id()),
+            operation: operation.map(String::from),
+            pid: Some(pid),
+            time: None,
+            paths: paths.iter().map(OsString::from).collect(),
+        }
+    }
+
+    fn names(paths: &[OsString]) -> Vec<&str> {
+        paths.iter().map(|path| path.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn compares_files_by_path() {
+        let pipe = FileAccess {
+            ipc_kind: Some(IpcKind::Pipe),
+            ..access(1, "pipe:[1234]", 100)
+        };
+        let before = ProcessingData {
+            file_events: vec![
+                access(1, "/etc/hosts", 10),
+                access(1, "/etc/passwd", 5),
+                pipe,
+            ],
+            ..Default::default()
+        };
+        // other processes, and the same file accessed twice
+        let after = ProcessingData {
+            file_events: vec![
+                access(2, "/etc/passwd", 3),
+                access(3, "/etc/passwd", 4),
+                access(2, "/etc/group", 0),
+            ],
+            ..Default::default()
+        };
+
+        let diff = diff_traces(&before, &after);
+        assert_eq!(names(&diff.added_files), ["/etc/group"]);
+        assert_eq!(names(&diff.removed_files), ["/etc/hosts"]);
+        let changes = diff
+            .byte_changes
+            .iter()
+            .map(|change| (change.path.to_str().unwrap(), change.before, change.after))
+            .collect::<Vec<_>>();
+        assert_eq!(changes, [("/etc/passwd", 5, 7)]);
+    }
+
+    #[test]
+    fn compares_alerts_by_rule_operation_and_files() {
+        let before = ProcessingData {
+            alerts: vec![
+                alert(
+                    AlertRule::PrivilegedOperation,
+                    10,
+                    Some("Unloaded kernel module floppy"),
+                    &[],
+                ),
+                alert(AlertRule::SystemWrite, 10, None, &["/etc/a", "/etc/b"]),
+                alert(AlertRule::RuntimeAccess, 10, None, &["/run/user/1000/bus"]),
+                alert(AlertRule::NoFindings, 10, None, &[]),
+            ],
+            ..Default::default()
+        };
+        // the same alerts from other processes, with files in another order
+        let after = ProcessingData {
+            alerts: vec![
+                alert(
+                    AlertRule::PrivilegedOperation,
+                    20,
+                    Some("Unloaded kernel module floppy"),
+                    &[],
+                ),
+                alert(
+                    AlertRule::PrivilegedOperation,
+                    20,
+                    Some("Unloaded kernel module ext4"),
+                    &[],
+                ),
+                alert(AlertRule::SystemWrite, 20, None, &["/etc/b", "/etc/a"]),
+            ],
+            ..Default::default()
+        };
+
+        let diff = diff_traces(&before, &after);
+        let added = diff
+            .added_alerts
+            .iter()
+            .map(|alert| alert.operation.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(added, [Some("Unloaded kernel module ext4")]);
+        let removed = diff
+            .removed_alerts
+            .iter()
+            .map(|alert| alert.rule)
+            .collect::<Vec<_>>();
+        assert_eq!(removed, [AlertRule::RuntimeAccess]);
+    }
+}
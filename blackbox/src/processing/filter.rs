@@ -0,0 +1,216 @@
+use std::collections::HashSet;
+
+use crate::types::{AlertRule, ProcessNode, ProcessingData};
+
+use super::summarize_processes;
+use super::tree::find_node;
+
+/// Narrows `data` down to the process `pid`, and to its descendants if `include_children`
+/// is set. The summaries are recomputed from the activity of those processes.
+///
+/// Alerts that are not attributed to a single process are kept if the selected processes
+/// used one of their files, or if they are about the trace as a whole.
+pub fn filter_processes(data: &ProcessingData, pid: u32, include_children: bool) -> ProcessingData {
+    let node = find_node(&data.process_tree, pid).map(|node| {
+        let mut node = node.clone();
+        if !include_children {
+            node.children.clear();
+        }
+        node
+    });
+    let mut pids = HashSet::from([pid]);
+    if let Some(node) = &node {
+        collect_pids(node, &mut pids);
+    }
+
+    let mut filtered = ProcessingData::default();
+    for (pid, activity) in data
+        .process_activity
+        .iter()
+        .filter(|(pid, _)| pids.contains(pid))
+    {
+        filtered.file_summary.update(&activity.file_summary);
+        filtered.network_summary.update(&activity.network_summary);
+        filtered.process_activity.insert(*pid, activity.clone());
+    }
+    filtered.file_events = data
+        .file_events
+        .iter()
+        .filter(|access| pids.contains(&access.pid))
+        .cloned()
+        .collect();
+    filtered.network_events = data
+        .network_events
+        .iter()
+        .filter(|conn| pids.contains(&conn.pid))
+        .cloned()
+        .collect();
+    // the parent of an exec is the process itself
+    filtered.process_events = data
+        .process_events
+        .iter()
+        .filter(|spawn| pids.contains(&spawn.parent_id))
+        .cloned()
+        .collect();
+    filtered.process_summary = summarize_processes(
+        &filtered.process_events,
+        node.as_ref().and_then(|node| node.exit_status),
+    );
+    filtered.process_tree = node.into_iter().collect();
+    // the files opened, programs executed and paths checked by the selected processes
+    let used_paths = filtered
+        .file_events
+        .iter()
+        .filter_map(|access| access.file_name.as_ref())
+        .chain(
+            filtered
+                .process_events
+                .iter()
+                .filter_map(|spawn| spawn.command.as_ref()),
+        )
+        .chain(
+            data.path_probes
+                .iter()
+                .filter(|probe| pids.contains(&probe.pid))
+                .map(|probe| &probe.path),
+        )
+        .collect::<HashSet<_>>();
+    filtered.alerts = data
+        .alerts
+        .iter()
+        .filter(|alert| match alert.pid {
+            Some(pid) => pids.contains(&pid),
+            None if is_about_trace(alert.rule) => true,
+            None => alert.paths.iter().any(|path| used_paths.contains(path)),
+        })
+        .cloned()
+        .collect();
+    filtered.unhandled_ids = data.unhandled_ids.clone();
+    filtered.ipc_channels = data
+        .ipc_channels
+        .iter()
+        .filter(|channel| {
+            channel
+                .writers
+                .iter()
+                .chain(&channel.readers)
+                .any(|endpoint| pids.contains(&endpoint.pid))
+        })
+        .cloned()
+        .collect();
+    filtered.path_probes = data
+        .path_probes
+        .iter()
+        .filter(|probe| pids.contains(&probe.pid))
+        .cloned()
+        .collect();
+    filtered.trace_quality = data.trace_quality.clone();
+    filtered.clock = data.clock;
+    filtered.trace_start = data.trace_start;
+    filtered
+}
+
+/// Whether alerts of `rule` are about the trace as a whole rather than what processes did
+fn is_about_trace(rule: AlertRule) -> bool {
+    matches!(
+        rule,
+        AlertRule::LostEvents | AlertRule::UnpairedEvents | AlertRule::NoFindings
+    )
+}
+
+fn collect_pids(node: &ProcessNode, pids: &mut HashSet<u32>) {
+    pids.insert(node.pid);
+    for child in &node.children {
+        collect_pids(child, pids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use crate::types::{AccessType, Alert, FileAccess};
+
+    use super::*;
+
+    fn node(pid: u32) -> ProcessNode {
+        ProcessNode {
+            pid,
+            spawn_type: None,
+            command: None,
+            arguments: vec![],
+            start_time: 0,
+            end_time: None,
+            exit_status: None,
+            namespaces: 0,
+            file_count: 0,
+            bytes_transferred: 0,
+            connection_count: 0,
+            alert_count: 0,
+            children: vec![],
+        }
+    }
+
+    fn access(pid: u32, file_name: &str) -> FileAccess {
+        FileAccess {
+            pid,
+            file_name: Some(OsString::from(file_name)),
+            file_descriptor: 3,
+            data_length: 0,
+            read_data: vec![],
+            write_data: vec![],
+            start_time: 0,
+            end_time: 0,
+            error_count: 0,
+            access_type: AccessType {
+                write: true,
+                ..Default::default()
+            },
+            data_truncated: false,
+            ipc_kind: None,
+            read_format: None,
+            write_format: None,
+        }
+    }
+
+    fn alert(rule: AlertRule, pid: Option<u32>, paths: &[&str]) -> Alert {
+        Alert {
+            rule,
+            severity: 1,
+            message: String::new(),
+            operation: None,
+            pid,
+            time: None,
+            paths: paths.iter().map(OsString::from).collect(),
+        }
+    }
+
+    #[test]
+    fn keeps_alerts_about_the_selected_processes() {
+        let data = ProcessingData {
+            process_tree: vec![node(1), node(2)],
+            file_events: vec![access(1, "/etc/hosts"), access(2, "/etc/passwd")],
+            alerts: vec![
+                alert(AlertRule::SystemWrite, None, &["/etc/hosts", "/etc/passwd"]),
+                alert(AlertRule::SystemWrite, None, &["/etc/passwd"]),
+                alert(AlertRule::RootAccess, Some(2), &["/root/.bashrc"]),
+                alert(AlertRule::CredentialProbing, Some(1), &["/home/user/.ssh"]),
+                alert(AlertRule::LostEvents, None, &[]),
+            ],
+            ..Default::default()
+        };
+
+        let filtered = filter_processes(&data, 1, true);
+        let kept = filtered
+            .alerts
+            .iter()
+            .map(|alert| (alert.rule.id(), alert.paths.len()))
+            .collect::<Vec<_>>();
+        let expected = [
+            (AlertRule::SystemWrite.id(), 2),
+            (AlertRule::CredentialProbing.id(), 1),
+            (AlertRule::LostEvents.id(), 0),
+        ];
+        assert_eq!(kept, expected);
+    }
+}
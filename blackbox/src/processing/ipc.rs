@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+
+use crate::types::{
+    CloseData, DupData, ExecveData, ForkData, IpcChannel, IpcData, IpcEndpoint, ReadData,
+    TraceEvent, TransferData, WriteData,
+};
+
+use crate::types::SyscallData::*;
+
+/// An operation on the file descriptor table of a process
+#[derive(Debug)]
+enum FdOperation {
+    Create { channel: usize, fds: Vec<i32> },
+    Dup { old_fd: i32, new_fd: i32 },
+    Fork { child: u32 },
+    Close(i32),
+    Read(i32),
+    Write { fd: i32, bytes: usize },
+    ProcessExit,
+}
+
+/// Identifies the use of a file descriptor by a single event:
+/// the thread ID, the enter timestamp and the file descriptor
+type EventKey = (u32, u64, i32);
+
+#[derive(Debug)]
+struct RecordedOperation {
+    /// the time the operation takes effect
+    timestamp: u64,
+    pid: u32,
+    key: EventKey,
+    operation: FdOperation,
+}
+
+/// Follows the file descriptors of pipes, socketpairs and eventfds through dup, fork and
+/// close, so that reads and writes on them can be linked to the channel they use.
+///
+/// Events are recorded as they are received, then [`IpcTracker::resolve`] replays them in
+/// order. Forks are ordered by the time the call started, so that the file descriptors are
+/// copied to the child before it uses them.
+#[derive(Debug, Default)]
+pub struct IpcTracker {
+    operations: Vec<RecordedOperation>,
+    /// the last command executed by each process
+    commands: HashMap<u32, OsString>,
+    channels: Vec<IpcChannel>,
+    /// the channel used by each event that created, read or wrote an IPC file descriptor
+    event_channels: HashMap<EventKey, usize>,
+}
+
+impl IpcTracker {
+    /// Records an event that may create, copy, close or use an IPC file descriptor
+    pub fn record(&mut self, event: &TraceEvent) {
+        let mut operations = vec![];
+        let mut push = |timestamp, fd, operation| {
+            operations.push(RecordedOperation {
+                timestamp,
+                pid: event.pid,
+                key: event_key(event, fd),
+                operation,
+            })
+        };
+        let exit = event.monotonic_exit_timestamp;
+        match &event.data {
+            Ipc(IpcData {
+                kind,
+                file_descriptors: Ok(fds),
+                ..
+            }) if !fds.is_empty() => {
+                let channel = self.channels.len();
+                self.channels.push(IpcChannel {
+                    kind: *kind,
+                    name: format!("{}:[{}]", kind.text().to_lowercase(), channel),
+                    writers: vec![],
+                    readers: vec![],
+                    bytes_written: 0,
+                });
+                for fd in fds {
+                    self.event_channels.insert(event_key(event, *fd), channel);
+                }
+                let fds = fds.clone();
+                push(exit, -1, FdOperation::Create { channel, fds });
+            }
+            Dup(DupData {
+                old_fd,
+                new_fd: Ok(new_fd),
+            }) => push(
+                exit,
+                *new_fd,
+                FdOperation::Dup {
+                    old_fd: *old_fd,
+                    new_fd: *new_fd,
+                },
+            ),
+            // the fd table is copied when the call starts, before the child can run.
+            // Threads share the table of their process
+            Fork(fork @ ForkData { pid: Ok(child), .. }) if !fork.is_thread() => push(
+                event.monotonic_enter_timestamp,
+                -1,
+                FdOperation::Fork { child: *child },
+            ),
+            Close(CloseData {
+                file_descriptor, ..
+            }) => push(exit, *file_descriptor, FdOperation::Close(*file_descriptor)),
+            Read(ReadData {
+                file_descriptor, ..
+            }) => push(exit, *file_descriptor, FdOperation::Read(*file_descriptor)),
+            Write(WriteData {
+                file_descriptor,
+                bytes_written,
+                ..
+            }) => push(
+                exit,
+                *file_descriptor,
+                FdOperation::Write {
+                    fd: *file_descriptor,
+                    bytes: bytes_written.unwrap_or(0),
+                },
+            ),
+            Transfer(TransferData {
+                in_fd,
+                out_fd,
+                bytes_transferred,
+                ..
+            }) => {
+                push(exit, *in_fd, FdOperation::Read(*in_fd));
+                push(
+                    exit,
+                    *out_fd,
+                    FdOperation::Write {
+                        fd: *out_fd,
+                        bytes: bytes_transferred.unwrap_or(0),
+                    },
+                );
+            }
+            Execve(ExecveData {
+                filename: Some(filename),
+                ..
+            }) => {
+                self.commands.insert(event.pid, filename.clone());
+            }
+            ProcessExit(_) => push(exit, -1, FdOperation::ProcessExit),
+            _ => {}
+        }
+        self.operations.append(&mut operations);
+    }
+
+    /// Replays the recorded events in order, linking each channel to the processes
+    /// that read from and wrote to it
+    pub fn resolve(&mut self) {
+        let mut operations = std::mem::take(&mut self.operations);
+        operations.sort_by_key(|op| op.timestamp);
+
+        // the channel each (pid, fd) currently refers to
+        let mut fd_table: HashMap<(u32, i32), usize> = HashMap::new();
+        for RecordedOperation {
+            pid,
+            key,
+            operation,
+            ..
+        } in operations
+        {
+            match operation {
+                FdOperation::Create { channel, fds } => {
+                    for fd in fds {
+                        fd_table.insert((pid, fd), channel);
+                    }
+                }
+                FdOperation::Dup { old_fd, new_fd } => match fd_table.get(&(pid, old_fd)) {
+                    Some(&channel) => {
+                        fd_table.insert((pid, new_fd), channel);
+                    }
+                    None => {
+                        // dup2 closes `new_fd` if it was open
+                        fd_table.remove(&(pid, new_fd));
+                    }
+                },
+                FdOperation::Fork { child } => {
+                    let inherited = fd_table
+                        .iter()
+                        .filter(|((owner, _), _)| *owner == pid)
+                        .map(|((_, fd), channel)| ((child, *fd), *channel))
+                        .collect::<Vec<_>>();
+                    fd_table.extend(inherited);
+                }
+                FdOperation::Close(fd) => {
+                    fd_table.remove(&(pid, fd));
+                }
+                FdOperation::Read(fd) => {
+                    if let Some(&channel) = fd_table.get(&(pid, fd)) {
+                        self.event_channels.insert(key, channel);
+                        let endpoint = self.endpoint(pid, fd);
+                        push_unique(&mut self.channels[channel].readers, endpoint);
+                    }
+                }
+                FdOperation::Write { fd, bytes } => {
+                    if let Some(&channel) = fd_table.get(&(pid, fd)) {
+                        self.event_channels.insert(key, channel);
+                        let endpoint = self.endpoint(pid, fd);
+                        let channel = &mut self.channels[channel];
+                        push_unique(&mut channel.writers, endpoint);
+                        channel.bytes_written += bytes as u64;
+                    }
+                }
+                FdOperation::ProcessExit => fd_table.retain(|(owner, _), _| *owner != pid),
+            }
+        }
+    }
+
+    /// The channel that `event` used through the file descriptor `fd`.
+    /// Only valid after [`IpcTracker::resolve`] has been called
+    pub fn channel_of(&self, event: &TraceEvent, fd: i32) -> Option<&IpcChannel> {
+        self.event_channels
+            .get(&event_key(event, fd))
+            .map(|&channel| &self.channels[channel])
+    }
+
+    /// The channels that were read from or written to
+    pub fn into_channels(self) -> Vec<IpcChannel> {
+        self.channels
+            .into_iter()
+            .filter(|channel| !channel.readers.is_empty() || !channel.writers.is_empty())
+            .collect()
+    }
+
+    fn endpoint(&self, pid: u32, file_descriptor: i32) -> IpcEndpoint {
+        IpcEndpoint {
+            pid,
+            command: self.commands.get(&pid).cloned(),
+            file_descriptor,
+        }
+    }
+}
+
+fn event_key(event: &TraceEvent, fd: i32) -> EventKey {
+    (event.thread_id, event.monotonic_enter_timestamp, fd)
+}
+
+fn push_unique(endpoints: &mut Vec<IpcEndpoint>, endpoint: IpcEndpoint) {
+    if !endpoints.contains(&endpoint) {
+        endpoints.push(endpoint);
+    }
+}
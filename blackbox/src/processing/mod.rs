@@ -1,95 +1,219 @@
 use color_eyre::eyre::Result;
 use log::warn;
 use nix::sys::socket::{AddressFamily, SockProtocol};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::types::{
-    AccessType, Alert, CloseData, Connection, ConnectionDomain, ConnectionProtocol, ExecveData,
-    ExitData, FileAccess, FileBehavior, ForkData, OpenData, ProcessingData, ReadData, ShutdownData,
-    SocketData, SpawnEvent, SpawnType, TraceEvent, UnhandledSyscallData, WriteData,
+    AccessType, Alert, AlertRule, CloseData, Connection, ConnectionDomain, ConnectionProtocol,
+    ExecveData, ExitData, ExitStatus, FileAccess, FileBehavior, ForkData, IpcChannel, IpcData,
+    ListDirectoryData, OpenData, PathProbe, Policy, PrivilegedData, ProbeData, ProcessActivity,
+    ProcessExitData, ProcessSummary, ProcessingData, ReadData, ShutdownData, SocketData,
+    SpawnEvent, SpawnType, TraceEvent, TransferData, UnhandledSyscallData, WallClock, WriteData,
 };
 
 use crate::types::SyscallData::*;
 
+mod diff;
+mod filter;
+mod ipc;
+mod payload;
+mod policy;
+mod tree;
+
+pub use diff::diff_traces;
+pub use filter::filter_processes;
+use ipc::IpcTracker;
+pub use payload::describe_payload;
+use payload::detect_format;
+use policy::apply_policy;
+pub use policy::baseline_policy;
+use tree::build_process_tree;
+pub use tree::{collect_commands, find_node};
+
 pub async fn start_processing(
     mut rx: tokio::sync::mpsc::Receiver<TraceEvent>,
+    clock: WallClock,
+    policy: Option<Policy>,
     done_notifier: Arc<tokio::sync::Semaphore>,
     shared_state: Arc<Mutex<Option<ProcessingData>>>,
 ) -> Result<()> {
     // collect trace events into hashmap
-    let mut file_hash: HashMap<i32, Vec<TraceEvent>> = HashMap::new(); // Map each event to their File_Descriptor (Same File)
+    let mut file_hash: HashMap<(u32, i32), Vec<TraceEvent>> = HashMap::new(); // Map each event to their process and File_Descriptor (Same File)
     let mut spawns: Vec<TraceEvent> = vec![];
-    let mut data = ProcessingData::default();
+    let mut exit_statuses: HashMap<u32, ExitStatus> = HashMap::new();
+    // the monotonic timestamps of the first event and the exit of each process
+    let mut start_times: HashMap<u32, u64> = HashMap::new();
+    let mut exit_times: HashMap<u32, u64> = HashMap::new();
+    let mut process_activity: HashMap<u32, ProcessActivity> = HashMap::new();
+    let mut ipc = IpcTracker::default();
+    // the paths each process opened, to tell probing apart from normal use
+    let mut opened_paths: HashSet<(u32, OsString)> = HashSet::new();
+    // the file names of the open file descriptors, to name the ones used by privileged calls
+    let mut open_files: HashMap<(u32, i32), OsString> = HashMap::new();
+    // the first event received is from the traced process
+    let mut root_pid = None;
+    let mut data = ProcessingData {
+        clock,
+        ..Default::default()
+    };
     while let Some(i) = rx.recv().await {
+        root_pid.get_or_insert(i.pid);
+        if !matches!(i.data, LostEvents(_)) {
+            start_times
+                .entry(i.pid)
+                .or_insert(i.monotonic_enter_timestamp);
+        }
+        ipc.record(&i);
         match i.clone().data {
             Open(OpenData {
-                file_descriptor, ..
+                file_descriptor,
+                filename,
+                ..
             }) => {
+                if let (Ok(fd), Some(filename)) = (file_descriptor, filename) {
+                    open_files.insert((i.pid, fd), filename.clone());
+                    opened_paths.insert((i.pid, filename));
+                }
                 if let Ok(fd) = file_descriptor {
-                    let list = file_hash.entry(fd).or_insert(vec![]);
+                    let list = file_hash.entry((i.pid, fd)).or_insert(vec![]);
                     list.push(i);
                 }
             }
             Read(ReadData {
                 file_descriptor, ..
             }) => {
-                let list = file_hash.entry(file_descriptor).or_insert(vec![]);
+                let list = file_hash.entry((i.pid, file_descriptor)).or_insert(vec![]);
                 list.push(i);
             }
             Write(WriteData {
                 file_descriptor, ..
             }) => {
-                let list = file_hash.entry(file_descriptor).or_insert(vec![]);
+                let list = file_hash.entry((i.pid, file_descriptor)).or_insert(vec![]);
                 list.push(i);
             }
+            Transfer(TransferData { in_fd, out_fd, .. }) => {
+                // the data is read from one file and written to the other
+                let list = file_hash.entry((i.pid, in_fd)).or_insert(vec![]);
+                list.push(i.clone());
+                if out_fd != in_fd {
+                    let list = file_hash.entry((i.pid, out_fd)).or_insert(vec![]);
+                    list.push(i);
+                }
+            }
+            ListDirectory(ListDirectoryData {
+                file_descriptor, ..
+            }) => {
+                let list = file_hash.entry((i.pid, file_descriptor)).or_insert(vec![]);
+                list.push(i);
+            }
+            Probe(ProbeData {
+                probe,
+                filename: Some(filename),
+                return_val,
+                ..
+            }) => {
+                // an empty path with AT_EMPTY_PATH checks the directory fd itself
+                if !filename.is_empty() {
+                    data.path_probes.push(PathProbe {
+                        probe,
+                        path: filename,
+                        pid: i.pid,
+                        time: i.monotonic_enter_timestamp,
+                        success: return_val.is_ok(),
+                    });
+                }
+            }
+            Probe(_) => {}
             Close(CloseData {
                 file_descriptor, ..
             }) => {
-                let list = file_hash.entry(file_descriptor).or_insert(vec![]);
+                open_files.remove(&(i.pid, file_descriptor));
+                let list = file_hash.entry((i.pid, file_descriptor)).or_insert(vec![]);
                 list.push(i);
             }
+            Privileged(PrivilegedData {
+                operation,
+                return_val,
+            }) => {
+                let fd_name = |fd| open_files.get(&(i.pid, fd)).cloned();
+                let description = operation.describe(fd_name);
+                data.alerts.push(Alert {
+                    rule: AlertRule::PrivilegedOperation,
+                    severity: 0,
+                    message: format!(
+                        "Critical: {} (process {}){}",
+                        description,
+                        i.pid,
+                        match return_val {
+                            Ok(()) => String::new(),
+                            Err(errno) => format!("; the call failed with error {}", -errno),
+                        }
+                    ),
+                    operation: Some(description),
+                    pid: Some(i.pid),
+                    time: Some(i.monotonic_enter_timestamp),
+                    paths: operation.paths(fd_name),
+                });
+            }
             Socket(SocketData {
                 file_descriptor, ..
             }) => {
                 if let Ok(fd) = file_descriptor {
-                    let list = file_hash.entry(fd).or_insert(vec![]);
+                    let list = file_hash.entry((i.pid, fd)).or_insert(vec![]);
                     list.push(i);
                 }
             }
             Shutdown(ShutdownData {
                 file_descriptor, ..
             }) => {
-                let list = file_hash.entry(file_descriptor).or_insert(vec![]);
+                let list = file_hash.entry((i.pid, file_descriptor)).or_insert(vec![]);
                 list.push(i);
             }
+            Ipc(IpcData {
+                file_descriptors: Ok(fds),
+                ..
+            }) => {
+                for fd in fds {
+                    let list = file_hash.entry((i.pid, fd)).or_insert(vec![]);
+                    list.push(i.clone());
+                }
+            }
+            Ipc(_) | Dup(_) => {
+                // dup only matters for following IPC channels
+            }
             Fork(ForkData { .. }) => spawns.push(i),
             Execve(ExecveData { .. }) => spawns.push(i),
             Exit(ExitData { .. }) => {
-                // the process is done
-                // TODO: handle async events sent after this point
+                // the process is exiting; its files are closed by the ProcessExit event
+            }
+            ProcessExit(ProcessExitData { status }) => {
+                exit_statuses.insert(i.pid, status);
+                exit_times.insert(i.pid, i.monotonic_exit_timestamp);
 
-                // add to lists to close
-                for (_, value) in file_hash.iter_mut() {
+                // add to lists of this process to close
+                for (_, value) in file_hash.iter_mut().filter(|((pid, _), _)| *pid == i.pid) {
                     value.push(i.clone())
                 }
-
-                break;
             }
+            Unpaired(_) => data.trace_quality.unpaired_events.push(i),
+            LostEvents(lost) => data.trace_quality.lost_events.push(lost),
             Unhandled(UnhandledSyscallData { syscall_id, .. }) => {
                 data.unhandled_ids.push(syscall_id); //not associated with any summary, just tracking ids
             }
         }
     }
-    for (_, mut value) in file_hash {
+    ipc.resolve();
+    for ((pid, fd), mut value) in file_hash {
         // begin processing all events
         // sort value by monotonic time
         value.sort_by(|a, b| a.monotonic_exit_timestamp.cmp(&b.monotonic_exit_timestamp));
 
         let mut fa = FileAccess {
+            pid,
             file_name: None,
             file_descriptor: -1,
             data_length: 0,
@@ -99,14 +223,20 @@ pub async fn start_processing(
             end_time: 0,
             error_count: 0,
             access_type: AccessType::default(),
+            data_truncated: false,
+            ipc_kind: None,
+            read_format: None,
+            write_format: None,
         };
         let mut conn = Connection {
+            pid,
             domain: ConnectionDomain::Other,
             protocol: crate::types::ConnectionProtocol::Other,
             start_time: 0,
             end_time: 0,
         };
         let mut conn_fd = -1;
+        let file_summary = &mut process_activity.entry(pid).or_default().file_summary;
 
         for event in value {
             match event.clone().data {
@@ -138,19 +268,26 @@ pub async fn start_processing(
                     }
                     fa.start_time = event.monotonic_enter_timestamp;
                 }
+                Ipc(_) => {
+                    fa.file_descriptor = fd;
+                    fa.start_time = event.monotonic_enter_timestamp;
+                    name_ipc_access(&mut fa, ipc.channel_of(&event, fd), fd);
+                }
                 Read(ReadData {
                     data_read,
                     bytes_read,
                     ..
                 }) => {
+                    name_ipc_access(&mut fa, ipc.channel_of(&event, fd), fd);
                     if let Some(mut dr) = data_read {
                         fa.access_type.read = true;
                         fa.read_data.append(&mut dr);
                     }
+                    fa.data_truncated |= event.data_truncated;
                     match bytes_read {
                         Ok(br) => {
                             fa.data_length += br;
-                            data.file_summary.bytes_read += br as u64;
+                            file_summary.bytes_read += br as u64;
                         }
                         Err(_) => {
                             fa.error_count += 1;
@@ -162,20 +299,51 @@ pub async fn start_processing(
                     bytes_written,
                     ..
                 }) => {
+                    name_ipc_access(&mut fa, ipc.channel_of(&event, fd), fd);
                     if let Some(mut dw) = data_written {
                         fa.access_type.write = true;
                         fa.write_data.append(&mut dw);
                     }
+                    fa.data_truncated |= event.data_truncated;
                     match bytes_written {
                         Ok(bw) => {
                             fa.data_length += bw;
-                            data.file_summary.bytes_written += bw as u64;
+                            file_summary.bytes_written += bw as u64;
+                        }
+                        Err(_) => {
+                            fa.error_count += 1;
+                        }
+                    }
+                }
+                Transfer(TransferData {
+                    in_fd,
+                    bytes_transferred,
+                    ..
+                }) => {
+                    name_ipc_access(&mut fa, ipc.channel_of(&event, fd), fd);
+                    // the transferred data is not captured, only counted
+                    match bytes_transferred {
+                        Ok(bt) if fd == in_fd => {
+                            fa.access_type.read = true;
+                            fa.data_length += bt;
+                            file_summary.bytes_read += bt as u64;
+                        }
+                        Ok(bt) => {
+                            fa.access_type.write = true;
+                            fa.data_length += bt;
+                            file_summary.bytes_written += bt as u64;
                         }
                         Err(_) => {
                             fa.error_count += 1;
                         }
                     }
                 }
+                ListDirectory(ListDirectoryData { bytes_read, .. }) => {
+                    fa.access_type.list = true;
+                    if bytes_read.is_err() {
+                        fa.error_count += 1;
+                    }
+                }
                 Close(CloseData {
                     file_descriptor, ..
                 }) => {
@@ -194,6 +362,8 @@ pub async fn start_processing(
                         fa.read_data.clear();
                         fa.write_data.clear();
                         fa.access_type = AccessType::default();
+                        fa.data_truncated = false;
+                        fa.ipc_kind = None;
                     } else if conn_fd == file_descriptor {
                         conn.end_time = event.monotonic_exit_timestamp;
                         data.network_events.push(conn.clone());
@@ -203,7 +373,7 @@ pub async fn start_processing(
                         warn!("File descriptor not matched in close! {file_descriptor}");
                     }
                 }
-                Exit(_) => {
+                ProcessExit(_) => {
                     if fa.file_descriptor != -1 {
                         fa.end_time = event.monotonic_exit_timestamp;
                         data.file_events.push(fa.clone());
@@ -252,24 +422,41 @@ pub async fn start_processing(
 
     for event in spawns {
         match event.clone().data {
-            Fork(ForkData { pid }) => {
-                if let Ok(pid) = pid {
+            Fork(fork) => {
+                if let Ok(pid) = fork.pid {
+                    let spawn_type = if fork.is_thread() {
+                        SpawnType::Thread
+                    } else if fork.is_vfork() {
+                        SpawnType::VFork
+                    } else {
+                        SpawnType::Fork
+                    };
                     data.process_events.push(SpawnEvent {
-                        spawn_type: SpawnType::Fork,
+                        spawn_type,
                         spawn_time: event.monotonic_enter_timestamp,
                         process_id: pid,
                         parent_id: event.pid,
                         command: None,
+                        arguments: vec![],
+                        // threads exit with their process
+                        exit_status: if fork.is_thread() {
+                            None
+                        } else {
+                            exit_statuses.get(&pid).copied()
+                        },
+                        namespaces: fork.namespaces(),
                     })
                 }
             }
-            Execve(ExecveData { filename, .. }) => {
-                if let Some(filename) = &filename {
-                    data.process_summary.programs.push(filename.clone());
-                }
+            Execve(ExecveData {
+                filename,
+                arguments,
+                ..
+            }) => {
                 if filename.is_some() {
+                    let activity = process_activity.entry(event.pid).or_default();
                     update_behavior(
-                        &mut data.file_summary.behavior,
+                        &mut activity.file_summary.behavior,
                         &AccessType {
                             execute: true,
                             ..Default::default()
@@ -283,129 +470,421 @@ pub async fn start_processing(
                     process_id: event.pid, // this does not spawn a new process, but overwrite the current one
                     parent_id: event.pid,
                     command: filename,
+                    arguments,
+                    exit_status: exit_statuses.get(&event.pid).copied(),
+                    namespaces: 0,
                 })
             }
             _ => unreachable!(),
         }
     }
 
+    for fa in data.file_events.iter_mut() {
+        fa.read_format = (!fa.read_data.is_empty()).then(|| detect_format(&fa.read_data));
+        fa.write_format = (!fa.write_data.is_empty()).then(|| detect_format(&fa.write_data));
+    }
+
     // file summary
     for fa in data.file_events.iter() {
+        let file_summary = &mut process_activity.entry(fa.pid).or_default().file_summary;
         if fa.file_descriptor < 3 {
             // stdio
-            data.file_summary.behavior.stdio.update(&fa.access_type);
+            file_summary.behavior.stdio.update(&fa.access_type);
         } else {
-            data.file_summary.access_count += 1;
+            file_summary.access_count += 1;
         }
         let Some(name) = fa.file_name.clone() else {
             continue;
         };
+        if fa.ipc_kind.is_some() {
+            // IPC channels have no path
+            continue;
+        }
         // directories first
         let path = Path::new(&name);
         if path.is_dir() {
-            data.file_summary.directories.push(name.clone());
+            file_summary.directories.push(name.clone());
         } else if let Some(parent) = path.parent() {
-            data.file_summary
+            file_summary
                 .directories
                 .push(parent.to_owned().as_os_str().to_owned());
         }
 
-        update_behavior(&mut data.file_summary.behavior, &fa.access_type, &name);
+        update_behavior(&mut file_summary.behavior, &fa.access_type, &name);
+    }
+    for probe in data.path_probes.iter() {
+        process_activity
+            .entry(probe.pid)
+            .or_default()
+            .file_summary
+            .probe_count += 1;
     }
-    data.file_summary.directories.sort();
-    data.file_summary.directories.dedup();
-    data.file_summary.directories.retain(|d| !d.is_empty());
 
     // process summary
-    let mut fork_count = 0;
-    if !data.process_events.is_empty() {
-        for spawn in data.process_events.iter() {
-            if spawn.spawn_type == SpawnType::Exec {
-                fork_count -= 1;
-            } else {
-                fork_count += 1;
-            }
-            data.process_summary.processes_created += 1;
-        }
-        if fork_count < 0 {
-            data.process_summary.most_common_spawn_type = SpawnType::Exec;
-        }
-    }
+    data.process_summary = summarize_processes(
+        &data.process_events,
+        root_pid.and_then(|pid| exit_statuses.get(&pid).copied()),
+    );
+
+    data.ipc_channels = ipc.into_channels();
 
     // network summary
     for conn in data.network_events.iter() {
-        data.network_summary.connection_count += 1;
-        data.network_summary.domains.push(conn.domain);
-        data.network_summary.protocols.push(conn.protocol);
+        let network_summary = &mut process_activity
+            .entry(conn.pid)
+            .or_default()
+            .network_summary;
+        network_summary.connection_count += 1;
+        network_summary.domains.push(conn.domain);
+        network_summary.protocols.push(conn.protocol);
+    }
+
+    // the overall summaries are the sum of the summaries of each process
+    for activity in process_activity.values_mut() {
+        let directories = &mut activity.file_summary.directories;
+        directories.sort();
+        directories.dedup();
+        directories.retain(|d| !d.is_empty());
+        let network_summary = &mut activity.network_summary;
+        network_summary.domains.sort();
+        network_summary.domains.dedup();
+        network_summary.protocols.sort();
+        network_summary.protocols.dedup();
+
+        data.file_summary.update(&activity.file_summary);
+        data.network_summary.update(&activity.network_summary);
     }
-    data.network_summary.domains.sort();
-    data.network_summary.domains.dedup();
-    data.network_summary.protocols.sort();
-    data.network_summary.protocols.dedup();
 
     // check for /root access
-    let mut accessed_root = false;
-    let mut suspicious_searching = false;
+    // the processes that accessed each kind of file
+    let mut accessed_root = HashSet::new();
+    let mut suspicious_searching = HashSet::new();
+    let mut root_time = None;
+    let mut suspicious_time = None;
+    let mut root_paths = vec![];
+    let mut suspicious_paths = vec![];
     let suspicious_regex = regex::Regex::new(r"\.[^/]+_history|^/etc/passwd$|\.aws/").unwrap();
     let root_dir_regex = regex::Regex::new(r"^/root").unwrap();
+    let home_dir_regex = regex::Regex::new(HOME_DIR_PATTERN).unwrap();
     for access in data.file_events.iter() {
         let Some(name) = &access.file_name else {
             continue;
         };
         if root_dir_regex.is_match(&name.to_string_lossy()) {
-            accessed_root = true;
+            accessed_root.insert(access.pid);
+            keep_earliest(&mut root_time, access.start_time);
+            root_paths.push(name.clone());
         }
         if suspicious_regex.is_match(&name.to_string_lossy()) {
-            suspicious_searching = true;
+            suspicious_searching.insert(access.pid);
+            keep_earliest(&mut suspicious_time, access.start_time);
+            suspicious_paths.push(name.clone());
         }
     }
-    if suspicious_searching {
+    if !suspicious_searching.is_empty() {
         data.alerts.push(Alert {
+            rule: AlertRule::SuspiciousFiles,
             severity: 1,
             message: String::from("Urgent: Suspicious files read; this could be data exfiltration"),
+            operation: None,
+            pid: single_process(&suspicious_searching),
+            time: suspicious_time,
+            paths: sorted(suspicious_paths),
         })
     }
-    if accessed_root {
+    if !accessed_root.is_empty() {
         data.alerts.push(Alert {
+            rule: AlertRule::RootAccess,
             severity: 0,
             message: String::from("Critical: Root infiltration detected!"),
+            operation: None,
+            pid: single_process(&accessed_root),
+            time: root_time,
+            paths: sorted(root_paths),
         })
     }
-    if data.file_summary.behavior.system.write {
+    for (pid, paths) in probed_without_opening(&data.path_probes, &opened_paths, &home_dir_regex) {
+        let first_probe = data
+            .path_probes
+            .iter()
+            .filter(|probe| probe.pid == pid)
+            .map(|probe| probe.time)
+            .min();
         data.alerts.push(Alert {
+            rule: AlertRule::CredentialProbing,
+            severity: 1,
+            message: format!(
+                "Urgent: Process {pid} checked {} home directory or hidden files without \
+                 opening them; this could be searching for credentials",
+                paths.len()
+            ),
+            operation: None,
+            pid: Some(pid),
+            time: first_probe,
+            paths,
+        });
+    }
+    // the processes whose file behavior matches
+    let behaving = |matches: fn(&FileBehavior) -> bool| {
+        process_activity
+            .iter()
+            .filter(|(_, activity)| matches(&activity.file_summary.behavior))
+            .map(|(pid, _)| *pid)
+            .collect::<HashSet<_>>()
+    };
+    let writing_system = behaving(|b| b.system.write);
+    if !writing_system.is_empty() {
+        let (paths, time) = matching_paths(&data, |b| b.system.write);
+        data.alerts.push(Alert {
+            rule: AlertRule::SystemWrite,
             severity: 1,
             message: String::from("Urgent: Attempting to write into system"),
+            operation: None,
+            pid: single_process(&writing_system),
+            time,
+            paths,
         });
     }
-    if data.file_summary.behavior.current_dir.execute {
+    let executing_current_dir = behaving(|b| b.current_dir.execute);
+    if !executing_current_dir.is_empty() {
+        let (paths, time) = matching_paths(&data, |b| b.current_dir.execute);
         data.alerts.push(Alert {
+            rule: AlertRule::CurrentDirectoryExecution,
             severity: 1,
             message: String::from("Warning: Attempting to execute in current directory"),
+            operation: None,
+            pid: single_process(&executing_current_dir),
+            time,
+            paths,
         });
     }
-    if data.file_summary.behavior.home_dir.execute || data.file_summary.behavior.runtime.execute {
+    let executing_non_system = behaving(|b| b.home_dir.execute || b.runtime.execute);
+    if !executing_non_system.is_empty() {
+        let (paths, time) = matching_paths(&data, |b| b.home_dir.execute || b.runtime.execute);
         data.alerts.push(Alert {
+            rule: AlertRule::NonSystemExecution,
             severity: 2,
             message: String::from("Caution: Attempting to execute from non-system directory"),
+            operation: None,
+            pid: single_process(&executing_non_system),
+            time,
+            paths,
         });
     }
-    if data.file_summary.behavior.runtime.write || data.file_summary.behavior.runtime.read {
+    let accessing_runtime = behaving(|b| b.runtime.write || b.runtime.read);
+    if !accessing_runtime.is_empty() {
+        let (paths, time) = matching_paths(&data, |b| b.runtime.write || b.runtime.read);
         data.alerts.push(Alert {
+            rule: AlertRule::RuntimeAccess,
             severity: 3,
             message: String::from("Note: Unexpected access of runtime directories"),
+            operation: None,
+            pid: single_process(&accessing_runtime),
+            time,
+            paths,
         });
     }
+    if let Some(policy) = &policy {
+        apply_policy(&mut data, policy);
+    }
     if data.alerts.is_empty() {
         data.alerts.push(Alert {
+            rule: AlertRule::NoFindings,
             severity: 4,
             message: String::from("No suspicious activity detected"),
+            operation: None,
+            pid: None,
+            time: None,
+            paths: vec![],
         });
     }
+    let lost_count = data.trace_quality.lost_count();
+    if lost_count > 0 {
+        data.alerts.insert(
+            0,
+            Alert {
+                rule: AlertRule::LostEvents,
+                severity: 1,
+                message: format!(
+                    "Urgent: The trace is incomplete; {lost_count} events were lost, so files, \
+                     connections or processes may be missing from this report"
+                ),
+                operation: None,
+                pid: None,
+                time: None,
+                paths: vec![],
+            },
+        );
+    }
+    if !data.trace_quality.unpaired_events.is_empty() {
+        data.alerts.push(Alert {
+            rule: AlertRule::UnpairedEvents,
+            severity: 3,
+            message: format!(
+                "Note: {} system call events could not be paired with their enter or exit; \
+                 the trace may be incomplete",
+                data.trace_quality.unpaired_events.len()
+            ),
+            operation: None,
+            pid: None,
+            time: None,
+            paths: vec![],
+        });
+    }
+    // after the alerts, so that they can be counted for each process
+    for pid in data.alerts.iter().filter_map(|alert| alert.pid) {
+        process_activity.entry(pid).or_default().alert_count += 1;
+    }
+    data.process_activity = process_activity;
+    data.trace_start = start_times.values().min().copied().unwrap_or(0);
+    data.process_tree = build_process_tree(&data, &start_times, &exit_times, &exit_statuses);
     *shared_state.lock().await = Some(data);
     done_notifier.add_permits(1);
     Ok(())
 }
 
+/// Summarizes the processes and threads created, and the programs executed
+fn summarize_processes(spawns: &[SpawnEvent], exit_status: Option<ExitStatus>) -> ProcessSummary {
+    let mut summary = ProcessSummary {
+        programs: vec![],
+        processes_created: 0,
+        threads_created: 0,
+        most_common_spawn_type: SpawnType::Fork,
+        exit_status,
+    };
+    let mut fork_count = 0;
+    for spawn in spawns {
+        match spawn.spawn_type {
+            SpawnType::Thread => {
+                summary.threads_created += 1;
+                continue;
+            }
+            SpawnType::Exec => {
+                fork_count -= 1;
+                if let Some(command) = &spawn.command {
+                    summary.programs.push(command.clone());
+                }
+            }
+            SpawnType::Fork | SpawnType::VFork => fork_count += 1,
+        }
+        summary.processes_created += 1;
+    }
+    if fork_count < 0 {
+        summary.most_common_spawn_type = SpawnType::Exec;
+    }
+    summary
+}
+
+/// Replaces `earliest` with `time` if `time` is earlier
+fn keep_earliest(earliest: &mut Option<u64>, time: u64) {
+    if !matches!(earliest, Some(earliest) if *earliest <= time) {
+        *earliest = Some(time);
+    }
+}
+
+/// Sorts `paths` and removes duplicates
+fn sorted(mut paths: Vec<OsString>) -> Vec<OsString> {
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// The files and executed programs whose access matches, such as the system files written to,
+/// and the monotonic timestamp of the first matching access
+fn matching_paths(
+    data: &ProcessingData,
+    matches: fn(&FileBehavior) -> bool,
+) -> (Vec<OsString>, Option<u64>) {
+    let execute = AccessType {
+        execute: true,
+        ..Default::default()
+    };
+    let files = data
+        .file_events
+        .iter()
+        .filter(|fa| fa.file_descriptor >= 3 && fa.ipc_kind.is_none())
+        .filter_map(|fa| Some((fa.file_name.as_ref()?, fa.access_type, fa.start_time)));
+    let programs = data
+        .process_events
+        .iter()
+        .filter_map(|spawn| Some((spawn.command.as_ref()?, execute, spawn.spawn_time)));
+    let mut paths = vec![];
+    let mut first = None;
+    for (name, access_type, time) in files.chain(programs) {
+        let mut behavior = FileBehavior::default();
+        update_behavior(&mut behavior, &access_type, name);
+        if matches(&behavior) {
+            paths.push(name.clone());
+            keep_earliest(&mut first, time);
+        }
+    }
+    (sorted(paths), first)
+}
+
+/// The process in `pids`, if there is only one
+fn single_process(pids: &HashSet<u32>) -> Option<u32> {
+    match pids.iter().collect::<Vec<_>>()[..] {
+        [pid] => Some(*pid),
+        _ => None,
+    }
+}
+
+/// The number of distinct home directory or hidden paths probed by a process before it is
+/// reported as searching for files
+const PROBE_ALERT_THRESHOLD: usize = 20;
+
+/// Matches the paths in home directories, including `~` paths that were not expanded
+const HOME_DIR_PATTERN: &str = r"^(~(/|$)|/home(/|$)|/root(/|$))";
+
+/// Finds the processes that probed at least [`PROBE_ALERT_THRESHOLD`] home directory or
+/// hidden paths that they never opened. `home_dir_regex` is compiled from
+/// [`HOME_DIR_PATTERN`]. Returns the process IDs and the sorted paths.
+///
+/// Relative paths other than `~` paths are skipped, since the working directory of the
+/// process is not known, so they cannot be compared with the absolute paths it opened
+fn probed_without_opening(
+    probes: &[PathProbe],
+    opened_paths: &HashSet<(u32, OsString)>,
+    home_dir_regex: &regex::Regex,
+) -> Vec<(u32, Vec<OsString>)> {
+    let mut probed: HashMap<u32, HashSet<&OsString>> = HashMap::new();
+    for probe in probes {
+        let path = Path::new(&probe.path);
+        if path.is_relative() && !path.starts_with("~") {
+            continue;
+        }
+        let is_hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if !is_hidden && !home_dir_regex.is_match(&probe.path.to_string_lossy()) {
+            continue;
+        }
+        if opened_paths.contains(&(probe.pid, probe.path.clone())) {
+            continue;
+        }
+        probed.entry(probe.pid).or_default().insert(&probe.path);
+    }
+    let mut suspicious = probed
+        .into_iter()
+        .filter(|(_, paths)| paths.len() >= PROBE_ALERT_THRESHOLD)
+        .map(|(pid, paths)| (pid, sorted(paths.into_iter().cloned().collect())))
+        .collect::<Vec<_>>();
+    suspicious.sort();
+    suspicious
+}
+
+/// Names a file access that uses an IPC channel, unless it already has a name
+fn name_ipc_access(fa: &mut FileAccess, channel: Option<&IpcChannel>, fd: i32) {
+    let Some(channel) = channel else {
+        return;
+    };
+    if fa.file_name.is_none() {
+        fa.file_name = Some(OsString::from(&channel.name));
+        fa.file_descriptor = fd;
+        fa.ipc_kind = Some(channel.kind);
+    }
+}
+
 fn update_behavior(behavior: &mut FileBehavior, access_type: &AccessType, name: &OsString) {
     let path = Path::new(&name);
     let name = name.to_string_lossy();
@@ -427,3 +906,70 @@ fn update_behavior(behavior: &mut FileBehavior, access_type: &AccessType, name:
         behavior.runtime.update(access_type);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::types::ProbeType;
+
+    use super::*;
+
+    fn probes(pid: u32, paths: impl Iterator<Item = String>) -> Vec<PathProbe> {
+        paths
+            .map(|path| PathProbe {
+                probe: ProbeType::Stat,
+                path: OsString::from(path),
+                pid,
+                time: 0,
+                success: false,
+            })
+            .collect()
+    }
+
+    fn probing_pids(probes: &[PathProbe], opened_paths: &HashSet<(u32, OsString)>) -> Vec<u32> {
+        let home_dir_regex = regex::Regex::new(HOME_DIR_PATTERN).unwrap();
+        probed_without_opening(probes, opened_paths, &home_dir_regex)
+            .into_iter()
+            .map(|(pid, _)| pid)
+            .collect()
+    }
+
+    #[test]
+    fn finds_processes_probing_home_directories() {
+        let threshold = PROBE_ALERT_THRESHOLD;
+        let mut all = probes(1, (0..threshold).map(|i| format!("/home/user/file{i}")));
+        all.extend(probes(2, (0..threshold).map(|i| format!("/root/file{i}"))));
+        all.extend(probes(
+            3,
+            (0..threshold).map(|i| format!("/etc/.hidden{i}")),
+        ));
+        // not in a home directory, and not hidden
+        all.extend(probes(
+            4,
+            (0..threshold).map(|i| format!("/homework/file{i}")),
+        ));
+        all.extend(probes(
+            5,
+            (0..threshold).map(|i| format!("/rootfs/file{i}")),
+        ));
+        // relative to an unknown directory
+        all.extend(probes(
+            6,
+            (0..threshold).map(|i| format!(".config/file{i}/.x")),
+        ));
+        all.extend(probes(7, (0..threshold).map(|i| format!("~/.file{i}"))));
+        assert_eq!(probing_pids(&all, &HashSet::new()), [1, 2, 3, 7]);
+    }
+
+    #[test]
+    fn ignores_probed_paths_that_were_opened() {
+        let all = probes(
+            1,
+            (0..PROBE_ALERT_THRESHOLD).map(|i| format!("/home/user/.f{i}")),
+        );
+        let opened = HashSet::from([(1, OsString::from("/home/user/.f0"))]);
+        assert!(probing_pids(&all, &opened).is_empty());
+        // another process opened it
+        let opened = HashSet::from([(2, OsString::from("/home/user/.f0"))]);
+        assert_eq!(probing_pids(&all, &opened), [1]);
+    }
+}
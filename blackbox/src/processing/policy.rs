@@ -0,0 +1,208 @@
+use std::collections::BTreeSet;
+use std::ffi::{OsStr, OsString};
+
+use crate::types::{Alert, AlertRule, Policy, ProcessingData};
+
+use super::sorted;
+
+/// Removes the alerts about behavior expected by `policy`, and adds alerts for the files,
+/// programs and connections that it does not expect.
+///
+/// Alerts about operations are removed if the operation is listed. Alerts about files keep
+/// only the files that are not expected, and are removed if none are left. Other alerts are
+/// only removed if their rule is listed
+pub fn apply_policy(data: &mut ProcessingData, policy: &Policy) {
+    let expected =
+        |path: &OsStr| matches_any(&policy.paths, path) || matches_any(&policy.executables, path);
+    data.alerts.retain_mut(|alert| {
+        if policy.rules.iter().any(|rule| rule == alert.rule.id()) {
+            return false;
+        }
+        if let Some(operation) = operation(alert) {
+            return !policy.operations.contains(&operation);
+        }
+        if alert.paths.is_empty() {
+            return true;
+        }
+        alert.paths.retain(|path| !expected(path));
+        !alert.paths.is_empty()
+    });
+
+    let programs = sorted(
+        data.process_summary
+            .programs
+            .iter()
+            .filter(|program| !matches_any(&policy.executables, program))
+            .cloned()
+            .collect(),
+    );
+    if !programs.is_empty() {
+        data.alerts.push(Alert {
+            rule: AlertRule::PolicyDeviation,
+            severity: 2,
+            message: format!(
+                "Caution: {} programs not in the policy were executed",
+                programs.len()
+            ),
+            operation: None,
+            pid: None,
+            time: None,
+            paths: programs,
+        });
+    }
+    let files = sorted(
+        accessed_files(data)
+            .into_iter()
+            .filter(|file| !matches_any(&policy.paths, file))
+            .collect(),
+    );
+    if !files.is_empty() {
+        data.alerts.push(Alert {
+            rule: AlertRule::PolicyDeviation,
+            severity: 3,
+            message: format!(
+                "Note: {} files not in the policy were accessed",
+                files.len()
+            ),
+            operation: None,
+            pid: None,
+            time: None,
+            paths: files,
+        });
+    }
+    let endpoints = endpoints(data)
+        .into_iter()
+        .filter(|endpoint| !policy.endpoints.contains(endpoint))
+        .collect::<Vec<_>>();
+    if !endpoints.is_empty() {
+        data.alerts.push(Alert {
+            rule: AlertRule::PolicyDeviation,
+            severity: 2,
+            message: format!(
+                "Caution: Connections not in the policy were made: {}",
+                endpoints.join(", ")
+            ),
+            operation: None,
+            pid: None,
+            time: None,
+            paths: vec![],
+        });
+    }
+}
+
+/// A policy that expects everything the traced command did, to compare later runs of the
+/// command against.
+///
+/// Alerts about operations are expected by listing each operation, so that other operations
+/// of the same rule are still reported. Incomplete traces are not expected, so alerts about
+/// the trace itself are left out
+pub fn baseline_policy(data: &ProcessingData) -> Policy {
+    let text = |paths: Vec<OsString>| {
+        paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    };
+    let executables = sorted(data.process_summary.programs.clone());
+    // alerts can be about files that were only probed, so they are expected as well
+    let mut paths = accessed_files(data);
+    for alert in data.alerts.iter().filter(|alert| alert.operation.is_none()) {
+        paths.extend(
+            alert
+                .paths
+                .iter()
+                .filter(|path| !executables.contains(path))
+                .cloned(),
+        );
+    }
+    let operations = data
+        .alerts
+        .iter()
+        .filter_map(operation)
+        .collect::<BTreeSet<_>>();
+    Policy {
+        paths: text(sorted(paths)),
+        executables: text(executables),
+        endpoints: endpoints(data).into_iter().collect(),
+        operations: operations.into_iter().collect(),
+        rules: vec![],
+    }
+}
+
+/// The operation an alert is about, as it is listed in policies, e.g. `BB001 Unloaded kernel
+/// module floppy`
+fn operation(alert: &Alert) -> Option<String> {
+    let operation = alert.operation.as_ref()?;
+    Some(format!("{} {}", alert.rule.id(), operation))
+}
+
+/// Whether `path` is one of `patterns`, or is in a directory of `patterns` ending in `/`
+fn matches_any(patterns: &[String], path: &OsStr) -> bool {
+    let path = path.to_string_lossy();
+    patterns.iter().any(|pattern| {
+        if pattern.ends_with('/') {
+            path.starts_with(pattern.as_str())
+        } else {
+            *pattern == path
+        }
+    })
+}
+
+/// The files opened by the traced processes. IPC channels have no path, so they are left out
+fn accessed_files(data: &ProcessingData) -> Vec<OsString> {
+    data.file_events
+        .iter()
+        .filter(|access| access.ipc_kind.is_none())
+        .filter_map(|access| access.file_name.clone())
+        .collect()
+}
+
+/// The kinds of connections made, such as `TCP IPv4`
+fn endpoints(data: &ProcessingData) -> BTreeSet<String> {
+    data.network_events
+        .iter()
+        .map(|conn| format!("{} {}", conn.protocol.text(), conn.domain.text()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privileged(operation: &str) -> Alert {
+        Alert {
+            rule: AlertRule::PrivilegedOperation,
+            severity: 0,
+            message: format!("Critical: {operation} (process 100)"),
+            operation: Some(String::from(operation)),
+            pid: Some(100),
+            time: Some(0),
+            paths: vec![],
+        }
+    }
+
+    #[test]
+    fn baselines_expect_only_the_operations_done() {
+        let baseline = ProcessingData {
+            alerts: vec![privileged("Unloaded kernel module floppy")],
+            ..Default::default()
+        };
+        let policy = baseline_policy(&baseline);
+        assert!(policy.rules.is_empty());
+
+        let mut data = ProcessingData {
+            alerts: vec![
+                privileged("Unloaded kernel module floppy"),
+                privileged("Unloaded kernel module ext4"),
+            ],
+            ..Default::default()
+        };
+        apply_policy(&mut data, &policy);
+        let operations = data
+            .alerts
+            .iter()
+            .filter_map(|alert| alert.operation.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(operations, ["Unloaded kernel module ext4"]);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use crate::types::{ExitStatus, ProcessActivity, ProcessNode, ProcessingData, SpawnType};
+
+/// Builds the tree of the traced processes from the spawn events in `data`, with the files,
+/// connections and alerts of each process from `data.process_activity`.
+///
+/// `start_times` and `exit_times` hold the monotonic timestamps of the first event and of
+/// the exit of each process, and `exit_statuses` how each process exited. The roots are
+/// the processes whose parent was not traced, which is normally only the traced program.
+pub fn build_process_tree(
+    data: &ProcessingData,
+    start_times: &HashMap<u32, u64>,
+    exit_times: &HashMap<u32, u64>,
+    exit_statuses: &HashMap<u32, ExitStatus>,
+) -> Vec<ProcessNode> {
+    let mut nodes: HashMap<u32, ProcessNode> = HashMap::new();
+    let mut parents: HashMap<u32, u32> = HashMap::new();
+    let no_activity = ProcessActivity::default();
+    let new_node = |pid: u32| {
+        let activity = data.process_activity.get(&pid).unwrap_or(&no_activity);
+        ProcessNode {
+            pid,
+            spawn_type: None,
+            command: None,
+            arguments: vec![],
+            start_time: start_times.get(&pid).copied().unwrap_or(0),
+            end_time: exit_times.get(&pid).copied(),
+            exit_status: exit_statuses.get(&pid).copied(),
+            namespaces: 0,
+            file_count: activity.file_summary.access_count,
+            bytes_transferred: activity.file_summary.bytes_read
+                + activity.file_summary.bytes_written,
+            connection_count: activity.network_summary.connection_count,
+            alert_count: activity.alert_count,
+            children: vec![],
+        }
+    };
+
+    for &pid in start_times.keys() {
+        nodes.insert(pid, new_node(pid));
+    }
+    for spawn in &data.process_events {
+        match spawn.spawn_type {
+            // threads are part of their process
+            SpawnType::Thread => {}
+            SpawnType::Fork | SpawnType::VFork => {
+                parents.insert(spawn.process_id, spawn.parent_id);
+                let node = nodes
+                    .entry(spawn.process_id)
+                    .or_insert_with(|| new_node(spawn.process_id));
+                node.spawn_type = Some(spawn.spawn_type);
+                node.start_time = spawn.spawn_time;
+                node.namespaces = spawn.namespaces;
+            }
+            SpawnType::Exec => {
+                // the process runs the last program it executed
+                let node = nodes
+                    .entry(spawn.process_id)
+                    .or_insert_with(|| new_node(spawn.process_id));
+                node.command = spawn.command.clone();
+                node.arguments = spawn.arguments.clone();
+            }
+        }
+    }
+
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut roots = vec![];
+    for &pid in nodes.keys() {
+        match parents.get(&pid) {
+            Some(parent) if nodes.contains_key(parent) => {
+                children.entry(*parent).or_default().push(pid)
+            }
+            _ => roots.push(pid),
+        }
+    }
+    let mut roots = roots
+        .into_iter()
+        .map(|pid| take_subtree(pid, &mut nodes, &children))
+        .collect::<Vec<_>>();
+    roots.sort_by_key(|node| node.start_time);
+    roots
+}
+
+/// Removes the node of `pid` from `nodes`, with its children
+fn take_subtree(
+    pid: u32,
+    nodes: &mut HashMap<u32, ProcessNode>,
+    children: &HashMap<u32, Vec<u32>>,
+) -> ProcessNode {
+    let mut node = nodes.remove(&pid).unwrap();
+    node.children = children
+        .get(&pid)
+        .into_iter()
+        .flatten()
+        .map(|child| take_subtree(*child, nodes, children))
+        .collect();
+    node.children.sort_by_key(|child| child.start_time);
+    node
+}
+
+/// Finds the process `pid` in a process tree
+pub fn find_node(nodes: &[ProcessNode], pid: u32) -> Option<&ProcessNode> {
+    nodes.iter().find_map(|node| {
+        if node.pid == pid {
+            Some(node)
+        } else {
+            find_node(&node.children, pid)
+        }
+    })
+}
+
+/// Adds the last program executed by each process in a process tree to `commands`
+pub fn collect_commands(nodes: &[ProcessNode], commands: &mut HashMap<u32, String>) {
+    for node in nodes {
+        if let Some(command) = &node.command {
+            commands.insert(node.pid, command.to_string_lossy().into_owned());
+        }
+        collect_commands(&node.children, commands);
+    }
+}
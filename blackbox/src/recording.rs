@@ -0,0 +1,292 @@
+use std::io::{self, BufRead, ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+use color_eyre::eyre::{bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::types::{TraceEvent, WallClock};
+
+/// The bytes at the start of every recording
+const MAGIC: &[u8; 8] = b"BBTRACE\0";
+/// The version of the record format, increased whenever the header or `TraceEvent` change in
+/// a way older readers cannot read
+const FORMAT_VERSION: u32 = 2;
+/// The zstd compression level; higher levels are too slow to keep up with busy traces
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Describes the trace stored in a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    /// The version of blackbox that recorded the trace
+    pub blackbox_version: String,
+    /// The release of the kernel the trace was recorded on, e.g. `6.5.0-14-generic`
+    pub kernel: String,
+    /// The architecture the trace was recorded on, e.g. `x86_64`. System call IDs depend on it
+    pub arch: String,
+    /// The traced command
+    pub command: String,
+    /// The directory the command was run in
+    pub working_dir: PathBuf,
+    /// Converts the monotonic timestamps of the events to wall-clock time
+    pub clock: WallClock,
+}
+
+impl RecordingHeader {
+    /// The header of a trace of `command` recorded on this machine
+    pub fn new(command: &str, working_dir: PathBuf, clock: WallClock) -> Self {
+        let kernel = match nix::sys::utsname::uname() {
+            Ok(uts) => uts.release().to_string_lossy().into_owned(),
+            Err(_) => String::from("unknown"),
+        };
+        Self {
+            blackbox_version: String::from(env!("CARGO_PKG_VERSION")),
+            kernel,
+            arch: String::from(std::env::consts::ARCH),
+            command: String::from(command),
+            working_dir,
+            clock,
+        }
+    }
+}
+
+/// Writes events in the binary recording format: the magic bytes and format version, followed
+/// by a zstd stream of records. Each record is a little-endian u32 length and a bincode value;
+/// the first is the [`RecordingHeader`] and the rest are events.
+///
+/// Unlike JSON, captured data is stored as raw bytes, so recordings are much smaller.
+pub struct TraceWriter<W: Write> {
+    encoder: zstd::Encoder<'static, W>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut writer: W, header: &RecordingHeader) -> Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        let mut trace_writer = Self {
+            encoder: zstd::Encoder::new(writer, COMPRESSION_LEVEL)?,
+        };
+        trace_writer.write_record(header)?;
+        Ok(trace_writer)
+    }
+
+    pub fn write(&mut self, event: &TraceEvent) -> Result<()> {
+        self.write_record(event)
+    }
+
+    /// Ends the zstd stream. Recordings that are not finished cannot be read to the end
+    pub fn finish(self) -> Result<W> {
+        Ok(self.encoder.finish()?)
+    }
+
+    fn write_record<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        let record = bincode::serialize(value)?;
+        self.encoder
+            .write_all(&(record.len() as u32).to_le_bytes())?;
+        self.encoder.write_all(&record)?;
+        Ok(())
+    }
+}
+
+/// Reads the events of a recording written by [`TraceWriter`]
+pub struct TraceReader<R: BufRead> {
+    decoder: zstd::Decoder<'static, R>,
+    header: RecordingHeader,
+    /// Set after an error, since nothing more can be read from the stream
+    failed: bool,
+}
+
+impl<R: BufRead> TraceReader<R> {
+    /// Reads the header of a recording. Fails if the reader is not a recording, was written in
+    /// another version of the format, or was recorded on another architecture, whose system
+    /// call IDs would be read as the wrong calls
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("Not a blackbox recording; recordings are written with `--format binary`");
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            bail!(
+                "Recording format version {} is not supported; expected version {}",
+                version,
+                FORMAT_VERSION
+            );
+        }
+        let mut decoder = zstd::Decoder::with_buffer(reader)?;
+        let Some(header) = read_record::<RecordingHeader>(&mut decoder)? else {
+            bail!("The recording has no header");
+        };
+        if header.arch != std::env::consts::ARCH {
+            bail!(
+                "The recording is from {}, and can only be read by blackbox on {}",
+                header.arch,
+                std::env::consts::ARCH
+            );
+        }
+        Ok(Self {
+            decoder,
+            header,
+            failed: false,
+        })
+    }
+
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+}
+
+impl<R: BufRead> Iterator for TraceReader<R> {
+    type Item = Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let record = read_record(&mut self.decoder).transpose();
+        self.failed = matches!(record, Some(Err(_)));
+        record
+    }
+}
+
+/// Reads a record, or None at the end of the recording
+fn read_record<T: DeserializeOwned>(reader: &mut impl Read) -> Result<Option<T>> {
+    let mut length = [0; 4];
+    // the recording can only end cleanly between records. The zstd decoder also reports
+    // recordings that were not finished as an unexpected end of file, which must not be
+    // mistaken for the end
+    if reader.read(&mut length[..1]).map_err(truncated)? == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut length[1..]).map_err(truncated)?;
+    let mut record = vec![0; u32::from_le_bytes(length) as usize];
+    reader.read_exact(&mut record).map_err(truncated)?;
+    Ok(Some(bincode::deserialize(&record)?))
+}
+
+fn truncated(err: io::Error) -> io::Error {
+    match err.kind() {
+        ErrorKind::UnexpectedEof => io::Error::new(err.kind(), "The recording is truncated"),
+        _ => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use crate::types::{ExitStatus, ProcessExitData, ReadData, SyscallData, PROCESS_EXIT_ID};
+
+    use super::*;
+
+    fn header() -> RecordingHeader {
+        RecordingHeader::new(
+            "cat /etc/hostname",
+            PathBuf::from("/"),
+            WallClock::default(),
+        )
+    }
+
+    fn events() -> Vec<TraceEvent> {
+        let event = |syscall_id, timestamp, data| TraceEvent {
+            pid: 100,
+            thread_id: 100,
+            syscall_id,
+            monotonic_enter_timestamp: timestamp,
+            monotonic_exit_timestamp: timestamp + 1,
+            enter_time: SystemTime::UNIX_EPOCH,
+            exit_time: SystemTime::UNIX_EPOCH,
+            data_truncated: false,
+            data,
+        };
+        vec![
+            event(
+                0,
+                10,
+                SyscallData::Read(ReadData {
+                    file_descriptor: 3,
+                    count: 4096,
+                    data_read: Some(b"localhost\n".to_vec()),
+                    bytes_read: Ok(10),
+                    offset: None,
+                }),
+            ),
+            event(
+                PROCESS_EXIT_ID,
+                20,
+                SyscallData::ProcessExit(ProcessExitData {
+                    status: ExitStatus::Exited(0),
+                }),
+            ),
+        ]
+    }
+
+    fn record(events: &[TraceEvent]) -> Vec<u8> {
+        let mut writer = TraceWriter::new(Vec::new(), &header()).unwrap();
+        for event in events {
+            writer.write(event).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn reads_what_was_written() {
+        let events = events();
+        let recording = record(&events);
+
+        let reader = TraceReader::new(recording.as_slice()).unwrap();
+        assert_eq!(reader.header().command, header().command);
+        let read = reader.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(format!("{read:?}"), format!("{events:?}"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(TraceReader::new(&b"{\"pid\": 100}\n"[..]).is_err());
+    }
+
+    #[test]
+    fn rejects_other_architectures() {
+        let header = RecordingHeader {
+            arch: String::from("riscv64"),
+            ..header()
+        };
+        let recording = TraceWriter::new(Vec::new(), &header)
+            .and_then(TraceWriter::finish)
+            .unwrap();
+        assert!(TraceReader::new(recording.as_slice()).is_err());
+    }
+
+    #[test]
+    fn reports_truncated_recordings() {
+        // enough data that does not compress well for the recording to be compressed in
+        // several blocks, so that the start of a truncated recording can still be read
+        let mut state = 1u32;
+        let events: Vec<_> = (0..500)
+            .map(|_| {
+                let mut event = events().remove(0);
+                let data = (0..1024).map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                });
+                if let SyscallData::Read(read) = &mut event.data {
+                    read.data_read = Some(data.collect());
+                }
+                event
+            })
+            .collect();
+        let recording = record(&events);
+        let truncated = &recording[..recording.len() / 2];
+
+        let reader = TraceReader::new(truncated).unwrap();
+        let read = reader.collect::<Vec<_>>();
+        assert!(read.len() > 1 && read.len() < events.len());
+        assert!(read[..read.len() - 1].iter().all(Result::is_ok));
+        let err = read.last().unwrap().as_ref().unwrap_err();
+        assert_eq!(err.to_string(), "The recording is truncated");
+    }
+}
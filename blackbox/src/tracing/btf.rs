@@ -0,0 +1,86 @@
+use std::fs;
+
+use color_eyre::eyre::{bail, eyre, Result};
+
+/// The type information the kernel exposes about itself
+const VMLINUX_BTF: &str = "/sys/kernel/btf/vmlinux";
+const BTF_MAGIC: u16 = 0xeb9f;
+const BTF_KIND_STRUCT: u32 = 4;
+
+/// Looks up the offset in bytes of `member` in the kernel's `struct struct_name`.
+///
+/// Kernel structs such as `task_struct` are laid out differently depending on the kernel
+/// version and configuration, so the offset is read from the running kernel's BTF rather
+/// than compiled into the eBPF program
+pub fn kernel_member_offset(struct_name: &str, member: &str) -> Result<u64> {
+    let btf = fs::read(VMLINUX_BTF)?;
+    member_offset(&btf, struct_name, member)
+}
+
+fn member_offset(btf: &[u8], struct_name: &str, member: &str) -> Result<u64> {
+    let u16_at = |offset: usize| -> Result<u16> {
+        let bytes = btf
+            .get(offset..offset + 2)
+            .ok_or_else(|| eyre!("BTF is truncated"))?;
+        Ok(u16::from_ne_bytes(bytes.try_into().unwrap()))
+    };
+    let u32_at = |offset: usize| -> Result<u32> {
+        let bytes = btf
+            .get(offset..offset + 4)
+            .ok_or_else(|| eyre!("BTF is truncated"))?;
+        Ok(u32::from_ne_bytes(bytes.try_into().unwrap()))
+    };
+    if u16_at(0)? != BTF_MAGIC {
+        bail!("Not BTF data, or not in the byte order of this machine");
+    }
+    // the type and string sections are located relative to the end of the header
+    let header_length = u32_at(4)? as usize;
+    let types_start = header_length + u32_at(8)? as usize;
+    let types_end = types_start + u32_at(12)? as usize;
+    let strings_start = header_length + u32_at(16)? as usize;
+    let name = |offset: u32| -> &[u8] {
+        let start = strings_start + offset as usize;
+        let rest = btf.get(start..).unwrap_or_default();
+        let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        &rest[..end]
+    };
+
+    let mut offset = types_start;
+    while offset < types_end {
+        let name_offset = u32_at(offset)?;
+        let info = u32_at(offset + 4)?;
+        let kind = (info >> 24) & 0x1f;
+        let vlen = (info & 0xffff) as usize;
+        let kind_flag = info >> 31 == 1;
+        offset += 12;
+        if kind == BTF_KIND_STRUCT && name(name_offset) == struct_name.as_bytes() {
+            for i in 0..vlen {
+                let member_start = offset + i * 12;
+                if name(u32_at(member_start)?) == member.as_bytes() {
+                    let bit_offset = u32_at(member_start + 8)?;
+                    // with the kind flag set, the top byte holds the size of a bitfield
+                    let bit_offset = if kind_flag {
+                        bit_offset & 0xff_ffff
+                    } else {
+                        bit_offset
+                    };
+                    return Ok(bit_offset as u64 / 8);
+                }
+            }
+            bail!("struct {struct_name} has no member {member}");
+        }
+        // skip the data that follows the type, which depends on its kind
+        offset += match kind {
+            // INT, VAR and DECL_TAG
+            1 | 14 | 17 => 4,
+            // ARRAY
+            3 => 12,
+            // STRUCT, UNION, DATASEC and ENUM64
+            4 | 5 | 15 | 19 => vlen * 12,
+            // ENUM and FUNC_PROTO
+            6 | 13 => vlen * 8,
+            _ => 0,
+        };
+    }
+    bail!("struct {struct_name} was not found in the kernel's BTF")
+}
@@ -1,17 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::mem::size_of;
 use std::os::unix::prelude::OsStringExt;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use aya::maps::perf::AsyncPerfEventArrayBuffer;
-use aya::maps::{Array, AsyncPerfEventArray, MapData};
-use aya::programs::RawTracePoint;
+use aya::maps::{Array, AsyncPerfEventArray, HashMap as BpfHashMap, MapData};
+use aya::programs::{RawTracePoint, TracePoint};
 use aya::util::online_cpus;
 use aya::{include_bytes_aligned, Bpf};
 use aya_log::BpfLogger;
-use blackbox_common::{EventBuffer, EventID, GetEventId, SyscallEvent, SyscallID};
+use blackbox_common::{
+    EventBuffer, EventID, GetEventId, PathPrefix, ProcessExitEvent, SyscallEvent, SyscallID,
+    TraceConfig, BUFFER_SIZE, PATH_PREFIX_SIZE,
+};
 use bytes::BytesMut;
 use color_eyre::eyre::Result;
 use color_eyre::Report;
@@ -19,49 +23,64 @@ use futures::stream::FuturesUnordered;
 use futures::TryStreamExt;
 use log::{debug, error, info, warn};
 use nix::sys::signal::Signal;
+use nix::time::{clock_gettime, ClockId};
 use tokio::select;
 use tokio::sync::mpsc::{self, Sender};
+use tokio::sync::watch;
 
-use crate::types::{CloseData, ForkData, OpenData, ReadData, SyscallData, TraceEvent, WriteData};
+use crate::types::{
+    CloseData, DupData, ExitStatus, ForkData, IpcData, IpcKind, ListDirectoryData, LostEventsData,
+    OpenData, PrivilegedData, PrivilegedOperation, ProbeData, ProbeType, ProcessExitData, ReadData,
+    SyscallData, TraceEvent, TransferData, UnpairedData, UnpairedReason, WallClock, WriteData,
+    LOST_EVENTS_ID, PROCESS_EXIT_ID,
+};
 
-#[derive(Debug)]
-pub struct SyscallBuilder {
-    enter_args: Option<SyscallEvent>,
-    data: Option<EventBuffer>,
-    exit_args: Option<SyscallEvent>,
+/// How long to keep collecting events after the last traced process exits
+const EXIT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+/// How long a stopped reader waits for more events before it takes its buffer to be empty
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(10);
+
+mod btf;
+mod pairing;
+
+use pairing::{EventPairer, PairedEvent, SyscallBuilder};
+
+/// The number of events a perf buffer could not store, because it was full
+#[derive(Debug, Clone)]
+pub struct LostCounter {
+    /// The name of the map the buffer belongs to
+    pub map: &'static str,
+    pub cpu: u32,
+    pub lost: Arc<AtomicU64>,
 }
 
-impl SyscallBuilder {
-    fn event_id(event: &SyscallEvent) -> (u64, u64) {
-        let ptid = ((event.pid as u64) << 32) & (event.tgid as u64);
-        (ptid, event.syscall_id)
-    }
-    fn new() -> Self {
+impl LostCounter {
+    fn new(map: &'static str, cpu: u32) -> Self {
         Self {
-            enter_args: None,
-            data: None,
-            exit_args: None,
-        }
-    }
-    fn is_finished(&self) -> bool {
-        if let (Some(enter), Some(exit)) = (&self.enter_args, &self.exit_args) {
-            (!enter.has_data() || self.data.is_some()) && (!exit.has_data() || self.data.is_some())
-        } else if let Some(enter) = &self.enter_args {
-            SyscallID::from(enter.syscall_id).is_noreturn()
-        } else {
-            false
+            map,
+            cpu,
+            lost: Arc::new(AtomicU64::new(0)),
         }
     }
 
-    pub fn get_return(&self) -> u64 {
-        self.exit_args.as_ref().unwrap().return_val.unwrap()
+    fn add(&self, lost: usize) {
+        if lost > 0 {
+            warn!(
+                "Lost {} events from {} on CPU {}!",
+                lost, self.map, self.cpu
+            );
+            self.lost.fetch_add(lost as u64, Ordering::Relaxed);
+        }
     }
 }
 
+/// Sends the events of one perf buffer to `tx` until `stop` changes. After that, events left in
+/// the buffer are dropped, but those it lost are still counted
 pub async fn tracing_thread<T>(
     tx: Sender<T>,
     mut buf: AsyncPerfEventArrayBuffer<MapData>,
-    done: Arc<AtomicBool>,
+    mut stop: watch::Receiver<bool>,
+    counter: LostCounter,
 ) -> Result<()>
 where
     T: Clone + std::fmt::Debug + Sync + Send + 'static,
@@ -72,7 +91,12 @@ where
 
     loop {
         // wait for events
-        let events = buf.read_events(&mut buffers).await?;
+        let events = select! {
+            biased;
+            _ = stop.changed() => break,
+            events = buf.read_events(&mut buffers) => events?,
+        };
+        counter.add(events.lost);
 
         // events.read contains the number of events that have been read,
         // and is always <= buffers.len()
@@ -82,95 +106,169 @@ where
                 (*ptr).clone()
             };
 
-            tx.send(payload).await?;
-        }
-        if events.lost > 0 {
-            warn!("Lost {} events!", events.lost);
-        }
-        if done.load(Ordering::Acquire) {
-            break;
+            // the receiver is closed just before the readers are stopped
+            if tx.send(payload).await.is_err() {
+                break;
+            }
         }
     }
+    // losses are only reported when the buffer is read, so read what it still holds
+    while let Ok(events) = tokio::time::timeout(DRAIN_TIMEOUT, buf.read_events(&mut buffers)).await
+    {
+        counter.add(events?.lost);
+    }
     debug!("after loop");
     Ok(())
 }
 
+/// Options that control which events are traced and how much data is collected
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    /// Send the initial execve of the traced program
+    pub include_initial_execve: bool,
+    /// The maximum number of bytes captured for each read or write
+    pub capture_limit: usize,
+    /// IDs of the system calls that are not sent to userspace
+    pub excluded_syscalls: Vec<u64>,
+    /// Only open calls on paths starting with one of these prefixes are sent to userspace.
+    /// If this is empty, all open calls are sent.
+    pub open_path_prefixes: Vec<Vec<u8>>,
+}
+
 pub async fn start_tracing(
     pid: u32,
     tx: tokio::sync::mpsc::Sender<TraceEvent>,
-    include_initial_execve: bool,
+    options: TraceOptions,
+    clock: WallClock,
 ) -> Result<()> {
-    let (mut bpf, detach_action) = init_bpf(pid)?;
+    let (mut bpf, detach_action) = init_bpf(pid, &options)?;
+    let include_initial_execve = options.include_initial_execve;
     let mut event_output = AsyncPerfEventArray::try_from(bpf.take_map("EVENT_OUTPUT").unwrap())?;
     let mut buffer_output = AsyncPerfEventArray::try_from(bpf.take_map("BUFFER_OUTPUT").unwrap())?;
+    let mut process_output =
+        AsyncPerfEventArray::try_from(bpf.take_map("PROCESS_OUTPUT").unwrap())?;
     let mut handles = FuturesUnordered::new();
-    let done = Arc::new(AtomicBool::new(false));
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let mut readers = vec![];
     let (args_tx, mut args_rx) = mpsc::channel::<SyscallEvent>(256);
     let (buffer_tx, mut buffer_rx) = mpsc::channel::<EventBuffer>(256);
+    let (exit_tx, mut exit_rx) = mpsc::channel::<ProcessExitEvent>(256);
+    let mut lost_counters = vec![];
     for cpu_id in online_cpus()? {
         debug!("Creating listener: {}/{}", cpu_id, 23);
         // open a separate perf buffer for each cpu
         let buf = event_output.open(cpu_id, Some(128))?;
 
         let movable_tx = args_tx.clone();
-        let movable_done = done.clone();
-        handles.push(tokio::task::spawn(async move {
-            tracing_thread(movable_tx, buf, movable_done).await
+        let movable_stop = stop_rx.clone();
+        let counter = LostCounter::new("EVENT_OUTPUT", cpu_id);
+        lost_counters.push(counter.clone());
+        readers.push(tokio::task::spawn(async move {
+            tracing_thread(movable_tx, buf, movable_stop, counter).await
         }));
         let buf = buffer_output.open(cpu_id, Some(128))?;
 
         let movable_tx = buffer_tx.clone();
-        let movable_done = done.clone();
-        handles.push(tokio::task::spawn(async move {
-            tracing_thread(movable_tx, buf, movable_done).await
+        let movable_stop = stop_rx.clone();
+        let counter = LostCounter::new("BUFFER_OUTPUT", cpu_id);
+        lost_counters.push(counter.clone());
+        readers.push(tokio::task::spawn(async move {
+            tracing_thread(movable_tx, buf, movable_stop, counter).await
+        }));
+        let buf = process_output.open(cpu_id, Some(16))?;
+
+        let movable_tx = exit_tx.clone();
+        let movable_stop = stop_rx.clone();
+        let counter = LostCounter::new("PROCESS_OUTPUT", cpu_id);
+        lost_counters.push(counter.clone());
+        readers.push(tokio::task::spawn(async move {
+            tracing_thread(movable_tx, buf, movable_stop, counter).await
         }));
     }
     handles.push(tokio::task::spawn(async move {
         let mut events = vec![];
-        let mut buffers = HashMap::<EventID, EventBuffer>::new();
-        let mut start_time = None;
+        let mut exits = vec![];
+        let mut buffers = HashMap::<EventID, Vec<EventBuffer>>::new();
+        // thread group IDs of the processes that have been seen but have not exited
+        let mut live = HashSet::from([pid]);
+        let mut exited = HashSet::new();
+        // once every process has exited, events still in the other CPU buffers are drained
+        // for a short while before collection stops
+        let grace_period = tokio::time::sleep(EXIT_GRACE_PERIOD);
+        tokio::pin!(grace_period);
         loop {
             select! {
                 Some(args) = args_rx.recv() => {
-                    if args.syscall_id == SyscallID::Execve as u64 && start_time.is_none() {
-                        start_time = Some(args.timestamp);
+                    if !exited.contains(&args.tgid) {
+                        live.insert(args.tgid);
                     }
-                    let done = args.syscall_id == SyscallID::Exit as u64 || args.syscall_id == SyscallID::ExitGroup as u64;
                     events.push(args);
-                    if done {
-                        break;
-                    }
                 }
                 Some(buffer) = buffer_rx.recv() => {
-                    buffers.insert(buffer.get_event_id(), buffer);
+                    buffers.entry(buffer.get_event_id()).or_default().push(buffer);
                 }
+                Some(exit) = exit_rx.recv() => {
+                    debug!("Process {} exited: {:?}", exit.tgid, exit);
+                    live.remove(&exit.tgid);
+                    exited.insert(exit.tgid);
+                    exits.push(exit);
+                    if live.is_empty() {
+                        grace_period
+                            .as_mut()
+                            .reset(tokio::time::Instant::now() + EXIT_GRACE_PERIOD);
+                    }
+                }
+                _ = &mut grace_period, if live.is_empty() => break,
                 else => break
             };
         }
-        let mut map = HashMap::<(u64, u64), SyscallBuilder>::new();
+        // stop the readers, and wait until they have counted what their buffers lost
+        args_rx.close();
+        buffer_rx.close();
+        exit_rx.close();
+        stop_tx.send(true)?;
+        for reader in readers {
+            reader.await??;
+        }
+        let mut pairer = EventPairer::default();
         events.sort_by(|a,b| a.timestamp.cmp(&b.timestamp));
+        exits.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        let mut exits = exits.into_iter().peekable();
         for event in events {
-            let id = SyscallBuilder::event_id(&event);
-            let is_finished = {
-                let builder = map.entry(id).or_insert(SyscallBuilder::new());
-                if event.has_data() {
-                    let buffer = buffers.remove(&event.get_event_id());
-                    builder.data = buffer;
-                }
-                if event.is_enter() {
-                    builder.enter_args = Some(event);
-                } else {
-                    builder.exit_args = Some(event);
-                }
-                builder.is_finished()
+            while let Some(exit) = exits.next_if(|e| e.timestamp <= event.timestamp) {
+                pairer.process_exit(exit);
+            }
+            let data = if event.has_data() {
+                buffers.remove(&event.get_event_id())
+            } else {
+                None
             };
-
-            if is_finished {
-                let builder = map.remove(&id).unwrap();
-
-                if send_event(builder, &tx, start_time, include_initial_execve).await? {
-                    done.store(true, Ordering::Release);
+            pairer.push(event, data);
+        }
+        for exit in exits {
+            pairer.process_exit(exit);
+        }
+        let paired = pairer.finish();
+        let start_time = program_start(&paired, pid);
+        for event in paired {
+            match event {
+                PairedEvent::Call(call) => {
+                    send_event(call, &tx, clock, start_time, include_initial_execve).await?
                 }
+                PairedEvent::Unpaired { event, reason } => {
+                    if start_time.is_some_and(|start| event.timestamp > start) {
+                        send_unpaired(event, reason, &tx, clock).await?;
+                    }
+                }
+                PairedEvent::ProcessExit(exit) => send_process_exit(exit, &tx, clock).await?,
+            }
+        }
+        // the counts are sent last, so that they are stamped after every loss they include
+        let read_time = monotonic_now()?;
+        for counter in lost_counters {
+            let count = counter.lost.load(Ordering::Relaxed);
+            if count > 0 {
+                send_lost_events(&counter, count, read_time, &tx, clock).await?;
             }
         }
         info!("Done with collection thread");
@@ -192,52 +290,41 @@ pub async fn start_tracing(
         result?;
     }
 
-    // TODO: handle the fact that these will never rejoin if stuck awaiting events
-    // while let Some(result) = handles.try_next().await? {
-    //     info!("thread exited!");
-    //     result?
-    // }
-
     Ok(())
 }
 
 async fn send_event(
     syscall: SyscallBuilder,
     tx: &tokio::sync::mpsc::Sender<TraceEvent>,
+    clock: WallClock,
     start_time: Option<u64>,
     include_initial_execve: bool,
-) -> Result<bool> {
+) -> Result<()> {
     let entry = syscall.enter_args.as_ref().unwrap();
     let exit = syscall.exit_args.as_ref();
     {
         if let Some(timestamp) = start_time {
             if entry.timestamp < timestamp {
-                return Ok(false);
+                return Ok(());
             }
             if entry.timestamp == timestamp && !include_initial_execve {
-                return Ok(false);
+                return Ok(());
             }
         } else {
             debug!("timestamp is none!");
-            return Ok(false);
+            return Ok(());
         }
     }
 
     if let Some(exit) = exit {
         if entry.timestamp > exit.timestamp {
-            if entry.syscall_id == SyscallID::Execve as u64
-                || entry.syscall_id == SyscallID::ExecveAt as u64
-            {
-                info!("execve with timing anomaly");
-            } else {
-                error!("Received incorrect event: enter after exit!\n{:?}", syscall);
-                return Ok(false);
-            }
+            error!("Received incorrect event: enter after exit!\n{:?}", syscall);
+            return Ok(());
         }
     }
 
     let mut data = None;
-    if let Some(buffer) = &syscall.data {
+    if let Some(chunks) = &syscall.data {
         if !entry.has_data() && !exit.unwrap().has_data() {
             error!("Recieved data for an event with no data! {:?}", syscall);
         } else {
@@ -246,9 +333,24 @@ async fn send_event(
             } else {
                 exit.unwrap().data_size.unwrap()
             };
-            data = Some(buffer.data_buffer[..len.get()].to_owned());
+            data = Some(assemble_chunks(chunks, len.get()));
         }
     }
+    // the number of bytes the call actually transferred, which may be more than was captured
+    let transferred = match entry.syscall_id.into() {
+        SyscallID::Read
+        | SyscallID::Write
+        | SyscallID::PRead64
+        | SyscallID::PWrite64
+        | SyscallID::ReadV
+        | SyscallID::WriteV
+        | SyscallID::PReadV
+        | SyscallID::PWriteV
+        | SyscallID::PReadV2
+        | SyscallID::PWriteV2 => (syscall.get_return() as isize).max(0) as usize,
+        _ => 0,
+    };
+    let data_truncated = data.as_ref().map(Vec::len).unwrap_or(0) < transferred;
 
     let event_data = match entry.syscall_id.into() {
         SyscallID::Read => SyscallData::Read(ReadData {
@@ -259,6 +361,28 @@ async fn send_event(
                 r if r as isize > 0 => Ok(r as usize),
                 r => Err(r as isize),
             },
+            offset: None,
+        }),
+        SyscallID::PRead64 => SyscallData::Read(ReadData {
+            file_descriptor: entry.arg_0 as i32,
+            count: entry.arg_2 as usize,
+            data_read: data,
+            bytes_read: match syscall.get_return() {
+                r if r as isize > 0 => Ok(r as usize),
+                r => Err(r as isize),
+            },
+            offset: Some(entry.arg_3),
+        }),
+        SyscallID::ReadV | SyscallID::PReadV | SyscallID::PReadV2 => SyscallData::Read(ReadData {
+            file_descriptor: entry.arg_0 as i32,
+            // the total length of the iovecs is not known
+            count: (syscall.get_return() as isize).max(0) as usize,
+            data_read: data,
+            bytes_read: match syscall.get_return() {
+                r if r as isize > 0 => Ok(r as usize),
+                r => Err(r as isize),
+            },
+            offset: vectored_offset(entry),
         }),
         SyscallID::Write => SyscallData::Write(WriteData {
             file_descriptor: entry.arg_0 as i32,
@@ -268,7 +392,59 @@ async fn send_event(
                 r if r as isize > 0 => Ok(r as usize),
                 r => Err(r as isize),
             },
+            offset: None,
+        }),
+        SyscallID::PWrite64 => SyscallData::Write(WriteData {
+            file_descriptor: entry.arg_0 as i32,
+            count: entry.arg_2 as usize,
+            data_written: data,
+            bytes_written: match syscall.get_return() {
+                r if r as isize > 0 => Ok(r as usize),
+                r => Err(r as isize),
+            },
+            offset: Some(entry.arg_3),
         }),
+        SyscallID::WriteV | SyscallID::PWriteV | SyscallID::PWriteV2 => {
+            SyscallData::Write(WriteData {
+                file_descriptor: entry.arg_0 as i32,
+                // the total length of the iovecs is not known
+                count: (syscall.get_return() as isize).max(0) as usize,
+                data_written: data,
+                bytes_written: match syscall.get_return() {
+                    r if r as isize > 0 => Ok(r as usize),
+                    r => Err(r as isize),
+                },
+                offset: vectored_offset(entry),
+            })
+        }
+        SyscallID::SendFile => {
+            let (in_offset, _) = transfer_offsets(data.as_deref());
+            SyscallData::Transfer(TransferData {
+                in_fd: entry.arg_1 as i32,
+                out_fd: entry.arg_0 as i32,
+                in_offset,
+                out_offset: None,
+                count: entry.arg_3 as usize,
+                bytes_transferred: match syscall.get_return() {
+                    r if r as isize > 0 => Ok(r as usize),
+                    r => Err(r as isize),
+                },
+            })
+        }
+        SyscallID::Splice | SyscallID::CopyFileRange => {
+            let (in_offset, out_offset) = transfer_offsets(data.as_deref());
+            SyscallData::Transfer(TransferData {
+                in_fd: entry.arg_0 as i32,
+                out_fd: entry.arg_2 as i32,
+                in_offset,
+                out_offset,
+                count: entry.arg_4 as usize,
+                bytes_transferred: match syscall.get_return() {
+                    r if r as isize > 0 => Ok(r as usize),
+                    r => Err(r as isize),
+                },
+            })
+        }
         SyscallID::Open => SyscallData::Open(OpenData {
             filename: data.map(OsString::from_vec),
             flags: entry.arg_1 as i32,
@@ -301,6 +477,50 @@ async fn send_event(
             directory_fd: None,
             mode: entry.arg_1 as u32,
         }),
+        SyscallID::GetDents | SyscallID::GetDents64 => {
+            SyscallData::ListDirectory(ListDirectoryData {
+                file_descriptor: entry.arg_0 as i32,
+                count: entry.arg_2 as usize,
+                bytes_read: match syscall.get_return() {
+                    r if r as isize >= 0 => Ok(r as usize),
+                    r => Err(r as isize),
+                },
+            })
+        }
+        SyscallID::Stat
+        | SyscallID::LStat
+        | SyscallID::NewFStatAt
+        | SyscallID::Statx
+        | SyscallID::Access
+        | SyscallID::FAccessAt
+        | SyscallID::FAccessAt2
+        | SyscallID::ReadLink
+        | SyscallID::ReadLinkAt => {
+            let id: SyscallID = entry.syscall_id.into();
+            SyscallData::Probe(ProbeData {
+                probe: match id {
+                    SyscallID::Access | SyscallID::FAccessAt | SyscallID::FAccessAt2 => {
+                        ProbeType::Access
+                    }
+                    SyscallID::ReadLink | SyscallID::ReadLinkAt => ProbeType::ReadLink,
+                    _ => ProbeType::Stat,
+                },
+                filename: data.map(OsString::from_vec),
+                directory_fd: match id {
+                    SyscallID::NewFStatAt
+                    | SyscallID::Statx
+                    | SyscallID::FAccessAt
+                    | SyscallID::FAccessAt2
+                    | SyscallID::ReadLinkAt => Some(entry.arg_0 as i32),
+                    _ => None,
+                },
+                // readlink returns the length of the link target
+                return_val: match syscall.get_return() {
+                    r if r as isize >= 0 => Ok(()),
+                    r => Err(r as i32),
+                },
+            })
+        }
         SyscallID::Close => SyscallData::Close(CloseData {
             file_descriptor: entry.arg_0 as i32,
             return_val: match syscall.get_return() {
@@ -325,32 +545,165 @@ async fn send_event(
                 r => Err(r as i32),
             },
         }),
-        SyscallID::Clone => SyscallData::Fork(ForkData {
-            pid: match syscall.get_return() {
-                r if r as i32 > 0 => Ok(r as u32),
+        SyscallID::Pipe | SyscallID::Pipe2 | SyscallID::SocketPair => SyscallData::Ipc(IpcData {
+            kind: if SyscallID::from(entry.syscall_id) == SyscallID::SocketPair {
+                IpcKind::SocketPair
+            } else {
+                IpcKind::Pipe
+            },
+            file_descriptors: match syscall.get_return() {
+                0 => Ok(data
+                    .map(|data| {
+                        data.chunks_exact(4)
+                            .map(|fd| i32::from_ne_bytes(fd.try_into().unwrap()))
+                            .collect()
+                    })
+                    .unwrap_or_default()),
                 r => Err(r as i32),
             },
+            flags: match entry.syscall_id.into() {
+                SyscallID::Pipe2 => entry.arg_1 as i32,
+                SyscallID::SocketPair => entry.arg_1 as i32,
+                _ => 0,
+            },
         }),
-        SyscallID::Fork => SyscallData::Fork(ForkData {
-            pid: match syscall.get_return() {
-                r if r as i32 > 0 => Ok(r as u32),
+        SyscallID::EventFd | SyscallID::EventFd2 => SyscallData::Ipc(IpcData {
+            kind: IpcKind::EventFd,
+            file_descriptors: match syscall.get_return() {
+                r if r as i32 >= 0 => Ok(vec![r as i32]),
                 r => Err(r as i32),
             },
+            flags: match entry.syscall_id.into() {
+                SyscallID::EventFd2 => entry.arg_1 as i32,
+                _ => 0,
+            },
         }),
-        SyscallID::Execve => SyscallData::Execve(crate::types::ExecveData {
-            filename: data.map(OsString::from_vec),
-            args: entry.arg_1,
-            environment: entry.arg_2,
-            directory_fd: None,
-            flags: None,
-        }),
-        SyscallID::ExecveAt => SyscallData::Execve(crate::types::ExecveData {
-            filename: data.map(OsString::from_vec),
-            args: entry.arg_2,
-            environment: entry.arg_3,
-            directory_fd: Some(entry.arg_0 as i32),
-            flags: Some(entry.arg_5 as i32),
+        SyscallID::Dup | SyscallID::Dup2 | SyscallID::Dup3 => SyscallData::Dup(DupData {
+            old_fd: entry.arg_0 as i32,
+            new_fd: match syscall.get_return() {
+                r if r as i32 >= 0 => Ok(r as i32),
+                r => Err(r as i32),
+            },
         }),
+        SyscallID::InitModule
+        | SyscallID::FInitModule
+        | SyscallID::DeleteModule
+        | SyscallID::Bpf
+        | SyscallID::Mount
+        | SyscallID::Umount2
+        | SyscallID::PivotRoot
+        | SyscallID::Chroot
+        | SyscallID::Unshare
+        | SyscallID::SetNs
+        | SyscallID::KexecLoad
+        | SyscallID::KexecFileLoad => {
+            let mut strings = split_strings(data.as_deref()).into_iter();
+            let mut next_string = || strings.next().flatten();
+            let operation = match entry.syscall_id.into() {
+                SyscallID::InitModule => PrivilegedOperation::LoadModule {
+                    file_descriptor: None,
+                    params: next_string(),
+                },
+                SyscallID::FInitModule => PrivilegedOperation::LoadModule {
+                    file_descriptor: Some(entry.arg_0 as i32),
+                    params: next_string(),
+                },
+                SyscallID::DeleteModule => PrivilegedOperation::UnloadModule {
+                    name: next_string(),
+                },
+                SyscallID::Bpf => PrivilegedOperation::Bpf {
+                    command: entry.arg_0 as i32,
+                },
+                SyscallID::Mount => PrivilegedOperation::Mount {
+                    source: next_string(),
+                    target: next_string(),
+                    filesystem_type: next_string(),
+                    flags: entry.arg_3,
+                },
+                SyscallID::Umount2 => PrivilegedOperation::Unmount {
+                    target: next_string(),
+                    flags: entry.arg_1 as i32,
+                },
+                SyscallID::PivotRoot => PrivilegedOperation::PivotRoot {
+                    new_root: next_string(),
+                    put_old: next_string(),
+                },
+                SyscallID::Chroot => PrivilegedOperation::Chroot {
+                    path: next_string(),
+                },
+                SyscallID::Unshare => PrivilegedOperation::Unshare {
+                    flags: entry.arg_0 as i32,
+                },
+                SyscallID::SetNs => PrivilegedOperation::SetNs {
+                    file_descriptor: entry.arg_0 as i32,
+                    namespace_type: entry.arg_1 as i32,
+                },
+                SyscallID::KexecLoad => PrivilegedOperation::KexecLoad {
+                    kernel_fd: None,
+                    cmdline: None,
+                },
+                _ => PrivilegedOperation::KexecLoad {
+                    kernel_fd: Some(entry.arg_0 as i32),
+                    cmdline: next_string(),
+                },
+            };
+            SyscallData::Privileged(PrivilegedData {
+                operation,
+                return_val: match syscall.get_return() {
+                    r if r as isize >= 0 => Ok(()),
+                    r => Err(r as i32),
+                },
+            })
+        }
+        SyscallID::Clone | SyscallID::Clone3 | SyscallID::Fork | SyscallID::VFork => {
+            SyscallData::Fork(ForkData {
+                pid: match syscall.get_return() {
+                    r if r as i32 > 0 => Ok(r as u32),
+                    r => Err(r as i32),
+                },
+                flags: match entry.syscall_id.into() {
+                    SyscallID::Clone => entry.arg_0,
+                    SyscallID::Clone3 => data
+                        .and_then(|data| Some(u64::from_ne_bytes(data.get(..8)?.try_into().ok()?)))
+                        .unwrap_or(0),
+                    SyscallID::VFork => {
+                        (nix::libc::CLONE_VM | nix::libc::CLONE_VFORK | nix::libc::SIGCHLD) as u64
+                    }
+                    // fork is clone with only SIGCHLD
+                    _ => nix::libc::SIGCHLD as u64,
+                },
+            })
+        }
+        SyscallID::Execve => {
+            let (filename, arguments) = execve_strings(data.as_deref());
+            SyscallData::Execve(crate::types::ExecveData {
+                filename,
+                arguments,
+                args: entry.arg_1,
+                environment: entry.arg_2,
+                directory_fd: None,
+                flags: None,
+                return_val: exit.and_then(|exit| exit.return_val).map(|r| match r {
+                    0 => Ok(()),
+                    r => Err(r as i32),
+                }),
+            })
+        }
+        SyscallID::ExecveAt => {
+            let (filename, arguments) = execve_strings(data.as_deref());
+            SyscallData::Execve(crate::types::ExecveData {
+                filename,
+                arguments,
+                args: entry.arg_2,
+                environment: entry.arg_3,
+                directory_fd: Some(entry.arg_0 as i32),
+                flags: Some(entry.arg_4 as i32),
+                return_val: exit.and_then(|exit| exit.return_val).map(|r| match r {
+                    0 => Ok(()),
+                    r => Err(r as i32),
+                }),
+            })
+        }
         SyscallID::Exit => SyscallData::Exit(crate::types::ExitData {
             status: entry.arg_0 as i32,
         }),
@@ -368,26 +721,207 @@ async fn send_event(
             return_val: syscall.get_return(),
         }),
     };
-    let is_process_exit = matches!(event_data, SyscallData::Exit(_));
-
+    // if there is no exit event (e.g. with non-returning functions)
+    // then we consider the exection to take no time
+    let exit_timestamp = exit.map(|e| e.timestamp).unwrap_or(entry.timestamp);
     let event_to_send = TraceEvent {
         pid: entry.tgid,
         thread_id: entry.pid,
         syscall_id: entry.syscall_id,
         monotonic_enter_timestamp: entry.timestamp,
-        // if there is no exit event (e.g. with non-returning functions)
-        // then we consider the exection to take no time
-        monotonic_exit_timestamp: exit.map(|e| e.timestamp).unwrap_or(entry.timestamp),
+        monotonic_exit_timestamp: exit_timestamp,
+        enter_time: clock.wall_time(entry.timestamp),
+        exit_time: clock.wall_time(exit_timestamp),
+        data_truncated,
         data: event_data,
     };
 
     tx.send(event_to_send).await?;
-    Ok(is_process_exit)
+    Ok(())
+}
+
+/// The time the traced program started: the enter timestamp of the first successful execve
+/// of the traced process. Calls made before this are made by the shell that starts the
+/// program
+fn program_start(paired: &[PairedEvent], pid: u32) -> Option<u64> {
+    paired.iter().find_map(|event| match event {
+        PairedEvent::Call(SyscallBuilder {
+            enter_args: Some(enter),
+            exit_args: Some(exit),
+            ..
+        }) if enter.tgid == pid
+            && matches!(
+                enter.syscall_id.into(),
+                SyscallID::Execve | SyscallID::ExecveAt
+            )
+            && exit.return_val == Some(0) =>
+        {
+            Some(enter.timestamp)
+        }
+        _ => None,
+    })
+}
+
+/// The offset passed to preadv, pwritev, preadv2 or pwritev2.
+/// None for readv and writev, or if -1 is passed to use the current file position
+fn vectored_offset(entry: &SyscallEvent) -> Option<u64> {
+    match entry.syscall_id.into() {
+        SyscallID::PReadV | SyscallID::PWriteV | SyscallID::PReadV2 | SyscallID::PWriteV2
+            if entry.arg_3 as i64 >= 0 =>
+        {
+            Some(entry.arg_3)
+        }
+        _ => None,
+    }
+}
+
+/// Parses the input and output offsets sent for sendfile, splice and copy_file_range.
+/// An offset of -1 means the call used the current file position
+fn transfer_offsets(data: Option<&[u8]>) -> (Option<u64>, Option<u64>) {
+    let parse = |bytes: &[u8]| {
+        let offset = i64::from_ne_bytes(bytes.try_into().ok()?);
+        (offset >= 0).then_some(offset as u64)
+    };
+    match data {
+        Some(data) if data.len() >= 16 => (parse(&data[..8]), parse(&data[8..16])),
+        _ => (None, None),
+    }
+}
+
+/// Splits the null-terminated strings sent by the strings handler. Empty strings, which
+/// are sent for null pointers, are None
+fn split_strings(data: Option<&[u8]>) -> Vec<Option<OsString>> {
+    let Some(data) = data else {
+        return vec![];
+    };
+    data.strip_suffix(&[0])
+        .unwrap_or(data)
+        .split(|b| *b == 0)
+        .map(|s| (!s.is_empty()).then(|| OsString::from_vec(s.to_vec())))
+        .collect()
+}
+
+/// Splits the filename and arguments sent for execve and execveat
+fn execve_strings(data: Option<&[u8]>) -> (Option<OsString>, Vec<OsString>) {
+    let mut strings = split_strings(data).into_iter();
+    let filename = strings.next().flatten();
+    // empty arguments are valid
+    (filename, strings.map(Option::unwrap_or_default).collect())
+}
+
+/// Joins the chunks of data sent for an event with `length` bytes of data.
+/// If a chunk is missing, only the data before it is returned.
+fn assemble_chunks(chunks: &[EventBuffer], length: usize) -> Vec<u8> {
+    let mut chunks = chunks.iter().collect::<Vec<_>>();
+    chunks.sort_by_key(|chunk| chunk.offset);
+    let mut data = Vec::with_capacity(length);
+    for chunk in chunks {
+        if chunk.offset as usize != data.len() {
+            if chunk.offset as usize > data.len() {
+                warn!("Missing data chunk at offset {}", data.len());
+                break;
+            }
+            // a duplicate chunk
+            continue;
+        }
+        let chunk_length = (length - data.len())
+            .min(chunk.length as usize)
+            .min(BUFFER_SIZE);
+        data.extend_from_slice(&chunk.data_buffer[..chunk_length]);
+        if data.len() >= length {
+            break;
+        }
+    }
+    data
+}
+
+async fn send_process_exit(
+    exit: ProcessExitEvent,
+    tx: &tokio::sync::mpsc::Sender<TraceEvent>,
+    clock: WallClock,
+) -> Result<()> {
+    let status = match (exit.exit_code, exit.signal) {
+        (Some(code), _) => ExitStatus::Exited(code),
+        (None, Some(signal)) => ExitStatus::Signaled(signal),
+        (None, None) => ExitStatus::Unknown,
+    };
+    tx.send(TraceEvent {
+        pid: exit.tgid,
+        thread_id: exit.pid,
+        syscall_id: PROCESS_EXIT_ID,
+        monotonic_enter_timestamp: exit.timestamp,
+        monotonic_exit_timestamp: exit.timestamp,
+        enter_time: clock.wall_time(exit.timestamp),
+        exit_time: clock.wall_time(exit.timestamp),
+        data_truncated: false,
+        data: SyscallData::ProcessExit(ProcessExitData { status }),
+    })
+    .await?;
+    Ok(())
+}
+
+async fn send_unpaired(
+    event: SyscallEvent,
+    reason: UnpairedReason,
+    tx: &tokio::sync::mpsc::Sender<TraceEvent>,
+    clock: WallClock,
+) -> Result<()> {
+    warn!("Unpaired event ({:?}): {:?}", reason, event);
+    tx.send(TraceEvent {
+        pid: event.tgid,
+        thread_id: event.pid,
+        syscall_id: event.syscall_id,
+        monotonic_enter_timestamp: event.timestamp,
+        monotonic_exit_timestamp: event.timestamp,
+        enter_time: clock.wall_time(event.timestamp),
+        exit_time: clock.wall_time(event.timestamp),
+        data_truncated: false,
+        data: SyscallData::Unpaired(UnpairedData {
+            reason,
+            return_val: event.return_val.map(|r| r as i64),
+        }),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Sends the number of events lost by a perf buffer. `read_time` is the monotonic timestamp
+/// when the counters were read
+async fn send_lost_events(
+    counter: &LostCounter,
+    count: u64,
+    read_time: u64,
+    tx: &tokio::sync::mpsc::Sender<TraceEvent>,
+    clock: WallClock,
+) -> Result<()> {
+    tx.send(TraceEvent {
+        pid: 0,
+        thread_id: 0,
+        syscall_id: LOST_EVENTS_ID,
+        monotonic_enter_timestamp: read_time,
+        monotonic_exit_timestamp: read_time,
+        enter_time: clock.wall_time(read_time),
+        exit_time: clock.wall_time(read_time),
+        data_truncated: false,
+        data: SyscallData::LostEvents(LostEventsData {
+            map: counter.map.to_owned(),
+            cpu: counter.cpu,
+            count,
+        }),
+    })
+    .await?;
+    Ok(())
+}
+
+/// The current time of the clock used by bpf_ktime_get_ns, in nanoseconds since boot
+fn monotonic_now() -> Result<u64> {
+    let now = Duration::from(clock_gettime(ClockId::CLOCK_MONOTONIC)?);
+    Ok(now.as_nanos() as u64)
 }
 
 type DetachAction = Box<dyn FnOnce(&mut Bpf) -> Result<()> + Send>;
 
-fn init_bpf(pid: u32) -> Result<(Bpf, DetachAction)> {
+fn init_bpf(pid: u32, options: &TraceOptions) -> Result<(Bpf, DetachAction)> {
     #[cfg(debug_assertions)]
     let mut bpf = Bpf::load(include_bytes_aligned!(
         "../../../target/bpfel-unknown-none/debug/blackbox"
@@ -411,20 +945,92 @@ fn init_bpf(pid: u32) -> Result<(Bpf, DetachAction)> {
     program.load()?;
     let exit_handle = program.attach("sys_exit")?;
 
-    // populate PID filter buffer
-    let mut traced_pids: aya::maps::array::Array<_, u32> =
-        Array::try_from(bpf.map_mut("PIDS").unwrap())?;
+    // set up the process lifecycle tracepoints, used to follow children and collect exit statuses
+    let program: &mut TracePoint = bpf
+        .program_mut("handle_sched_process_fork")
+        .unwrap()
+        .try_into()?;
+    program.load()?;
+    let fork_handle = program.attach("sched", "sched_process_fork")?;
+    let program: &mut TracePoint = bpf
+        .program_mut("handle_sched_process_exit")
+        .unwrap()
+        .try_into()?;
+    program.load()?;
+    let process_exit_handle = program.attach("sched", "sched_process_exit")?;
+
+    // populate PID filter map
+    let mut traced_pids: BpfHashMap<_, u32, u8> =
+        BpfHashMap::try_from(bpf.map_mut("TRACED").unwrap())?;
 
-    traced_pids.set(0, pid, 0)?;
+    traced_pids.insert(pid, 1, 0)?;
 
     info!("Tracing PID {}", pid);
 
+    let exit_code_offset = match btf::kernel_member_offset("task_struct", "exit_code") {
+        Ok(offset) => offset,
+        Err(err) => {
+            warn!("Exit statuses will not be recorded: {}", err);
+            0
+        }
+    };
+    let live_offsets = btf::kernel_member_offset("task_struct", "signal")
+        .and_then(|signal| Ok((signal, btf::kernel_member_offset("signal_struct", "live")?)));
+    let (signal_offset, live_offset) = match live_offsets {
+        Ok(offsets) => offsets,
+        Err(err) => {
+            warn!(
+                "Processes will be taken to exit when their main thread exits: {}",
+                err
+            );
+            (0, 0)
+        }
+    };
+    let mut config: Array<_, TraceConfig> = Array::try_from(bpf.map_mut("CONFIG").unwrap())?;
+    config.set(
+        0,
+        TraceConfig {
+            capture_limit: options.capture_limit as u64,
+            path_prefix_count: options.open_path_prefixes.len() as u64,
+            exit_code_offset,
+            signal_offset,
+            live_offset,
+        },
+        0,
+    )?;
+
+    // populate the filters
+    let mut syscall_filter: Array<_, u8> = Array::try_from(bpf.map_mut("SYSCALL_FILTER").unwrap())?;
+    for id in options.excluded_syscalls.iter() {
+        syscall_filter.set(*id as u32, 1, 0)?;
+    }
+    let mut path_prefixes: Array<_, PathPrefix> =
+        Array::try_from(bpf.map_mut("PATH_PREFIXES").unwrap())?;
+    for (i, prefix) in options.open_path_prefixes.iter().enumerate() {
+        let mut path_prefix = PathPrefix {
+            length: prefix.len() as u64,
+            prefix: [0; PATH_PREFIX_SIZE],
+        };
+        path_prefix.prefix[..prefix.len()].copy_from_slice(prefix);
+        path_prefixes.set(i as u32, path_prefix, 0)?;
+    }
+
     let use_handles = move |bpf: &mut Bpf| -> Result<()> {
         let program: &mut RawTracePoint =
             bpf.program_mut("handle_sys_enter").unwrap().try_into()?;
         program.detach(enter_handle)?;
         let program: &mut RawTracePoint = bpf.program_mut("handle_sys_exit").unwrap().try_into()?;
         program.detach(exit_handle)?;
+        let program: &mut TracePoint = bpf
+            .program_mut("handle_sched_process_fork")
+            .unwrap()
+            .try_into()?;
+        program.detach(fork_handle)?;
+        let program: &mut TracePoint = bpf
+            .program_mut("handle_sched_process_exit")
+            .unwrap()
+            .try_into()?;
+        program.detach(process_exit_handle)?;
         Ok(())
     };
 
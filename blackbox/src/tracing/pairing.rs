@@ -0,0 +1,614 @@
+use std::collections::HashMap;
+
+use blackbox_common::syscall_names::syscall_id;
+use blackbox_common::{EventBuffer, ProcessExitEvent, SyscallEvent, SyscallID};
+use log::debug;
+
+use crate::types::UnpairedReason;
+
+/// The error returned when a call is interrupted by a signal and may be restarted.
+/// These are never seen by user space: the kernel either restarts the call or returns
+/// `EINTR` instead
+const RESTART_ERRORS: [i64; 4] = [
+    512, // ERESTARTSYS
+    513, // ERESTARTNOINTR
+    514, // ERESTARTNOHAND
+    516, // ERESTART_RESTARTBLOCK
+];
+
+/// The enter and exit events and data of a single system call
+#[derive(Debug)]
+pub struct SyscallBuilder {
+    pub enter_args: Option<SyscallEvent>,
+    pub data: Option<Vec<EventBuffer>>,
+    pub exit_args: Option<SyscallEvent>,
+}
+
+impl SyscallBuilder {
+    pub fn get_return(&self) -> u64 {
+        self.exit_args.as_ref().unwrap().return_val.unwrap()
+    }
+
+    fn id(&self) -> SyscallID {
+        self.enter_args.as_ref().unwrap().syscall_id.into()
+    }
+
+    fn add_data(&mut self, data: Option<Vec<EventBuffer>>) {
+        if let Some(mut data) = data {
+            self.data.get_or_insert_with(Vec::new).append(&mut data);
+        }
+    }
+}
+
+/// A call that was interrupted by a signal, waiting to be restarted
+#[derive(Debug)]
+struct InterruptedCall {
+    call: SyscallBuilder,
+    /// The signal handler has returned, so the next call of the thread is either the
+    /// restarted call or a new one
+    handler_returned: bool,
+}
+
+/// An item of the paired trace, in the order it should be sent
+#[derive(Debug)]
+pub enum PairedEvent {
+    Call(SyscallBuilder),
+    /// An enter or exit event whose other half was not found
+    Unpaired {
+        event: SyscallEvent,
+        reason: UnpairedReason,
+    },
+    ProcessExit(ProcessExitEvent),
+}
+
+/// Pairs the sys_enter and sys_exit events of each system call.
+///
+/// A thread can only be in one system call at a time, so an exit event belongs to the last
+/// enter event of the same thread. Events must be pushed in timestamp order. Calls that are
+/// interrupted by a signal and restarted are joined into a single call.
+#[derive(Debug)]
+pub struct EventPairer {
+    /// the call each thread is currently in
+    pending: HashMap<u32, SyscallBuilder>,
+    /// the interrupted calls of each thread, most recent last. A signal handler may make
+    /// other calls before the interrupted one is restarted
+    interrupted: HashMap<u32, Vec<InterruptedCall>>,
+    restart_syscall: Option<u64>,
+    sigreturn: Option<u64>,
+    paired: Vec<PairedEvent>,
+}
+
+impl Default for EventPairer {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+            interrupted: HashMap::new(),
+            restart_syscall: syscall_id("restart_syscall"),
+            sigreturn: syscall_id("rt_sigreturn"),
+            paired: vec![],
+        }
+    }
+}
+
+impl EventPairer {
+    /// Adds a sys_enter or sys_exit event, with the data sent for it
+    pub fn push(&mut self, event: SyscallEvent, data: Option<Vec<EventBuffer>>) {
+        if event.is_enter() {
+            self.push_enter(event, data);
+        } else {
+            self.push_exit(event, data);
+        }
+    }
+
+    /// Adds the exit of the process `exit.tgid`. Its threads will not make any more calls,
+    /// so their unfinished calls are sent
+    pub fn process_exit(&mut self, exit: ProcessExitEvent) {
+        let threads = self
+            .pending
+            .iter()
+            .filter(|(_, call)| call.enter_args.as_ref().unwrap().tgid == exit.tgid)
+            .map(|(thread, _)| *thread)
+            .chain(
+                self.interrupted
+                    .iter()
+                    .filter(|(_, calls)| {
+                        calls
+                            .iter()
+                            .any(|c| c.call.enter_args.as_ref().unwrap().tgid == exit.tgid)
+                    })
+                    .map(|(thread, _)| *thread),
+            )
+            .collect::<Vec<_>>();
+        for thread in threads {
+            self.flush_thread(thread);
+        }
+        self.paired.push(PairedEvent::ProcessExit(exit));
+    }
+
+    /// Sends the calls that are still unfinished and returns the paired trace
+    pub fn finish(mut self) -> Vec<PairedEvent> {
+        let mut threads = self
+            .pending
+            .keys()
+            .chain(self.interrupted.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        threads.sort();
+        threads.dedup();
+        for thread in threads {
+            self.flush_thread(thread);
+        }
+        self.paired
+    }
+
+    fn push_enter(&mut self, event: SyscallEvent, data: Option<Vec<EventBuffer>>) {
+        let thread = event.pid;
+        if let Some(previous) = self.pending.remove(&thread) {
+            // the thread started another call, so the exit of the previous one was lost
+            self.flush(previous);
+        }
+
+        if Some(event.syscall_id) == self.restart_syscall {
+            // restart_syscall continues the interrupted call, and its exit is the exit
+            // of that call
+            if let Some(interrupted) = self.interrupted.get_mut(&thread).and_then(Vec::pop) {
+                debug!("Restarting interrupted call: {:?}", interrupted.call);
+                let mut call = interrupted.call;
+                call.exit_args = None;
+                self.pending.insert(thread, call);
+                return;
+            }
+        }
+        self.check_restarted(&event);
+
+        let id = SyscallID::from(event.syscall_id);
+        let call = SyscallBuilder {
+            enter_args: Some(event),
+            data,
+            exit_args: None,
+        };
+        // exit and exit_group never return
+        if matches!(id, SyscallID::Exit | SyscallID::ExitGroup) {
+            self.paired.push(PairedEvent::Call(call));
+        } else {
+            self.pending.insert(thread, call);
+        }
+    }
+
+    /// Checks whether `event` restarts the last interrupted call of its thread, or whether
+    /// that call returned EINTR instead
+    fn check_restarted(&mut self, event: &SyscallEvent) {
+        let Some(calls) = self.interrupted.get_mut(&event.pid) else {
+            return;
+        };
+        while let Some(last) = calls.last_mut() {
+            let enter = last.call.enter_args.as_ref().unwrap();
+            if enter.syscall_id == event.syscall_id
+                && (enter.arg_0, enter.arg_1, enter.arg_2)
+                    == (event.arg_0, event.arg_1, event.arg_2)
+            {
+                debug!("Restarting interrupted call: {:?}", last.call);
+                calls.pop();
+                return;
+            }
+            if Some(event.syscall_id) == self.sigreturn {
+                last.handler_returned = true;
+                return;
+            }
+            if !last.handler_returned {
+                // a call made by the signal handler
+                return;
+            }
+            let call = calls.pop().unwrap().call;
+            self.paired.push(PairedEvent::Call(interrupted(call)));
+        }
+    }
+
+    fn push_exit(&mut self, event: SyscallEvent, data: Option<Vec<EventBuffer>>) {
+        let thread = event.pid;
+        let Some(mut call) = self
+            .pending
+            .remove(&thread)
+            .or_else(|| self.take_exec_of_other_thread(&event))
+        else {
+            let id = SyscallID::from(event.syscall_id);
+            let is_fork = matches!(
+                id,
+                SyscallID::Clone | SyscallID::Clone3 | SyscallID::Fork | SyscallID::VFork
+            );
+            // new processes and threads return from the call that created them
+            if !(is_fork && event.return_val == Some(0)) {
+                self.paired.push(PairedEvent::Unpaired {
+                    event,
+                    reason: UnpairedReason::MissingEnter,
+                });
+            }
+            return;
+        };
+
+        let enter_id = call.enter_args.as_ref().unwrap().syscall_id;
+        if enter_id != event.syscall_id && Some(event.syscall_id) != self.restart_syscall {
+            // both the exit of the pending call and the enter of this one were lost
+            self.flush(call);
+            self.paired.push(PairedEvent::Unpaired {
+                event,
+                reason: UnpairedReason::MissingEnter,
+            });
+            return;
+        }
+
+        call.add_data(data);
+        let return_val = event.return_val.unwrap() as i64;
+        call.exit_args = Some(event);
+        if RESTART_ERRORS.contains(&-return_val) {
+            self.interrupted
+                .entry(thread)
+                .or_default()
+                .push(InterruptedCall {
+                    call,
+                    handler_returned: false,
+                });
+        } else {
+            self.paired.push(PairedEvent::Call(call));
+        }
+    }
+
+    /// When a thread other than the leader calls execve, it takes over the thread ID of the
+    /// leader, so the exit event has a different thread ID than the enter event
+    fn take_exec_of_other_thread(&mut self, event: &SyscallEvent) -> Option<SyscallBuilder> {
+        if event.pid != event.tgid
+            || !matches!(
+                event.syscall_id.into(),
+                SyscallID::Execve | SyscallID::ExecveAt
+            )
+        {
+            return None;
+        }
+        let thread = self
+            .pending
+            .iter()
+            .find(|(_, call)| {
+                let enter = call.enter_args.as_ref().unwrap();
+                enter.tgid == event.tgid && enter.syscall_id == event.syscall_id
+            })
+            .map(|(thread, _)| *thread)?;
+        self.pending.remove(&thread)
+    }
+
+    fn flush_thread(&mut self, thread: u32) {
+        if let Some(call) = self.pending.remove(&thread) {
+            self.flush(call);
+        }
+        for interrupted_call in self.interrupted.remove(&thread).unwrap_or_default() {
+            self.paired
+                .push(PairedEvent::Call(interrupted(interrupted_call.call)));
+        }
+    }
+
+    /// Sends a call whose exit will not be received
+    fn flush(&mut self, call: SyscallBuilder) {
+        if call.id().is_noreturn() {
+            // execve does not return if the process is killed while it runs
+            self.paired.push(PairedEvent::Call(call));
+        } else {
+            self.paired.push(PairedEvent::Unpaired {
+                event: call.enter_args.unwrap(),
+                reason: UnpairedReason::MissingExit,
+            });
+        }
+    }
+}
+
+/// An interrupted call that was not restarted returns EINTR
+fn interrupted(mut call: SyscallBuilder) -> SyscallBuilder {
+    if let Some(exit) = &mut call.exit_args {
+        exit.return_val = Some(-(nix::libc::EINTR as i64) as u64);
+    }
+    call
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TGID: u32 = 100;
+
+    /// A paired event, without the arguments that do not matter for pairing
+    #[derive(Debug, PartialEq)]
+    enum Paired {
+        /// A call by `thread` to `name`, and the value it returned
+        Call {
+            thread: u32,
+            name: &'static str,
+            return_val: Option<i64>,
+        },
+        Unpaired {
+            name: &'static str,
+            reason: UnpairedReason,
+        },
+        ProcessExit(u32),
+    }
+
+    fn number(name: &str) -> u64 {
+        syscall_id(name).unwrap()
+    }
+
+    fn name(id: u64) -> &'static str {
+        blackbox_common::syscall_names::syscall_name(id).unwrap()
+    }
+
+    fn enter(thread: u32, name: &str, args: [u64; 3]) -> SyscallEvent {
+        SyscallEvent {
+            timestamp: 0,
+            tgid: TGID,
+            pid: thread,
+            syscall_id: number(name),
+            arg_0: args[0],
+            arg_1: args[1],
+            arg_2: args[2],
+            arg_3: 0,
+            arg_4: 0,
+            arg_5: 0,
+            return_val: None,
+            data_size: None,
+        }
+    }
+
+    fn exit(thread: u32, name: &str, args: [u64; 3], return_val: i64) -> SyscallEvent {
+        SyscallEvent {
+            return_val: Some(return_val as u64),
+            ..enter(thread, name, args)
+        }
+    }
+
+    fn call(thread: u32, name: &'static str, return_val: i64) -> Paired {
+        Paired::Call {
+            thread,
+            name,
+            return_val: Some(return_val),
+        }
+    }
+
+    fn process_exit(tgid: u32) -> ProcessExitEvent {
+        ProcessExitEvent {
+            timestamp: 0,
+            tgid,
+            pid: tgid,
+            exit_code: Some(0),
+            signal: None,
+        }
+    }
+
+    /// Pairs `events` and describes the result by the name of each call
+    fn pair(events: Vec<SyscallEvent>) -> Vec<Paired> {
+        let mut pairer = EventPairer::default();
+        for event in events {
+            pairer.push(event, None);
+        }
+        summarize(pairer.finish())
+    }
+
+    fn summarize(paired: Vec<PairedEvent>) -> Vec<Paired> {
+        paired
+            .into_iter()
+            .map(|event| match event {
+                PairedEvent::Call(call) => {
+                    let enter = call.enter_args.unwrap();
+                    Paired::Call {
+                        thread: enter.pid,
+                        name: name(enter.syscall_id),
+                        return_val: call.exit_args.map(|exit| exit.return_val.unwrap() as i64),
+                    }
+                }
+                PairedEvent::Unpaired { event, reason } => Paired::Unpaired {
+                    name: name(event.syscall_id),
+                    reason,
+                },
+                PairedEvent::ProcessExit(exit) => Paired::ProcessExit(exit.tgid),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pairs_calls_of_each_thread() {
+        let paired = pair(vec![
+            enter(100, "read", [3, 0, 10]),
+            enter(101, "write", [4, 0, 5]),
+            exit(101, "write", [4, 0, 5], 5),
+            exit(100, "read", [3, 0, 10], 10),
+        ]);
+        assert_eq!(paired, vec![call(101, "write", 5), call(100, "read", 10)]);
+    }
+
+    #[test]
+    fn joins_calls_continued_by_restart_syscall() {
+        let read = [3, 0, 10];
+        let paired = pair(vec![
+            enter(100, "read", read),
+            exit(100, "read", read, -516),
+            // the thread was stopped, so no signal handler ran
+            enter(100, "restart_syscall", [0; 3]),
+            exit(100, "restart_syscall", [0; 3], 10),
+        ]);
+        assert_eq!(paired, vec![call(100, "read", 10)]);
+    }
+
+    #[test]
+    fn joins_calls_restarted_after_signal_handler() {
+        let read = [3, 0, 10];
+        // rt_sigreturn returns the restored register, which holds the number of the call
+        // that is restarted
+        let restored = number("read") as i64;
+        let paired = pair(vec![
+            enter(100, "read", read),
+            exit(100, "read", read, -512),
+            // calls made by the signal handler do not end the interrupted call
+            enter(100, "write", [1, 0, 5]),
+            exit(100, "write", [1, 0, 5], 5),
+            enter(100, "rt_sigreturn", [0; 3]),
+            exit(100, "rt_sigreturn", [0; 3], restored),
+            enter(100, "read", read),
+            exit(100, "read", read, 10),
+        ]);
+        assert_eq!(
+            paired,
+            vec![
+                call(100, "write", 5),
+                call(100, "rt_sigreturn", restored),
+                call(100, "read", 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn interrupted_calls_that_are_not_restarted_return_eintr() {
+        let restored = number("read") as i64;
+        let paired = pair(vec![
+            enter(100, "read", [3, 0, 10]),
+            exit(100, "read", [3, 0, 10], -512),
+            enter(100, "rt_sigreturn", [0; 3]),
+            exit(100, "rt_sigreturn", [0; 3], restored),
+            enter(100, "close", [3, 0, 0]),
+            exit(100, "close", [3, 0, 0], 0),
+        ]);
+        assert_eq!(
+            paired,
+            vec![
+                call(100, "rt_sigreturn", restored),
+                call(100, "read", -(nix::libc::EINTR as i64)),
+                call(100, "close", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_exec_by_other_thread() {
+        let mut exec_exit = exit(TGID, "execve", [0; 3], 0);
+        exec_exit.pid = TGID;
+        let mut pairer = EventPairer::default();
+        pairer.push(enter(101, "execve", [0; 3]), None);
+        // the thread takes over the thread ID of the leader
+        pairer.push(exec_exit, None);
+        let paired = pairer.finish();
+        assert_eq!(paired.len(), 1);
+        let PairedEvent::Call(call) = &paired[0] else {
+            panic!("not a call: {:?}", paired[0]);
+        };
+        assert_eq!(call.enter_args.as_ref().unwrap().pid, 101);
+        assert_eq!(call.exit_args.as_ref().unwrap().pid, TGID);
+    }
+
+    #[test]
+    fn reports_missing_enter() {
+        let paired = pair(vec![
+            exit(100, "read", [3, 0, 10], 10),
+            // new processes return from clone without entering it
+            exit(102, "clone", [0; 3], 0),
+        ]);
+        assert_eq!(
+            paired,
+            vec![Paired::Unpaired {
+                name: "read",
+                reason: UnpairedReason::MissingEnter,
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_missing_exit() {
+        let paired = pair(vec![
+            enter(100, "read", [3, 0, 10]),
+            enter(100, "close", [3, 0, 0]),
+            exit(100, "close", [3, 0, 0], 0),
+            enter(100, "openat", [0; 3]),
+        ]);
+        assert_eq!(
+            paired,
+            vec![
+                Paired::Unpaired {
+                    name: "read",
+                    reason: UnpairedReason::MissingExit,
+                },
+                call(100, "close", 0),
+                Paired::Unpaired {
+                    name: "openat",
+                    reason: UnpairedReason::MissingExit,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_both_halves_of_mismatched_calls() {
+        let paired = pair(vec![
+            enter(100, "read", [3, 0, 10]),
+            exit(100, "write", [1, 0, 5], 5),
+        ]);
+        assert_eq!(
+            paired,
+            vec![
+                Paired::Unpaired {
+                    name: "read",
+                    reason: UnpairedReason::MissingExit,
+                },
+                Paired::Unpaired {
+                    name: "write",
+                    reason: UnpairedReason::MissingEnter,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_that_do_not_return_are_complete() {
+        let paired = pair(vec![
+            enter(100, "exit_group", [0; 3]),
+            enter(101, "execve", [0; 3]),
+        ]);
+        assert_eq!(
+            paired,
+            vec![
+                Paired::Call {
+                    thread: 100,
+                    name: "exit_group",
+                    return_val: None,
+                },
+                Paired::Call {
+                    thread: 101,
+                    name: "execve",
+                    return_val: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn process_exit_ends_calls_of_its_threads() {
+        let mut other_process = enter(200, "read", [3, 0, 10]);
+        other_process.tgid = 200;
+        let mut pairer = EventPairer::default();
+        pairer.push(enter(100, "read", [3, 0, 10]), None);
+        pairer.push(enter(101, "write", [1, 0, 5]), None);
+        pairer.push(exit(101, "write", [1, 0, 5], -512), None);
+        pairer.push(other_process, None);
+        pairer.process_exit(process_exit(TGID));
+        let mut paired = summarize(pairer.finish());
+        // threads are flushed in no particular order
+        paired[..2].sort_by_key(|event| format!("{event:?}"));
+        assert_eq!(
+            paired,
+            vec![
+                call(101, "write", -(nix::libc::EINTR as i64)),
+                Paired::Unpaired {
+                    name: "read",
+                    reason: UnpairedReason::MissingExit,
+                },
+                Paired::ProcessExit(TGID),
+                Paired::Unpaired {
+                    name: "read",
+                    reason: UnpairedReason::MissingExit,
+                },
+            ]
+        );
+    }
+}
@@ -1,5 +1,8 @@
+use std::collections::HashMap;
 use std::ffi::OsString;
+use std::time::{Duration, SystemTime};
 
+use nix::time::{clock_gettime, ClockId};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +30,9 @@ pub struct ReadData {
     /// The number of bytes read, or the error value.
     /// Zero indicates EOF
     pub bytes_read: Result<usize, isize>,
+    /// The file offset read from, for pread and preadv. None if the current file position
+    /// was used
+    pub offset: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +44,60 @@ pub struct WriteData {
     pub data_written: Option<Vec<u8>>,
     /// The number of bytes written, or the error value.
     pub bytes_written: Result<usize, isize>,
+    /// The file offset written to, for pwrite and pwritev. None if the current file position
+    /// was used
+    pub offset: Option<u64>,
+}
+
+/// Data moved from one file descriptor to another without passing through the process
+/// (sendfile, splice, copy_file_range)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferData {
+    /// The file descriptor the data is read from
+    pub in_fd: i32,
+    /// The file descriptor the data is written to
+    pub out_fd: i32,
+    /// The offset read from in `in_fd`. None if the current file position was used
+    pub in_offset: Option<u64>,
+    /// The offset written to in `out_fd`. None if the current file position was used
+    pub out_offset: Option<u64>,
+    /// The number of bytes the user requested to be transferred
+    pub count: usize,
+    /// The number of bytes transferred, or the error value
+    pub bytes_transferred: Result<usize, isize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListDirectoryData {
+    /// The file descriptor of the directory being listed
+    pub file_descriptor: i32,
+    /// The size of the buffer passed for directory entries
+    pub count: usize,
+    /// The number of bytes of directory entries read, or the error value.
+    /// Zero indicates the end of the directory
+    pub bytes_read: Result<usize, isize>,
+}
+
+/// The kind of call that checks a path without opening it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProbeType {
+    /// stat, lstat, newfstatat or statx
+    Stat,
+    /// access, faccessat or faccessat2
+    Access,
+    /// readlink or readlinkat
+    ReadLink,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeData {
+    pub probe: ProbeType,
+    /// The path checked. This is Some unless there are errors reading the memory
+    pub filename: Option<OsString>,
+    /// The directory file descriptor passed to the *at variants. None for the other calls
+    pub directory_fd: Option<i32>,
+    /// Success or the returned error. A missing file returns ENOENT
+    pub return_val: Result<(), i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,17 +127,140 @@ pub struct ShutdownData {
     pub return_val: Result<(), i32>,
 }
 
+/// The kind of channel created by a call that returns IPC file descriptors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IpcKind {
+    /// pipe or pipe2
+    Pipe,
+    /// socketpair
+    SocketPair,
+    /// eventfd or eventfd2
+    EventFd,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcData {
+    pub kind: IpcKind,
+    /// The file descriptors created: two for pipes and socketpairs, one for eventfds,
+    /// or the error returned. Empty if there was an error reading the memory
+    pub file_descriptors: Result<Vec<i32>, i32>,
+    /// The flags passed to pipe2, socketpair (as part of the type) or eventfd2
+    pub flags: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DupData {
+    /// The file descriptor being duplicated
+    pub old_fd: i32,
+    /// The new file descriptor, or the error returned
+    pub new_fd: Result<i32, i32>,
+}
+
+/// A call that changes the kernel, mounts or namespaces, and so needs privileges
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PrivilegedOperation {
+    /// init_module or finit_module
+    LoadModule {
+        /// The file descriptor of the module for finit_module. None for init_module, which
+        /// loads the module from memory
+        file_descriptor: Option<i32>,
+        params: Option<OsString>,
+    },
+    /// delete_module
+    UnloadModule { name: Option<OsString> },
+    /// bpf
+    Bpf {
+        /// The command, such as BPF_PROG_LOAD. See bpf(2) for possible values
+        command: i32,
+    },
+    /// mount
+    Mount {
+        source: Option<OsString>,
+        target: Option<OsString>,
+        filesystem_type: Option<OsString>,
+        flags: u64,
+    },
+    /// umount2
+    Unmount {
+        target: Option<OsString>,
+        flags: i32,
+    },
+    /// pivot_root
+    PivotRoot {
+        new_root: Option<OsString>,
+        put_old: Option<OsString>,
+    },
+    /// chroot
+    Chroot { path: Option<OsString> },
+    /// unshare
+    Unshare {
+        /// The CLONE_* flags of the namespaces and other resources to unshare
+        flags: i32,
+    },
+    /// setns
+    SetNs {
+        /// A file descriptor referring to a namespace or process
+        file_descriptor: i32,
+        /// The CLONE_NEW* flag of the namespace type to join, or 0 for any
+        namespace_type: i32,
+    },
+    /// kexec_load or kexec_file_load
+    KexecLoad {
+        /// The file descriptor of the kernel for kexec_file_load. None for kexec_load
+        kernel_fd: Option<i32>,
+        cmdline: Option<OsString>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivilegedData {
+    pub operation: PrivilegedOperation,
+    /// Success or the returned error
+    pub return_val: Result<(), i32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ForkData {
     /// 0 for the child thread, the child PID for the parent, or the error returned
     pub pid: Result<u32, i32>,
+    /// The CLONE_* flags of the call. fork and vfork are given the flags of the
+    /// equivalent call to clone. See clone(2) for details
+    pub flags: u64,
+}
+
+impl ForkData {
+    /// Whether the call created a thread in the same process instead of a new process
+    pub fn is_thread(&self) -> bool {
+        self.flags & nix::libc::CLONE_THREAD as u64 != 0
+    }
+
+    /// Whether the parent is suspended until the child execs or exits, as with vfork
+    pub fn is_vfork(&self) -> bool {
+        self.flags & nix::libc::CLONE_VFORK as u64 != 0
+    }
+
+    /// The CLONE_NEW* flags of the namespaces created for the child
+    pub fn namespaces(&self) -> i32 {
+        let namespace_flags = nix::libc::CLONE_NEWNS
+            | nix::libc::CLONE_NEWUTS
+            | nix::libc::CLONE_NEWIPC
+            | nix::libc::CLONE_NEWUSER
+            | nix::libc::CLONE_NEWPID
+            | nix::libc::CLONE_NEWNET
+            | nix::libc::CLONE_NEWCGROUP;
+        // CLONE_NEWTIME overlaps CLONE_CSIGNAL in clone; it can only be used with clone3
+        self.flags as i32 & namespace_flags
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecveData {
     /// the file of the process to execute
     pub filename: Option<OsString>,
-    /// the arguments passed to the process. This pointer is not read
+    /// the arguments passed to the process, starting with the program name. At most 16 are
+    /// read
+    pub arguments: Vec<OsString>,
+    /// a pointer to the arguments passed to the process
     pub args: u64,
     /// the environment variables for the process. This pointer is not read
     pub environment: u64,
@@ -85,6 +268,8 @@ pub struct ExecveData {
     pub directory_fd: Option<i32>,
     /// flags passed to execveat. None if this call is not execveat
     pub flags: Option<i32>,
+    /// None if the call did not return, because the process was killed while it ran
+    pub return_val: Option<Result<(), i32>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -94,6 +279,50 @@ pub struct ExitData {
     pub status: i32,
 }
 
+/// How a process terminated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitStatus {
+    /// The process exited normally with the given status code
+    Exited(i32),
+    /// The process was terminated by the given signal
+    Signaled(i32),
+    /// The process exited, but its status could not be determined
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessExitData {
+    /// The termination status of the process, as seen by its parent
+    pub status: ExitStatus,
+}
+
+/// Why the other event of a system call is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnpairedReason {
+    /// The sys_enter event was lost, or the call started before tracing did
+    MissingEnter,
+    /// The sys_exit event was lost, or the call did not return before the process exited
+    MissingExit,
+}
+
+/// A sys_enter or sys_exit event that could not be paired with the other event of its call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnpairedData {
+    pub reason: UnpairedReason,
+    /// The value returned by the call, if the exit event was received
+    pub return_val: Option<i64>,
+}
+
+/// The number of events lost by the perf buffer of one map on one CPU
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LostEventsData {
+    /// The name of the eBPF map, e.g. `EVENT_OUTPUT` for system call events or
+    /// `BUFFER_OUTPUT` for the data they read and write
+    pub map: String,
+    pub cpu: u32,
+    pub count: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnhandledSyscallData {
     pub syscall_id: u64,
@@ -111,22 +340,42 @@ pub enum SyscallData {
     Open(OpenData),
     Read(ReadData),
     Write(WriteData),
+    Transfer(TransferData),
+    ListDirectory(ListDirectoryData),
+    Probe(ProbeData),
     Close(CloseData),
     Socket(SocketData),
     Shutdown(ShutdownData),
+    Ipc(IpcData),
+    Dup(DupData),
+    Privileged(PrivilegedData),
     Fork(ForkData),
     Execve(ExecveData),
     Exit(ExitData),
+    /// Not a system call: the process has terminated. This is the last event sent for a process.
+    ProcessExit(ProcessExitData),
+    /// Not a complete system call: the enter or exit event of the call is missing
+    Unpaired(UnpairedData),
+    /// Not a system call: events were lost because a perf buffer was full. These are sent
+    /// after all other events
+    LostEvents(LostEventsData),
     Unhandled(UnhandledSyscallData),
 }
 
+/// The `syscall_id` used for [`SyscallData::ProcessExit`] events, which are not caused by a
+/// system call
+pub const PROCESS_EXIT_ID: u64 = u64::MAX;
+
+/// The `syscall_id` used for [`SyscallData::LostEvents`] events
+pub const LOST_EVENTS_ID: u64 = u64::MAX - 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TraceEvent {
     /// The process ID, also the thread global ID in kernel space
     pub pid: u32,
     /// The thread ID, also the process ID in kernel space
     pub thread_id: u32,
-    /// ID of the system call that caused the ID
+    /// ID of the system call that caused the event, or [`PROCESS_EXIT_ID`]
     pub syscall_id: u64,
     /// the value returned by bpf_ktime_get_ns: nanoseconds running since boot for the sys_enter
     /// event
@@ -134,18 +383,78 @@ pub struct TraceEvent {
     /// the value returned by bpf_ktime_get_ns: nanoseconds running since boot for the sys_exit
     /// event
     pub monotonic_exit_timestamp: u64,
+    /// the wall-clock time of the sys_enter event
+    #[serde(with = "iso8601")]
+    pub enter_time: SystemTime,
+    /// the wall-clock time of the sys_exit event
+    #[serde(with = "iso8601")]
+    pub exit_time: SystemTime,
+    /// Whether the data captured for this event is shorter than the data the system call
+    /// transferred, because of the capture limit or lost data
+    pub data_truncated: bool,
     pub data: SyscallData,
 }
 
+/// Converts the monotonic timestamps of events to wall-clock time.
+///
+/// bpf_ktime_get_ns counts from boot and does not follow changes to the system clock, so the
+/// offset between the two clocks is recorded once, when tracing starts.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct WallClock {
+    /// the wall-clock time at boot, as a duration since the Unix epoch
+    boot_time: Duration,
+}
+
+impl WallClock {
+    /// Records the current offset between the monotonic clock and the wall clock
+    pub fn now() -> nix::Result<Self> {
+        let monotonic = Duration::from(clock_gettime(ClockId::CLOCK_MONOTONIC)?);
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            boot_time: now.saturating_sub(monotonic),
+        })
+    }
+
+    /// The wall-clock time of a monotonic timestamp
+    pub fn wall_time(self, monotonic_timestamp: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + self.boot_time + Duration::from_nanos(monotonic_timestamp)
+    }
+
+    /// The ISO-8601 UTC time of a monotonic timestamp, to the millisecond
+    pub fn format(self, monotonic_timestamp: u64) -> String {
+        humantime::format_rfc3339_millis(self.wall_time(monotonic_timestamp)).to_string()
+    }
+}
+
+/// Serializes times as ISO-8601 (RFC 3339) UTC strings, to the nanosecond
+mod iso8601 {
+    use std::time::SystemTime;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&humantime::format_rfc3339_nanos(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        humantime::parse_rfc3339(&text).map_err(D::Error::custom)
+    }
+}
+
 // ============================== processing types ==============================
 
-/// The access type for a file or directory; whether it was read, written to, or executed.
-/// Similar to the Unix file permissions, but for a specific file.
+/// The access type for a file or directory; whether it was read, written to, executed, or
+/// listed. Similar to the Unix file permissions, but for a specific file.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct AccessType {
     pub read: bool,
     pub write: bool,
     pub execute: bool,
+    /// The entries of the directory were read
+    pub list: bool,
 }
 
 //  TODO(ui): add a warning in the UI that shows to the user if there is something bad
@@ -178,7 +487,7 @@ pub struct FileBehavior {
 }
 
 /// A summary of the file accesses: information as to the amount of accesses, bytes written/read, as well directories and overall behavior
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct FileSummary {
     /// number of accesses
     pub access_count: u64,
@@ -188,6 +497,8 @@ pub struct FileSummary {
     pub bytes_read: u64,
     /// the directories accessed by the process
     pub directories: Vec<OsString>,
+    /// number of paths checked with stat, access or readlink
+    pub probe_count: u64,
     /// the overall behavior of the process with respect to major system directory types
     pub behavior: FileBehavior,
 }
@@ -214,6 +525,8 @@ pub enum ConnectionProtocol {
 ///General summary of the connection, including the start and endtime for each connection, as well the domain and protocol
 #[derive(Debug, Clone)]
 pub struct Connection {
+    /// the process that made the connection
+    pub pid: u32,
     /// the monotonic timestamp when the processs first began
     pub start_time: u64,
     /// the
@@ -229,13 +542,17 @@ pub struct ProcessSummary {
     pub programs: Vec<OsString>,
     /// the number of other processes spawned by the process, including forks
     pub processes_created: u32,
+    /// the number of threads created by the processes, which are not counted as processes
+    pub threads_created: u32,
     /// the most common spawn type of the process
     pub most_common_spawn_type: SpawnType,
+    /// how the traced process terminated. None if its exit was not recorded
+    pub exit_status: Option<ExitStatus>,
 }
 
 /// A summary of the network accesses: information as to the number of
 /// connections, domains, and protocols used by the process
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct NetworkSummary {
     /// number of connections created by the process
     pub connection_count: u64,
@@ -245,11 +562,13 @@ pub struct NetworkSummary {
     pub protocols: Vec<ConnectionProtocol>,
 }
 
-/// The type of spawn: fork or exec
+/// The type of spawn: fork, vfork, exec, or a new thread
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SpawnType {
     Fork,
+    VFork,
     Exec,
+    Thread,
 }
 
 /// An event indicating a new process being spawned
@@ -265,7 +584,52 @@ pub struct SpawnEvent {
     pub parent_id: u32,
     /// the command/filename of the spawned process
     pub command: Option<OsString>,
-    // TODO(tracing): add arguments and environment
+    /// the arguments passed to the command. Empty for forks
+    pub arguments: Vec<OsString>,
+    // TODO(tracing): add environment
+    /// how the spawned process terminated. None if its exit was not recorded
+    pub exit_status: Option<ExitStatus>,
+    /// the CLONE_NEW* flags of the namespaces created for the process. 0 if none were created
+    pub namespaces: i32,
+}
+
+/// A traced process in the process tree, with a summary of what it did
+#[derive(Debug, Clone)]
+pub struct ProcessNode {
+    pub pid: u32,
+    /// How the process was created. None for the traced program
+    pub spawn_type: Option<SpawnType>,
+    /// The last program the process executed. None if it did not call execve
+    pub command: Option<OsString>,
+    /// The arguments passed to `command`
+    pub arguments: Vec<OsString>,
+    /// The monotonic timestamp when the process was created, or of its first event
+    pub start_time: u64,
+    /// The monotonic timestamp when the process exited. None if its exit was not recorded
+    pub end_time: Option<u64>,
+    /// How the process terminated. None if its exit was not recorded
+    pub exit_status: Option<ExitStatus>,
+    /// The CLONE_NEW* flags of the namespaces created for the process. 0 if none were created
+    pub namespaces: i32,
+    /// The number of files the process accessed, other than standard input and output
+    pub file_count: u64,
+    /// The number of bytes the process read from or wrote to files
+    pub bytes_transferred: u64,
+    /// The number of connections the process made
+    pub connection_count: u64,
+    /// The number of alerts caused by the process
+    pub alert_count: usize,
+    /// The processes created by this process, in the order they were created
+    pub children: Vec<ProcessNode>,
+}
+
+/// The file and network activity of a single process
+#[derive(Debug, Clone, Default)]
+pub struct ProcessActivity {
+    pub file_summary: FileSummary,
+    pub network_summary: NetworkSummary,
+    /// the number of alerts caused by the process
+    pub alert_count: usize,
 }
 
 /// The total sum of the data collected from the tracing and processing stages
@@ -277,23 +641,117 @@ pub struct ProcessingData {
     pub network_events: Vec<Connection>,
     pub process_summary: ProcessSummary,
     pub process_events: Vec<SpawnEvent>,
+    /// The traced processes, arranged by which process created which
+    pub process_tree: Vec<ProcessNode>,
+    /// The activity of each traced process. The file and network summaries are the sum of these
+    pub process_activity: HashMap<u32, ProcessActivity>,
     pub alerts: Vec<Alert>,
     /// The system call IDs that were not handled by the tracing stage
     pub unhandled_ids: Vec<u64>,
+    /// The pipes, socketpairs and eventfds used by the traced processes
+    pub ipc_channels: Vec<IpcChannel>,
+    /// The paths checked without being opened
+    pub path_probes: Vec<PathProbe>,
+    /// How complete the trace is
+    pub trace_quality: TraceQuality,
+    /// Converts the monotonic timestamps to wall-clock time
+    pub clock: WallClock,
+    /// The monotonic timestamp of the first event, or 0 if there were none
+    pub trace_start: u64,
+}
+
+/// The events that were lost or incomplete while tracing. If any were, the rest of the
+/// data may be missing files, connections or processes
+#[derive(Debug, Clone, Default)]
+pub struct TraceQuality {
+    /// The events lost by each perf buffer that lost any
+    pub lost_events: Vec<LostEventsData>,
+    /// Events whose sys_enter or sys_exit was not received
+    pub unpaired_events: Vec<TraceEvent>,
+}
+
+/// A path checked with stat, access or readlink
+#[derive(Debug, Clone)]
+pub struct PathProbe {
+    pub probe: ProbeType,
+    /// The path checked, relative to the directory file descriptor if it is not absolute
+    pub path: OsString,
+    /// The process ID of the process that checked the path
+    pub pid: u32,
+    /// The monotonic timestamp of the check
+    pub time: u64,
+    /// Whether the path exists and could be checked
+    pub success: bool,
 }
 
 /// An alert indicating a potential security issue, such as writing to root directory.
 #[derive(Debug, Clone)]
 pub struct Alert {
+    /// The check that raised the alert
+    pub rule: AlertRule,
     /// The severity of the alert; lower is more severe
     pub severity: u8,
     /// A short message describing the alert
     pub message: String,
+    /// The operation that raised the alert, such as a privileged system call and its
+    /// arguments. Unlike the message, it does not name the process, so it can be compared
+    /// between runs. None if the alert is not about a single operation
+    pub operation: Option<String>,
+    /// The process that caused the alert. None if it was not caused by a single process
+    pub pid: Option<u32>,
+    /// The monotonic timestamp of the first event that caused the alert. None if the alert
+    /// is about the trace as a whole
+    pub time: Option<u64>,
+    /// The files involved, such as the system files written to. Empty if the alert is not
+    /// about specific files
+    pub paths: Vec<OsString>,
+}
+
+/// The checks that raise alerts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertRule {
+    PrivilegedOperation,
+    SuspiciousFiles,
+    RootAccess,
+    CredentialProbing,
+    SystemWrite,
+    CurrentDirectoryExecution,
+    NonSystemExecution,
+    RuntimeAccess,
+    LostEvents,
+    UnpairedEvents,
+    /// Raised when the command did something that is not in the policy
+    PolicyDeviation,
+    /// Raised when no other check found anything
+    NoFindings,
+}
+
+/// The behavior expected of a traced command, such as a baseline written from a known-good
+/// trace. Alerts about expected files and programs are suppressed, and anything else the
+/// command does is reported as a deviation.
+///
+/// Paths ending in `/` match every path in that directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// The files the command is expected to access
+    pub paths: Vec<String>,
+    /// The programs the command is expected to execute
+    pub executables: Vec<String>,
+    /// The kinds of connections the command is expected to make, such as `TCP IPv4`
+    pub endpoints: Vec<String>,
+    /// The operations the command is expected to do, such as privileged system calls, each
+    /// as the ID of its rule and its description, e.g. `BB001 Unloaded kernel module floppy`
+    pub operations: Vec<String>,
+    /// The IDs of the rules whose alerts are always suppressed, such as `BB008`
+    pub rules: Vec<String>,
 }
 
 /// A file access event that includes all of the relevant data about the file interaction
 #[derive(Debug, Clone)]
 pub struct FileAccess {
+    /// The process that accessed the file
+    pub pid: u32,
     /// The name of the file accessed
     pub file_name: Option<OsString>,
     /// The file descriptor of the file accessed
@@ -312,6 +770,92 @@ pub struct FileAccess {
     pub error_count: i32,
     /// The access type of the file access
     pub access_type: AccessType,
+    /// Whether `read_data` or `write_data` is missing data that was transferred
+    pub data_truncated: bool,
+    /// The kind of IPC channel the file descriptor refers to. None for regular files
+    pub ipc_kind: Option<IpcKind>,
+    /// The format detected in `read_data`. None if no data was read
+    pub read_format: Option<PayloadFormat>,
+    /// The format detected in `write_data`. None if no data was written
+    pub write_format: Option<PayloadFormat>,
+}
+
+/// The format of captured file data, detected from its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    Json,
+    Http,
+    Elf,
+    Gzip,
+    ShellScript,
+    /// PEM-encoded keys or certificates
+    Pem,
+    /// Other UTF-8 text
+    Text,
+    Binary,
+}
+
+/// A pipe, socketpair or eventfd, and the processes that used it
+#[derive(Debug, Clone)]
+pub struct IpcChannel {
+    pub kind: IpcKind,
+    /// A name for the channel, unique within a trace (e.x. `pipe:[0]`)
+    pub name: String,
+    /// The processes and file descriptors that wrote to the channel
+    pub writers: Vec<IpcEndpoint>,
+    /// The processes and file descriptors that read from the channel
+    pub readers: Vec<IpcEndpoint>,
+    /// The number of bytes written to the channel
+    pub bytes_written: u64,
+}
+
+/// One end of an [`IpcChannel`] in a process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpcEndpoint {
+    /// The process ID
+    pub pid: u32,
+    /// The command last executed by the process. None if it was not recorded
+    pub command: Option<OsString>,
+    /// The file descriptor the process used
+    pub file_descriptor: i32,
+}
+
+/// The differences between an older and a newer trace of the same command
+#[derive(Debug, Clone, Default)]
+pub struct TraceDiff {
+    /// The files accessed only in the newer trace
+    pub added_files: Vec<OsString>,
+    /// The files accessed only in the older trace
+    pub removed_files: Vec<OsString>,
+    pub added_directories: Vec<OsString>,
+    pub removed_directories: Vec<OsString>,
+    /// The programs executed only in the newer trace
+    pub added_programs: Vec<OsString>,
+    pub removed_programs: Vec<OsString>,
+    /// The kinds of connections made only in the newer trace. Addresses are not recorded, so
+    /// endpoints are told apart by their domain and protocol
+    pub added_endpoints: Vec<(ConnectionDomain, ConnectionProtocol)>,
+    pub removed_endpoints: Vec<(ConnectionDomain, ConnectionProtocol)>,
+    /// The alerts raised only in the newer trace
+    pub added_alerts: Vec<Alert>,
+    /// The alerts raised only in the older trace
+    pub removed_alerts: Vec<Alert>,
+    /// The files accessed in both traces whose number of bytes read or written changed
+    pub byte_changes: Vec<ByteChange>,
+    /// The total bytes read from files in the older and newer traces
+    pub bytes_read: (u64, u64),
+    /// The total bytes written to files in the older and newer traces
+    pub bytes_written: (u64, u64),
+}
+
+/// A change in the number of bytes read from or written to a file
+#[derive(Debug, Clone)]
+pub struct ByteChange {
+    pub path: OsString,
+    /// The bytes transferred in the older trace
+    pub before: usize,
+    /// The bytes transferred in the newer trace
+    pub after: usize,
 }
 
 impl Default for ProcessingData {
@@ -322,6 +866,7 @@ impl Default for ProcessingData {
                 bytes_written: 0,
                 bytes_read: 0,
                 directories: vec![],
+                probe_count: 0,
                 behavior: FileBehavior::default(),
             },
             file_events: vec![],
@@ -334,11 +879,20 @@ impl Default for ProcessingData {
             process_summary: ProcessSummary {
                 programs: vec![],
                 processes_created: 0,
+                threads_created: 0,
                 most_common_spawn_type: SpawnType::Fork,
+                exit_status: None,
             },
             process_events: vec![],
+            process_tree: vec![],
+            process_activity: HashMap::new(),
             alerts: vec![],
             unhandled_ids: vec![],
+            ipc_channels: vec![],
+            path_probes: vec![],
+            trace_quality: TraceQuality::default(),
+            clock: WallClock::default(),
+            trace_start: 0,
         }
     }
 }
@@ -348,6 +902,73 @@ impl AccessType {
         self.read |= other.read;
         self.write |= other.write;
         self.execute |= other.execute;
+        self.list |= other.list;
+    }
+}
+
+impl FileBehavior {
+    pub fn update(&mut self, other: &Self) {
+        self.stdio.update(&other.stdio);
+        self.current_dir.update(&other.current_dir);
+        self.home_dir.update(&other.home_dir);
+        self.system.update(&other.system);
+        self.runtime.update(&other.runtime);
+    }
+}
+
+impl FileSummary {
+    /// Adds the accesses of `other`, e.g. to sum the summaries of several processes
+    pub fn update(&mut self, other: &Self) {
+        self.access_count += other.access_count;
+        self.bytes_written += other.bytes_written;
+        self.bytes_read += other.bytes_read;
+        self.directories.extend(other.directories.iter().cloned());
+        self.directories.sort();
+        self.directories.dedup();
+        self.probe_count += other.probe_count;
+        self.behavior.update(&other.behavior);
+    }
+}
+
+impl NetworkSummary {
+    /// Adds the connections of `other`, e.g. to sum the summaries of several processes
+    pub fn update(&mut self, other: &Self) {
+        self.connection_count += other.connection_count;
+        self.domains.extend(other.domains.iter().copied());
+        self.domains.sort();
+        self.domains.dedup();
+        self.protocols.extend(other.protocols.iter().copied());
+        self.protocols.sort();
+        self.protocols.dedup();
+    }
+}
+
+impl TraceQuality {
+    /// The total number of events lost
+    pub fn lost_count(&self) -> u64 {
+        self.lost_events.iter().map(|lost| lost.count).sum()
+    }
+
+    /// Whether every event was received
+    pub fn is_complete(&self) -> bool {
+        self.lost_events.is_empty() && self.unpaired_events.is_empty()
+    }
+}
+
+impl TraceDiff {
+    /// Whether the traces behaved the same
+    pub fn is_empty(&self) -> bool {
+        self.added_files.is_empty()
+            && self.removed_files.is_empty()
+            && self.added_directories.is_empty()
+            && self.removed_directories.is_empty()
+            && self.added_programs.is_empty()
+            && self.removed_programs.is_empty()
+            && self.added_endpoints.is_empty()
+            && self.removed_endpoints.is_empty()
+            && self.added_alerts.is_empty()
+            && self.removed_alerts.is_empty()
+            && self.byte_changes.is_empty()
     }
 }
 
@@ -381,6 +1002,324 @@ impl ConnectionProtocol {
     }
 }
 
+impl ExitStatus {
+    pub fn text(self) -> String {
+        match self {
+            ExitStatus::Exited(code) => format!("Exited ({})", code),
+            ExitStatus::Signaled(signal) => match nix::sys::signal::Signal::try_from(signal) {
+                Ok(signal) => format!("Killed ({})", signal),
+                Err(_) => format!("Killed ({})", signal),
+            },
+            ExitStatus::Unknown => String::from("Exited (unknown)"),
+        }
+    }
+
+    pub fn tooltip(self) -> String {
+        match self {
+            ExitStatus::Exited(0) => String::from("The process exited successfully"),
+            ExitStatus::Exited(_) => String::from("The process exited with an error status"),
+            ExitStatus::Signaled(_) => String::from("The process was terminated by a signal"),
+            ExitStatus::Unknown => {
+                String::from("The process exited, but its exit status was not recorded")
+            }
+        }
+    }
+}
+
+/// Formats the time between two monotonic timestamps, such as `1.5ms`. `??` if the start
+/// was not recorded or the end is before it
+pub fn duration(start: u64, end: u64) -> String {
+    let time = end as i64 - start as i64;
+    if time < 0 || start == 0 {
+        return String::from("??");
+    }
+    match time {
+        t if t > 1e9 as i64 => {
+            format!("{:.1}s", (t as f64) / 1e9)
+        }
+        t if t > 1e6 as i64 => {
+            format!("{:.1}ms", (t as f64) / 1e6)
+        }
+        t if t > 1e3 as i64 => {
+            format!("{:.1}µs", (t as f64) / 1e3)
+        }
+        t => {
+            format!("{:.1}ns", (t as f64))
+        }
+    }
+}
+
+/// Describes the namespaces in a set of CLONE_NEW* flags, such as `mount, PID`
+pub fn namespace_flags_text(flags: i32) -> String {
+    use nix::libc;
+    let namespaces = [
+        (libc::CLONE_NEWNS, "mount"),
+        (libc::CLONE_NEWUTS, "UTS"),
+        (libc::CLONE_NEWIPC, "IPC"),
+        (libc::CLONE_NEWUSER, "user"),
+        (libc::CLONE_NEWPID, "PID"),
+        (libc::CLONE_NEWNET, "network"),
+        (libc::CLONE_NEWCGROUP, "cgroup"),
+        (libc::CLONE_NEWTIME, "time"),
+    ];
+    let names = namespaces
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        String::from("none")
+    } else {
+        names.join(", ")
+    }
+}
+
+impl PrivilegedOperation {
+    /// The files the operation used, such as the module loaded or the mount target.
+    /// `fd_name` gives the file name of a file descriptor, if it is known
+    pub fn paths(&self, fd_name: impl Fn(i32) -> Option<OsString>) -> Vec<OsString> {
+        let paths = match self {
+            PrivilegedOperation::LoadModule {
+                file_descriptor, ..
+            } => vec![file_descriptor.and_then(&fd_name)],
+            PrivilegedOperation::Mount { source, target, .. } => {
+                vec![source.clone(), target.clone()]
+            }
+            PrivilegedOperation::Unmount { target, .. } => vec![target.clone()],
+            PrivilegedOperation::PivotRoot { new_root, put_old } => {
+                vec![new_root.clone(), put_old.clone()]
+            }
+            PrivilegedOperation::Chroot { path } => vec![path.clone()],
+            PrivilegedOperation::SetNs {
+                file_descriptor, ..
+            } => vec![fd_name(*file_descriptor)],
+            PrivilegedOperation::KexecLoad { kernel_fd, .. } => vec![kernel_fd.and_then(&fd_name)],
+            PrivilegedOperation::UnloadModule { .. }
+            | PrivilegedOperation::Bpf { .. }
+            | PrivilegedOperation::Unshare { .. } => vec![],
+        };
+        paths.into_iter().flatten().collect()
+    }
+
+    /// A description of the operation and its arguments for an alert.
+    /// `fd_name` gives the file name of a file descriptor, if it is known
+    pub fn describe(&self, fd_name: impl Fn(i32) -> Option<OsString>) -> String {
+        let show = |s: &Option<OsString>| match s {
+            Some(s) => s.to_string_lossy().into_owned(),
+            None => String::from("??"),
+        };
+        let show_fd = |fd: i32| match fd_name(fd) {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => format!("fd {}", fd),
+        };
+        match self {
+            PrivilegedOperation::LoadModule {
+                file_descriptor,
+                params,
+            } => format!(
+                "Loaded kernel module {} with parameters \"{}\"",
+                file_descriptor
+                    .map(show_fd)
+                    .unwrap_or_else(|| String::from("from memory")),
+                show(params)
+            ),
+            PrivilegedOperation::UnloadModule { name } => {
+                format!("Unloaded kernel module {}", show(name))
+            }
+            PrivilegedOperation::Bpf { command } => format!(
+                "Used bpf to {}",
+                match command {
+                    0 => String::from("create a map"),
+                    5 => String::from("load a program"),
+                    8 => String::from("attach a program"),
+                    c => format!("run command {}", c),
+                }
+            ),
+            PrivilegedOperation::Mount {
+                source,
+                target,
+                filesystem_type,
+                flags,
+            } => format!(
+                "Mounted {} on {} (type {}, flags {:#x})",
+                show(source),
+                show(target),
+                show(filesystem_type),
+                flags
+            ),
+            PrivilegedOperation::Unmount { target, .. } => format!("Unmounted {}", show(target)),
+            PrivilegedOperation::PivotRoot { new_root, put_old } => format!(
+                "Changed the root mount to {}, moving the old root to {}",
+                show(new_root),
+                show(put_old)
+            ),
+            PrivilegedOperation::Chroot { path } => {
+                format!("Changed the root directory to {}", show(path))
+            }
+            PrivilegedOperation::Unshare { flags } => {
+                format!("Created new namespaces: {}", namespace_flags_text(*flags))
+            }
+            PrivilegedOperation::SetNs {
+                file_descriptor,
+                namespace_type,
+            } => format!(
+                "Joined the namespace {} (type: {})",
+                show_fd(*file_descriptor),
+                if *namespace_type == 0 {
+                    String::from("any")
+                } else {
+                    namespace_flags_text(*namespace_type)
+                }
+            ),
+            PrivilegedOperation::KexecLoad { kernel_fd, cmdline } => format!(
+                "Loaded a new kernel {} with command line \"{}\"",
+                kernel_fd
+                    .map(show_fd)
+                    .unwrap_or_else(|| String::from("from memory")),
+                show(cmdline)
+            ),
+        }
+    }
+}
+
+impl ProbeType {
+    pub fn text(self) -> String {
+        format!("{:?}", self)
+    }
+
+    pub fn tooltip(self) -> String {
+        match self {
+            ProbeType::Stat => String::from("The file's metadata was read with stat"),
+            ProbeType::Access => String::from("The file's permissions were checked with access"),
+            ProbeType::ReadLink => String::from("The target of a symbolic link was read"),
+        }
+    }
+}
+
+impl IpcKind {
+    pub fn text(self) -> String {
+        format!("{:?}", self)
+    }
+
+    pub fn tooltip(self) -> String {
+        match self {
+            IpcKind::Pipe => String::from("A one-way pipe created with pipe or pipe2"),
+            IpcKind::SocketPair => {
+                String::from("A pair of connected sockets created with socketpair")
+            }
+            IpcKind::EventFd => String::from("An event counter created with eventfd"),
+        }
+    }
+}
+
+impl AlertRule {
+    /// A stable identifier for the rule, e.g. for code scanning tools
+    pub fn id(self) -> &'static str {
+        match self {
+            AlertRule::PrivilegedOperation => "BB001",
+            AlertRule::SuspiciousFiles => "BB002",
+            AlertRule::RootAccess => "BB003",
+            AlertRule::CredentialProbing => "BB004",
+            AlertRule::SystemWrite => "BB005",
+            AlertRule::CurrentDirectoryExecution => "BB006",
+            AlertRule::NonSystemExecution => "BB007",
+            AlertRule::RuntimeAccess => "BB008",
+            AlertRule::LostEvents => "BB009",
+            AlertRule::UnpairedEvents => "BB010",
+            AlertRule::PolicyDeviation => "BB011",
+            AlertRule::NoFindings => "BB000",
+        }
+    }
+
+    pub fn text(self) -> String {
+        format!("{:?}", self)
+    }
+
+    pub fn tooltip(self) -> String {
+        match self {
+            AlertRule::PrivilegedOperation => String::from(
+                "An operation that changes the system, such as loading a kernel module or mounting \
+                 a filesystem",
+            ),
+            AlertRule::SuspiciousFiles => {
+                String::from("Files with credentials or history, such as ~/.aws or shell histories")
+            }
+            AlertRule::RootAccess => String::from("Files in the root user's home directory"),
+            AlertRule::CredentialProbing => String::from(
+                "Many home directory or hidden paths checked without being opened",
+            ),
+            AlertRule::SystemWrite => String::from("Writes to system directories such as /usr"),
+            AlertRule::CurrentDirectoryExecution => {
+                String::from("Programs executed from the current directory")
+            }
+            AlertRule::NonSystemExecution => {
+                String::from("Programs executed from home or runtime directories such as /tmp")
+            }
+            AlertRule::RuntimeAccess => {
+                String::from("Access to runtime directories such as /proc and /dev")
+            }
+            AlertRule::LostEvents => String::from("Events lost while tracing"),
+            AlertRule::UnpairedEvents => {
+                String::from("System call events missing their enter or exit")
+            }
+            AlertRule::PolicyDeviation => {
+                String::from("Files, programs or connections that are not in the policy")
+            }
+            AlertRule::NoFindings => String::from("No other check found suspicious activity"),
+        }
+    }
+}
+
+impl PayloadFormat {
+    pub fn text(self) -> String {
+        match self {
+            PayloadFormat::Json => String::from("JSON"),
+            PayloadFormat::Http => String::from("HTTP"),
+            PayloadFormat::Elf => String::from("ELF"),
+            PayloadFormat::Gzip => String::from("gzip"),
+            PayloadFormat::ShellScript => String::from("Script"),
+            PayloadFormat::Pem => String::from("PEM"),
+            PayloadFormat::Text => String::from("Text"),
+            PayloadFormat::Binary => String::from("Binary"),
+        }
+    }
+
+    pub fn tooltip(self) -> String {
+        match self {
+            PayloadFormat::Json => String::from("JSON data"),
+            PayloadFormat::Http => String::from("An HTTP request or response"),
+            PayloadFormat::Elf => String::from("An executable or shared library"),
+            PayloadFormat::Gzip => String::from("gzip-compressed data"),
+            PayloadFormat::ShellScript => {
+                String::from("A script that starts with #! and the program that runs it")
+            }
+            PayloadFormat::Pem => String::from("PEM-encoded keys or certificates"),
+            PayloadFormat::Text => String::from("Text in no recognized format"),
+            PayloadFormat::Binary => String::from("Binary data in no recognized format"),
+        }
+    }
+}
+
+impl IpcEndpoint {
+    /// A short description of the endpoint, such as `curl stdout`
+    pub fn text(&self) -> String {
+        let process = match &self.command {
+            Some(command) => std::path::Path::new(command)
+                .file_name()
+                .unwrap_or(command)
+                .to_string_lossy()
+                .into_owned(),
+            None => format!("pid {}", self.pid),
+        };
+        match self.file_descriptor {
+            0 => format!("{} stdin", process),
+            1 => format!("{} stdout", process),
+            2 => format!("{} stderr", process),
+            fd => format!("{} fd {}", process, fd),
+        }
+    }
+}
+
 impl SpawnType {
     pub fn text(self) -> String {
         format!("{:?}", self)
@@ -389,6 +1328,11 @@ impl SpawnType {
     pub fn tooltip(self) -> String {
         match self {
             SpawnType::Fork => String::from("This process was spawned by forking"),
+            SpawnType::VFork => String::from(
+                "This process was spawned by vfork, which suspends the parent until the child \
+                 executes a command or exits",
+            ),
+            SpawnType::Thread => String::from("This is a new thread in an existing process"),
             SpawnType::Exec => {
                 String::from("This process was spawned by executing another command")
             }
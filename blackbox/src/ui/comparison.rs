@@ -0,0 +1,134 @@
+use std::ffi::OsString;
+
+use iced::{
+    widget::{column, container, row, scrollable, text, Space},
+    Length,
+};
+
+use crate::types::{Alert, ConnectionDomain, ConnectionProtocol, TraceDiff};
+
+use super::theme::ContainerType;
+use super::{chip, header, monospace, Element};
+
+/// The differences between two traces. The other tabs show the newer trace
+pub fn comparison_view(diff: &TraceDiff) -> Element<'_> {
+    let summary = if diff.is_empty() {
+        "The traces accessed the same files, ran the same programs, made the same kinds of \
+         connections and raised the same alerts."
+    } else {
+        "Green items are only in the newer trace, which is shown in the other tabs. Red items \
+         are only in the older trace."
+    };
+    let endpoint = |(domain, protocol): &(ConnectionDomain, ConnectionProtocol)| -> Element {
+        row![
+            chip(protocol.text(), protocol.tooltip()),
+            chip(domain.text(), domain.tooltip()),
+        ]
+        .spacing(5)
+        .into()
+    };
+    let alert = |alert: &Alert| -> Element {
+        text(format!("[{}] {}", alert.rule.id(), alert.message)).into()
+    };
+    let bytes = diff
+        .byte_changes
+        .iter()
+        .map(|change| {
+            row![
+                monospace(change.path.to_string_lossy()),
+                Space::with_width(Length::Fill),
+                text(format!(
+                    "{} → {} bytes ({:+})",
+                    change.before,
+                    change.after,
+                    change.after as i64 - change.before as i64
+                )),
+            ]
+            .align_items(iced::Alignment::Center)
+            .spacing(10)
+            .into()
+        })
+        .collect::<Vec<Element>>();
+
+    let main_view = column![
+        text(summary),
+        section(
+            "Files:",
+            paths(&diff.added_files),
+            paths(&diff.removed_files)
+        ),
+        section(
+            "Directories:",
+            paths(&diff.added_directories),
+            paths(&diff.removed_directories)
+        ),
+        section(
+            "Programs Executed:",
+            paths(&diff.added_programs),
+            paths(&diff.removed_programs)
+        ),
+        section(
+            "Connections:",
+            diff.added_endpoints.iter().map(endpoint).collect(),
+            diff.removed_endpoints.iter().map(endpoint).collect()
+        ),
+        section(
+            "Alerts:",
+            diff.added_alerts.iter().map(alert).collect(),
+            diff.removed_alerts.iter().map(alert).collect()
+        ),
+        header("Bytes Transferred:"),
+        container(
+            column![
+                text(format!(
+                    "Total Bytes Read: {} → {}",
+                    diff.bytes_read.0, diff.bytes_read.1
+                )),
+                text(format!(
+                    "Total Bytes Written: {} → {}",
+                    diff.bytes_written.0, diff.bytes_written.1
+                )),
+                column(bytes).spacing(3),
+            ]
+            .spacing(3)
+        )
+        .padding(10.0)
+        .width(Length::Fill)
+        .style(ContainerType::SubtleCard(10.0)),
+    ]
+    .spacing(10);
+    scrollable(main_view)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+}
+
+fn paths(paths: &[OsString]) -> Vec<Element<'static>> {
+    paths
+        .iter()
+        .map(|path| monospace(path.to_string_lossy()))
+        .collect()
+}
+
+/// The added items, then the removed items. Sections without changes show that nothing changed
+fn section<'a>(title: &str, added: Vec<Element<'a>>, removed: Vec<Element<'a>>) -> Element<'a> {
+    let items: Element = if added.is_empty() && removed.is_empty() {
+        text("No changes").into()
+    } else {
+        column(
+            added
+                .into_iter()
+                .map(|item| container(item).style(ContainerType::Green))
+                .chain(
+                    removed
+                        .into_iter()
+                        .map(|item| container(item).style(ContainerType::Red)),
+                )
+                .map(|item| item.padding(3).width(Length::Fill).into())
+                .collect(),
+        )
+        .spacing(3)
+        .into()
+    };
+    column![header(title), items].into()
+}
@@ -1,3 +1,6 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
 use std::sync::Arc;
 
 use color_eyre::Result;
@@ -9,12 +12,19 @@ use iced::{
 };
 use tokio::sync::{Mutex, Semaphore};
 
+use crate::processing::{describe_payload, filter_processes};
 use crate::types::{
-    AccessType, Connection, FileAccess, FileSummary, NetworkSummary, ProcessSummary,
-    ProcessingData, SpawnEvent,
+    duration, namespace_flags_text, AccessType, Connection, ExitStatus, FileAccess, FileSummary,
+    IpcChannel, IpcEndpoint, NetworkSummary, PathProbe, PayloadFormat, ProcessNode, ProcessSummary,
+    ProcessingData, TraceDiff, TraceQuality, WallClock,
 };
 
+mod comparison;
+mod search;
 mod theme;
+mod timeline;
+
+use search::{match_count, Search, SearchMessage};
 
 use theme::{ContainerType, Theme};
 
@@ -35,12 +45,19 @@ impl Executor for CurrentExecutor {
     }
 }
 
+/// Shows the processed data once `done` is notified. If `diff` is set, the data is the newer of
+/// two compared traces, and the differences are shown in their own tab
 pub fn run(
     done: Arc<tokio::sync::Semaphore>,
     shared_state: Arc<Mutex<Option<ProcessingData>>>,
+    diff: Option<TraceDiff>,
 ) -> Result<()> {
     App::run(Settings {
-        flags: Flags { done, shared_state },
+        flags: Flags {
+            done,
+            shared_state,
+            diff,
+        },
         id: Some("Blackbox".to_string()),
         window: Default::default(),
         default_font: {
@@ -66,12 +83,57 @@ enum State {
 struct App {
     state: State,
     data: ProcessingData,
+    /// the processes whose children are hidden in the process tree
+    collapsed: HashSet<u32>,
+    /// the process that every tab is narrowed down to
+    selection: Option<Selection>,
+    /// `data` narrowed down to the selected processes
+    filtered: Option<ProcessingData>,
+    /// how far the timeline is stretched horizontally
+    zoom: f32,
+    /// the search applied to the File, Network and Process tabs
+    search: Search,
+    /// how captured file data is shown
+    payload_mode: PayloadMode,
+    /// the result of the last attempt to save captured data
+    save_status: Option<String>,
+    /// the differences from an older trace, when comparing two traces
+    diff: Option<TraceDiff>,
+}
+
+/// How captured file data is shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum PayloadMode {
+    #[default]
+    Hex,
+    /// UTF-8, with invalid sequences replaced
+    Text,
+    /// ASCII, with other bytes escaped
+    Escaped,
+    /// indented or summarized according to the detected format
+    Pretty,
+}
+
+/// Identifies the read or write data of a file access
+#[derive(Debug, Clone, Copy)]
+struct PayloadId {
+    pid: u32,
+    file_descriptor: i32,
+    start_time: u64,
+    write: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Selection {
+    pid: u32,
+    include_children: bool,
 }
 
 #[derive(Debug)]
 struct Flags {
     done: Arc<Semaphore>,
     shared_state: Arc<Mutex<Option<ProcessingData>>>,
+    diff: Option<TraceDiff>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -80,6 +142,9 @@ enum Tab {
     File,
     Network,
     Process,
+    Timeline,
+    /// only shown when comparing two traces
+    Comparison,
 }
 
 #[derive(Debug, Clone)]
@@ -87,6 +152,13 @@ enum Message {
     Done(Box<ProcessingData>),
     FontLoaded(Result<(), font::Error>),
     SwitchTab(Tab),
+    ToggleProcess(u32),
+    SelectProcess(Option<u32>),
+    IncludeChildren(bool),
+    Zoom(f32),
+    Search(SearchMessage),
+    PayloadMode(PayloadMode),
+    SavePayload(PayloadId),
 }
 
 impl Application for App {
@@ -103,6 +175,14 @@ impl Application for App {
             Self {
                 state: State::Processing,
                 data: ProcessingData::default(),
+                collapsed: HashSet::new(),
+                selection: None,
+                filtered: None,
+                zoom: 1.0,
+                search: Search::default(),
+                payload_mode: PayloadMode::default(),
+                save_status: None,
+                diff: flags.diff,
             },
             Command::batch(vec![
                 font::load(
@@ -129,7 +209,10 @@ impl Application for App {
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
         match message {
             Message::Done(processing_data) => {
-                self.state = State::OnTab(Tab::Summary);
+                self.state = State::OnTab(match self.diff {
+                    Some(_) => Tab::Comparison,
+                    None => Tab::Summary,
+                });
                 self.data = *processing_data;
                 Command::none()
             }
@@ -137,6 +220,43 @@ impl Application for App {
                 self.state = State::OnTab(tab);
                 Command::none()
             }
+            Message::ToggleProcess(pid) => {
+                if !self.collapsed.remove(&pid) {
+                    self.collapsed.insert(pid);
+                }
+                Command::none()
+            }
+            Message::SelectProcess(pid) => {
+                self.selection = pid.map(|pid| Selection {
+                    pid,
+                    include_children: true,
+                });
+                self.filter();
+                Command::none()
+            }
+            Message::IncludeChildren(include_children) => {
+                if let Some(selection) = &mut self.selection {
+                    selection.include_children = include_children;
+                }
+                self.filter();
+                Command::none()
+            }
+            Message::Zoom(zoom) => {
+                self.zoom = zoom;
+                Command::none()
+            }
+            Message::Search(message) => {
+                self.search.update(message);
+                Command::none()
+            }
+            Message::PayloadMode(mode) => {
+                self.payload_mode = mode;
+                Command::none()
+            }
+            Message::SavePayload(id) => {
+                self.save_status = Some(self.save_payload(id));
+                Command::none()
+            }
             Message::FontLoaded(r) => {
                 r.unwrap();
                 Command::none()
@@ -150,9 +270,18 @@ impl Application for App {
         );
         let logo = container(image(handle)).padding(10);
 
+        let mut tabs = vec![
+            Tab::Summary,
+            Tab::File,
+            Tab::Network,
+            Tab::Process,
+            Tab::Timeline,
+        ];
+        if self.diff.is_some() {
+            tabs.insert(0, Tab::Comparison);
+        }
         let tabs = column(
-            vec![Tab::Summary, Tab::File, Tab::Network, Tab::Process]
-                .into_iter()
+            tabs.into_iter()
                 .map(|tab| {
                     button(text(format!("{:?}", tab)).font(Font {
                         weight: Weight::Bold,
@@ -171,8 +300,8 @@ impl Application for App {
         )
         .spacing(10)
         .padding(10);
-        let alerts = self
-            .data
+        let data = self.filtered.as_ref().unwrap_or(&self.data);
+        let alerts = data
             .alerts
             .iter()
             .map(|alert| {
@@ -205,12 +334,27 @@ impl Application for App {
                     .height(Length::Fill)
                     .width(Length::FillPortion(1)),
                 column!(
+                    self.selection_view(),
                     alerts,
                     match tab {
-                        Tab::Summary => Self::summary_view(&self.data),
-                        Tab::File => Self::file_view(&self.data),
-                        Tab::Network => Self::network_view(&self.data),
-                        Tab::Process => Self::process_view(&self.data),
+                        Tab::File | Tab::Network | Tab::Process => self.search.view(),
+                        Tab::Summary | Tab::Timeline | Tab::Comparison => Space::new(0, 0).into(),
+                    },
+                    match tab {
+                        Tab::Summary => Self::summary_view(data),
+                        Tab::File => Self::file_view(
+                            data,
+                            &self.search,
+                            self.payload_mode,
+                            self.save_status.as_deref()
+                        ),
+                        Tab::Network => Self::network_view(data, &self.search),
+                        Tab::Process => Self::process_view(data, &self.collapsed, &self.search),
+                        Tab::Timeline => timeline::timeline_view(data, self.zoom),
+                        Tab::Comparison => match &self.diff {
+                            Some(diff) => comparison::comparison_view(diff),
+                            None => Space::new(0, 0).into(),
+                        },
                     }
                 )
                 .spacing(10.0)
@@ -228,6 +372,88 @@ type Element<'a> = iced::Element<
 >;
 
 impl App {
+    /// Writes the data of a file access to a new file in the directory blackbox was started
+    /// in, and describes the result. Existing files are not overwritten, and the file is owned
+    /// by the user who started blackbox with sudo
+    fn save_payload(&self, id: PayloadId) -> String {
+        let Some(access) = self.data.file_events.iter().find(|access| {
+            access.pid == id.pid
+                && access.file_descriptor == id.file_descriptor
+                && access.start_time == id.start_time
+        }) else {
+            return String::from("The file access was not found");
+        };
+        let (bytes, direction) = if id.write {
+            (&access.write_data, "write")
+        } else {
+            (&access.read_data, "read")
+        };
+        let name = format!(
+            "blackbox-{}-fd{}-{}-{}.bin",
+            id.pid, id.file_descriptor, direction, id.start_time
+        );
+        let path = std::env::current_dir().unwrap_or_default().join(name);
+        let result = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| {
+                give_to_sudo_user(&file)?;
+                file.write_all(bytes)
+            });
+        match result {
+            Ok(()) => format!("Saved {} bytes to {}", bytes.len(), path.display()),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                format!(
+                    "{} already exists, so it was not overwritten",
+                    path.display()
+                )
+            }
+            Err(error) => format!("Could not save {}: {}", path.display(), error),
+        }
+    }
+
+    /// Narrows the data down to the selected processes
+    fn filter(&mut self) {
+        self.filtered = self.selection.map(|selection| {
+            filter_processes(&self.data, selection.pid, selection.include_children)
+        });
+    }
+
+    fn selection_view(&self) -> Element<'_> {
+        let Some(selection) = self.selection else {
+            return text(
+                "Showing all processes. Select a process in the Process tab to narrow every tab down to it.",
+            )
+            .into();
+        };
+        row![
+            text(format!(
+                "Showing process {}{}",
+                selection.pid,
+                if selection.include_children {
+                    " and its children"
+                } else {
+                    ""
+                }
+            )),
+            Space::with_width(Length::Fill),
+            button(text(if selection.include_children {
+                "Only This Process"
+            } else {
+                "Include Children"
+            }))
+            .on_press(Message::IncludeChildren(!selection.include_children))
+            .style(Button::Secondary),
+            button(text("Show All"))
+                .on_press(Message::SelectProcess(None))
+                .style(Button::Secondary),
+        ]
+        .align_items(iced::Alignment::Center)
+        .spacing(10)
+        .into()
+    }
+
     fn summary_view(data: &ProcessingData) -> Element<'_> {
         let file_summary = container(row![
             container(header("File access:")).width(150),
@@ -251,41 +477,119 @@ impl App {
         .padding(10.0)
         .style(ContainerType::SubtleCard(10.0));
 
-        let main_view = column!(file_summary, network_summary, process_summary).spacing(10);
+        let trace_quality = container(row![
+            container(header("Trace quality:")).width(150),
+            Self::create_trace_quality(&data.trace_quality)
+        ])
+        .width(Length::Fill)
+        .padding(10.0)
+        .style(if data.trace_quality.lost_count() > 0 {
+            ContainerType::Orange
+        } else {
+            ContainerType::SubtleCard(10.0)
+        });
+
+        let main_view = column!(
+            file_summary,
+            network_summary,
+            process_summary,
+            trace_quality
+        )
+        .spacing(10);
         scrollable(main_view)
             .width(Length::Fill)
             .height(Length::Fill)
             .into()
     }
 
-    fn file_view(data: &ProcessingData) -> Element<'_> {
-        let mut sorted_files = data.file_events.clone();
+    fn file_view<'a>(
+        data: &'a ProcessingData,
+        search: &Search,
+        payload_mode: PayloadMode,
+        save_status: Option<&str>,
+    ) -> Element<'a> {
+        let mut sorted_files = data
+            .file_events
+            .iter()
+            .filter(|access| search.matches_file(access, data.trace_start))
+            .cloned()
+            .collect::<Vec<_>>();
         sorted_files.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+        let modes = [
+            ("Hex", PayloadMode::Hex),
+            ("Text", PayloadMode::Text),
+            ("Escaped", PayloadMode::Escaped),
+            ("Pretty", PayloadMode::Pretty),
+        ]
+        .into_iter()
+        .map(|(label, mode)| {
+            button(text(label).size(12))
+                .on_press(Message::PayloadMode(mode))
+                .style(if mode == payload_mode {
+                    Button::Primary
+                } else {
+                    Button::Secondary
+                })
+                .into()
+        })
+        .collect::<Vec<Element>>();
         column![
             header("Summary:"),
             container(Self::create_file_summary(&data.file_summary))
                 .padding(10.0)
                 .style(ContainerType::SubtleCard(10.0)),
             header("File Access Details:"),
+            row![
+                match_count(sorted_files.len(), data.file_events.len()),
+                Space::with_width(Length::Fill),
+                text(save_status.unwrap_or_default()).size(12),
+                text("Show data as:"),
+                row(modes).spacing(3),
+            ]
+            .align_items(iced::Alignment::Center)
+            .spacing(10),
             scrollable(
-                column(sorted_files.iter().map(Self::create_file_event).collect()).spacing(10.0)
+                column(
+                    sorted_files
+                        .iter()
+                        .map(|access| Self::create_file_event(access, data.clock, payload_mode))
+                        .collect()
+                )
+                .spacing(10.0)
             ),
+            header("Probed Paths:"),
+            scrollable(
+                column(
+                    data.path_probes
+                        .iter()
+                        .map(|probe| Self::create_path_probe(probe, data.clock))
+                        .collect()
+                )
+                .spacing(3.0)
+            )
+            .height(150),
         ]
         .into()
     }
 
-    fn network_view(data: &ProcessingData) -> Element<'_> {
+    fn network_view<'a>(data: &'a ProcessingData, search: &Search) -> Element<'a> {
+        let connections = data
+            .network_events
+            .iter()
+            .filter(|conn| search.matches_connection(conn, data.trace_start))
+            .collect::<Vec<_>>();
         column![
             header("Summary:"),
             container(Self::create_network_summary(&data.network_summary))
                 .padding(10.0)
                 .style(ContainerType::SubtleCard(10.0)),
             header("Connection Details:"),
+            match_count(connections.len(), data.network_events.len()),
             scrollable(
                 column(
-                    data.network_events
-                        .iter()
-                        .map(Self::create_connection)
+                    connections
+                        .into_iter()
+                        .map(|conn| Self::create_connection(conn, data.clock))
                         .collect()
                 )
                 .spacing(10.0)
@@ -294,19 +598,38 @@ impl App {
         .into()
     }
 
-    fn process_view(data: &ProcessingData) -> Element<'_> {
+    fn process_view<'a>(
+        data: &'a ProcessingData,
+        collapsed: &HashSet<u32>,
+        search: &Search,
+    ) -> Element<'a> {
+        let tree = search.filter_tree(&data.process_tree, data.trace_start);
+        let ipc_channels: Element = if data.ipc_channels.is_empty() {
+            text("No pipes, socketpairs or eventfds used").into()
+        } else {
+            column(
+                data.ipc_channels
+                    .iter()
+                    .map(Self::create_ipc_channel)
+                    .collect(),
+            )
+            .spacing(10.0)
+            .into()
+        };
         column![
             header("Summary:"),
             container(Self::create_process_summary(&data.process_summary))
                 .width(Length::Fill)
                 .padding(10.0)
                 .style(ContainerType::SubtleCard(10.0)),
-            header("Process Details:"),
+            header("IPC Channels:"),
+            ipc_channels,
+            header("Process Tree:"),
+            match_count(count_processes(&tree), count_processes(&data.process_tree)),
             scrollable(
                 column(
-                    data.process_events
-                        .iter()
-                        .map(Self::create_spawn_event)
+                    tree.iter()
+                        .flat_map(|node| Self::create_process_node(node, 0, collapsed, data.clock))
                         .collect()
                 )
                 .spacing(10.0)
@@ -326,6 +649,9 @@ impl App {
         if access.execute {
             boxes.push(("X", "execute", ContainerType::Red));
         }
+        if access.list {
+            boxes.push(("L", "list", ContainerType::Yellow));
+        }
         container(
             row(boxes
                 .into_iter()
@@ -394,6 +720,7 @@ impl App {
                         "Total Bytes Written: {}",
                         file_summary.bytes_written
                     )),
+                    text(format!("Paths Probed: {}", file_summary.probe_count)),
                 ])
                 .width(Length::FillPortion(1))
             ]
@@ -460,14 +787,27 @@ impl App {
     }
 
     fn create_process_summary(process_summary: &ProcessSummary) -> Element<'static> {
+        let exit_status = row![
+            text("Exit Status:"),
+            Self::create_exit_status(process_summary.exit_status)
+        ]
+        .spacing(5);
+        let threads = text(format!(
+            "Threads Created: {}",
+            process_summary.threads_created
+        ));
         if process_summary.processes_created == 0 {
-            text("No other processes created").into()
+            column![exit_status, text("No other processes created"), threads]
+                .spacing(3)
+                .into()
         } else {
             column![
+                exit_status,
                 text(format!(
                     "Total Processes Spawned: {}",
                     process_summary.processes_created,
                 )),
+                threads,
                 row![
                     text("Most Common Spawn Type:"),
                     chip(
@@ -496,24 +836,72 @@ impl App {
         }
     }
 
-    fn create_file_event(access: &FileAccess) -> Element<'static> {
+    fn create_trace_quality(trace_quality: &TraceQuality) -> Element<'static> {
+        if trace_quality.is_complete() {
+            return text("All events were received").into();
+        }
+        let mut lines: Vec<Element> =
+            vec![text(format!("Lost Events: {}", trace_quality.lost_count())).into()];
+        lines.extend(trace_quality.lost_events.iter().map(|lost| {
+            monospace(format!(
+                "{} on CPU {}: {} lost",
+                lost.map, lost.cpu, lost.count
+            ))
+        }));
+        lines.push(
+            text(format!(
+                "Unpaired System Call Events: {}",
+                trace_quality.unpaired_events.len()
+            ))
+            .into(),
+        );
+        column(lines).spacing(3).into()
+    }
+
+    fn create_exit_status(status: Option<ExitStatus>) -> Element<'static> {
+        match status {
+            Some(status) => chip(status.text(), status.tooltip()),
+            None => chip(
+                "Not recorded",
+                "The process did not exit while it was traced",
+            ),
+        }
+    }
+
+    fn create_file_event(
+        access: &FileAccess,
+        clock: WallClock,
+        payload_mode: PayloadMode,
+    ) -> Element<'static> {
         let data_views: Element = if access.read_data.is_empty() && access.write_data.is_empty() {
             container(text("No file data recorded.")).into()
         } else {
+            let id = PayloadId {
+                pid: access.pid,
+                file_descriptor: access.file_descriptor,
+                start_time: access.start_time,
+                write: false,
+            };
             let mut row = Row::new();
-            if !access.read_data.is_empty() {
-                row = row.push(column!(
-                    text("Read data:"),
-                    Self::create_hex_preview(&access.read_data)
+            if let (false, Some(format)) = (access.read_data.is_empty(), access.read_format) {
+                row = row.push(Self::create_payload_view(
+                    "Read data:",
+                    &access.read_data,
+                    format,
+                    payload_mode,
+                    id,
                 ));
             }
-            if !access.write_data.is_empty() {
-                row = row.push(column!(
-                    text("Write data:"),
-                    Self::create_hex_preview(&access.write_data)
+            if let (false, Some(format)) = (access.write_data.is_empty(), access.write_format) {
+                row = row.push(Self::create_payload_view(
+                    "Write data:",
+                    &access.write_data,
+                    format,
+                    payload_mode,
+                    PayloadId { write: true, ..id },
                 ));
             }
-            row.width(Length::Fill).into()
+            row.width(Length::Fill).spacing(10).into()
         };
         let name = if access.file_descriptor < 3 {
             text(match access.file_descriptor {
@@ -539,7 +927,16 @@ impl App {
                 ]
                 .align_items(iced::Alignment::Center)
                 .spacing(10),
-                text(format!("Total Data Length: {}", access.data_length)),
+                text(format!(
+                    "Total Data Length: {}{}",
+                    access.data_length,
+                    if access.data_truncated {
+                        " (captured data is truncated)"
+                    } else {
+                        ""
+                    }
+                )),
+                text(format!("Opened: {}", clock.format(access.start_time))),
                 text(format!(
                     "Duration: {}",
                     duration(access.start_time, access.end_time)
@@ -554,6 +951,61 @@ impl App {
         .into()
     }
 
+    fn create_path_probe(probe: &PathProbe, clock: WallClock) -> Element<'static> {
+        row![
+            text(clock.format(probe.time)).size(12),
+            chip(probe.probe.text(), probe.probe.tooltip()),
+            chip(probe.pid, "The Process ID (PID) that checked the path"),
+            monospace(probe.path.to_string_lossy()),
+            if probe.success {
+                text("")
+            } else {
+                text("(not found)")
+            },
+        ]
+        .align_items(iced::Alignment::Center)
+        .spacing(5)
+        .into()
+    }
+
+    fn create_payload_view(
+        label: &str,
+        bytes: &[u8],
+        format: PayloadFormat,
+        mode: PayloadMode,
+        id: PayloadId,
+    ) -> Element<'static> {
+        let contents = match mode {
+            PayloadMode::Hex => Self::create_hex_preview(bytes),
+            PayloadMode::Text => monospace(String::from_utf8_lossy(bytes)),
+            PayloadMode::Escaped => monospace(bytes.escape_ascii()),
+            PayloadMode::Pretty => monospace(
+                describe_payload(bytes, format)
+                    .unwrap_or_else(|| String::from_utf8_lossy(bytes).to_string()),
+            ),
+        };
+        column![
+            row![
+                text(label),
+                chip(format.text(), format.tooltip()),
+                tooltip(
+                    button(text("Save").size(12))
+                        .on_press(Message::SavePayload(id))
+                        .style(Button::Secondary),
+                    "Saves the data to a new file in the directory Blackbox was started in",
+                    tooltip::Position::FollowCursor,
+                )
+                .style(ContainerType::SubtleCard(5.0)),
+            ]
+            .align_items(iced::Alignment::Center)
+            .spacing(5),
+            contents,
+        ]
+        .spacing(3)
+        .width(Length::Fill)
+        .into()
+    }
+
     fn create_hex_preview(bytes: &[u8]) -> Element<'static> {
         let hex = column(
             bytes
@@ -599,9 +1051,10 @@ impl App {
         row!(hex, string).spacing(10).into()
     }
 
-    fn create_connection(conn: &Connection) -> Element<'static> {
+    fn create_connection(conn: &Connection, clock: WallClock) -> Element<'static> {
         container(
             column![
+                text(format!("Opened: {}", clock.format(conn.start_time))),
                 text(format!(
                     "Duration: {}",
                     duration(conn.start_time, conn.end_time)
@@ -625,41 +1078,182 @@ impl App {
         .into()
     }
 
-    fn create_spawn_event(spawn: &SpawnEvent) -> Element<'static> {
-        container(column![
-            row![
-                match &spawn.command {
-                    Some(os_str) => {
-                        text(os_str.to_string_lossy())
-                            .font(Font::MONOSPACE)
-                            .size(20)
-                    }
-                    None => {
-                        text("No Command").size(20)
-                    }
-                },
+    fn create_ipc_channel(channel: &IpcChannel) -> Element<'static> {
+        // link every writer to every reader; a side with no recorded use is shown as unknown
+        let unknown = [None];
+        let writers = channel.writers.iter().map(Some).collect::<Vec<_>>();
+        let readers = channel.readers.iter().map(Some).collect::<Vec<_>>();
+        let writers = if writers.is_empty() {
+            &unknown[..]
+        } else {
+            &writers[..]
+        };
+        let readers = if readers.is_empty() {
+            &unknown[..]
+        } else {
+            &readers[..]
+        };
+        let endpoint = |endpoint: &Option<&IpcEndpoint>| match endpoint {
+            Some(endpoint) => monospace(endpoint.text()),
+            None => text("??").into(),
+        };
+        let links = writers
+            .iter()
+            .flat_map(|writer| {
+                readers.iter().map(move |reader| {
+                    row![endpoint(writer), text("→"), endpoint(reader)]
+                        .align_items(iced::Alignment::Center)
+                        .spacing(5)
+                        .into()
+                })
+            })
+            .collect();
+        container(
+            column![
                 row![
-                    chip(spawn.parent_id, "The parent Process ID (PID)"),
-                    text("-"),
-                    chip(spawn.process_id, "The child Process ID (PID)")
+                    chip(channel.kind.text(), channel.kind.tooltip()),
+                    text(&channel.name).font(Font::MONOSPACE),
+                    text(format!("Bytes Written: {}", channel.bytes_written)),
                 ]
                 .align_items(iced::Alignment::Center)
-                .spacing(3)
-            ]
-            .align_items(iced::Alignment::Center)
-            .spacing(10),
-            text(format!("Timestamp: {}", spawn.spawn_time)),
-            row![
-                text("Spawn Type:"),
-                chip(spawn.spawn_type.text(), spawn.spawn_type.tooltip())
+                .spacing(10),
+                column(links).spacing(3),
             ]
             .spacing(5),
-        ])
+        )
         .style(ContainerType::SubtleCard(5.0))
         .width(Length::Fill)
         .padding(5.0)
         .into()
     }
+
+    /// Creates the rows of `node` and of its children, unless `node` is collapsed.
+    /// Each row is indented by its depth in the tree
+    fn create_process_node(
+        node: &ProcessNode,
+        depth: u16,
+        collapsed: &HashSet<u32>,
+        clock: WallClock,
+    ) -> Vec<Element<'static>> {
+        let is_collapsed = collapsed.contains(&node.pid);
+        let toggle: Element = if node.children.is_empty() {
+            Space::new(25, 25).into()
+        } else {
+            button(text(if is_collapsed { "+" } else { "-" }).font(Font::MONOSPACE))
+                .on_press(Message::ToggleProcess(node.pid))
+                .style(Button::Secondary)
+                .width(25)
+                .into()
+        };
+        let command = match &node.command {
+            Some(command) => text(command.to_string_lossy())
+                .font(Font::MONOSPACE)
+                .size(20),
+            None => text(format!("Process {}", node.pid)).size(20),
+        };
+        let spawn_type = match node.spawn_type {
+            Some(spawn_type) => chip(spawn_type.text(), spawn_type.tooltip()),
+            None => chip("Traced", "The program that was started by Blackbox"),
+        };
+        let arguments: Element = if node.arguments.is_empty() {
+            Space::new(0, 0).into()
+        } else {
+            monospace(
+                node.arguments
+                    .iter()
+                    .map(|arg| arg.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        };
+        let namespaces: Element = if node.namespaces == 0 {
+            Space::new(0, 0).into()
+        } else {
+            row![
+                text("New Namespaces:"),
+                container(text(namespace_flags_text(node.namespaces)))
+                    .style(ContainerType::Orange)
+                    .padding([3, 7, 2, 7]),
+            ]
+            .align_items(iced::Alignment::Center)
+            .spacing(5)
+            .into()
+        };
+        let card = container(
+            column![
+                row![
+                    toggle,
+                    command,
+                    chip(node.pid, "The Process ID (PID)"),
+                    spawn_type,
+                    Space::with_width(Length::Fill),
+                    button(text("Select").size(12))
+                        .on_press(Message::SelectProcess(Some(node.pid)))
+                        .style(Button::Secondary),
+                ]
+                .align_items(iced::Alignment::Center)
+                .spacing(10),
+                arguments,
+                text(format!(
+                    "Started: {} / Lifetime: {}",
+                    clock.format(node.start_time),
+                    match node.end_time {
+                        Some(end_time) => duration(node.start_time, end_time),
+                        None => String::from("??"),
+                    }
+                )),
+                row![
+                    text("Exit Status:"),
+                    Self::create_exit_status(node.exit_status)
+                ]
+                .spacing(5),
+                namespaces,
+                text(format!(
+                    "Files: {} ({} bytes) / Connections: {} / Alerts: {}",
+                    node.file_count,
+                    node.bytes_transferred,
+                    node.connection_count,
+                    node.alert_count
+                )),
+            ]
+            .spacing(3),
+        )
+        .style(ContainerType::SubtleCard(5.0))
+        .width(Length::Fill)
+        .padding(5.0);
+
+        let mut rows = vec![container(card).padding([0, 0, 0, depth * 30]).into()];
+        if !is_collapsed {
+            for child in &node.children {
+                rows.extend(Self::create_process_node(
+                    child,
+                    depth + 1,
+                    collapsed,
+                    clock,
+                ));
+            }
+        }
+        rows
+    }
+}
+
+/// The number of processes in a process tree
+fn count_processes(nodes: &[ProcessNode]) -> usize {
+    nodes
+        .iter()
+        .map(|node| 1 + count_processes(&node.children))
+        .sum()
+}
+
+/// Changes the owner of a file created by blackbox from root to the user who ran sudo, as
+/// given by the `SUDO_UID` and `SUDO_GID` variables sudo sets. Nothing is changed if blackbox
+/// was not started with sudo
+fn give_to_sudo_user(file: &File) -> std::io::Result<()> {
+    let id = |name| std::env::var(name).ok()?.parse::<u32>().ok();
+    match (id("SUDO_UID"), id("SUDO_GID")) {
+        (Some(uid), Some(gid)) => std::os::unix::fs::fchown(file, Some(uid), Some(gid)),
+        _ => Ok(()),
+    }
 }
 
 fn monospace(t: impl ToString) -> Element<'static> {
@@ -697,24 +1291,3 @@ fn header(t: impl ToString) -> Element<'static> {
     .padding([10, 0, 5, 0])
     .into()
 }
-
-fn duration(start: u64, end: u64) -> String {
-    let time = end as i64 - start as i64;
-    if time < 0 || start == 0 {
-        return String::from("??");
-    }
-    match time {
-        t if t > 1e9 as i64 => {
-            format!("{:.1}s", (t as f64) / 1e9)
-        }
-        t if t > 1e6 as i64 => {
-            format!("{:.1}ms", (t as f64) / 1e6)
-        }
-        t if t > 1e3 as i64 => {
-            format!("{:.1}µs", (t as f64) / 1e3)
-        }
-        t => {
-            format!("{:.1}ns", (t as f64))
-        }
-    }
-}
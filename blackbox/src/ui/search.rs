@@ -0,0 +1,277 @@
+use iced::{
+    theme::Button,
+    widget::{button, column, row, text, text_input, Space},
+    Length,
+};
+use regex::Regex;
+
+use crate::types::{Connection, ExitStatus, FileAccess, ProcessNode};
+
+use super::{Element, Message};
+
+/// A change to the search bar
+#[derive(Debug, Clone)]
+pub enum SearchMessage {
+    Text(String),
+    ToggleRegex,
+    ToggleAccess(AccessFilter),
+    ToggleErrorsOnly,
+    Pid(String),
+    From(String),
+    To(String),
+    Stdio(StdioFilter),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessFilter {
+    Read,
+    Write,
+    Execute,
+    List,
+}
+
+/// Whether standard I/O, other files, or both are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StdioFilter {
+    #[default]
+    All,
+    Stdio,
+    Files,
+}
+
+/// The search and filters applied to the File, Network and Process tabs.
+/// Filters that do not apply to a kind of item, e.g. access types for connections, are
+/// ignored for it
+#[derive(Debug, Default)]
+pub struct Search {
+    text: String,
+    use_regex: bool,
+    /// `text` compiled when `use_regex` is set
+    regex: Option<Result<Regex, regex::Error>>,
+    /// only show items with one of these access types. All are shown if this is empty
+    access: Vec<AccessFilter>,
+    errors_only: bool,
+    pid: String,
+    /// the time range, in seconds since the start of the trace
+    from: String,
+    to: String,
+    stdio: StdioFilter,
+}
+
+impl Search {
+    pub fn update(&mut self, message: SearchMessage) {
+        match message {
+            SearchMessage::Text(text) => self.text = text,
+            SearchMessage::ToggleRegex => self.use_regex = !self.use_regex,
+            SearchMessage::ToggleAccess(access) => {
+                if let Some(index) = self.access.iter().position(|a| *a == access) {
+                    self.access.remove(index);
+                } else {
+                    self.access.push(access);
+                }
+            }
+            SearchMessage::ToggleErrorsOnly => self.errors_only = !self.errors_only,
+            SearchMessage::Pid(pid) => self.pid = pid,
+            SearchMessage::From(from) => self.from = from,
+            SearchMessage::To(to) => self.to = to,
+            SearchMessage::Stdio(stdio) => self.stdio = stdio,
+        }
+        self.regex = (self.use_regex && !self.text.is_empty()).then(|| Regex::new(&self.text));
+    }
+
+    /// The search bar and filter chips
+    pub fn view(&self) -> Element<'static> {
+        let chip = |label: &'static str, active: bool, message: SearchMessage| {
+            button(text(label).size(12))
+                .on_press(Message::Search(message))
+                .style(if active {
+                    Button::Primary
+                } else {
+                    Button::Secondary
+                })
+        };
+        let input = |placeholder: &str, value: &str, message: fn(String) -> SearchMessage| {
+            text_input(placeholder, value)
+                .on_input(move |value| Message::Search(message(value)))
+                .padding(5)
+        };
+        let regex_error: Element = match &self.regex {
+            Some(Err(error)) => text(format!("Invalid regular expression: {error}"))
+                .size(12)
+                .into(),
+            _ => Space::new(0, 0).into(),
+        };
+        let access: Vec<Element> = [
+            ("Read", AccessFilter::Read),
+            ("Write", AccessFilter::Write),
+            ("Execute", AccessFilter::Execute),
+            ("List", AccessFilter::List),
+        ]
+        .into_iter()
+        .map(|(label, access)| {
+            chip(
+                label,
+                self.access.contains(&access),
+                SearchMessage::ToggleAccess(access),
+            )
+            .into()
+        })
+        .collect();
+        let stdio: Vec<Element> = [
+            ("All Files", StdioFilter::All),
+            ("Standard I/O", StdioFilter::Stdio),
+            ("Other Files", StdioFilter::Files),
+        ]
+        .into_iter()
+        .map(|(label, stdio)| chip(label, self.stdio == stdio, SearchMessage::Stdio(stdio)).into())
+        .collect();
+        column![
+            row![
+                input(
+                    "Search paths, programs and protocols",
+                    &self.text,
+                    SearchMessage::Text
+                )
+                .width(Length::FillPortion(4)),
+                chip("Regex", self.use_regex, SearchMessage::ToggleRegex),
+                input("PID", &self.pid, SearchMessage::Pid).width(Length::FillPortion(1)),
+                input("From (s)", &self.from, SearchMessage::From).width(Length::FillPortion(1)),
+                input("To (s)", &self.to, SearchMessage::To).width(Length::FillPortion(1)),
+            ]
+            .align_items(iced::Alignment::Center)
+            .spacing(5),
+            row![
+                row(access).spacing(3),
+                row(stdio).spacing(3),
+                chip(
+                    "Errors Only",
+                    self.errors_only,
+                    SearchMessage::ToggleErrorsOnly
+                ),
+            ]
+            .spacing(15),
+            regex_error,
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    /// Whether `access` matches every filter. `trace_start` is the monotonic timestamp that
+    /// the time range is relative to
+    pub fn matches_file(&self, access: &FileAccess, trace_start: u64) -> bool {
+        let name = access
+            .file_name
+            .as_ref()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let is_stdio = access.file_descriptor < 3;
+        let has_access = self.access.iter().any(|filter| match filter {
+            AccessFilter::Read => access.access_type.read,
+            AccessFilter::Write => access.access_type.write,
+            AccessFilter::Execute => access.access_type.execute,
+            AccessFilter::List => access.access_type.list,
+        });
+        self.matches_text(&[name.as_str()])
+            && self.matches_pid(access.pid)
+            && self.matches_time(access.start_time, trace_start)
+            && (self.access.is_empty() || has_access)
+            && (!self.errors_only || access.error_count > 0)
+            && match self.stdio {
+                StdioFilter::All => true,
+                StdioFilter::Stdio => is_stdio,
+                StdioFilter::Files => !is_stdio,
+            }
+    }
+
+    pub fn matches_connection(&self, conn: &Connection, trace_start: u64) -> bool {
+        self.matches_text(&[conn.protocol.text().as_str(), conn.domain.text().as_str()])
+            && self.matches_pid(conn.pid)
+            && self.matches_time(conn.start_time, trace_start)
+    }
+
+    /// Whether the process itself matches, ignoring its children. A process is an error if
+    /// it did not exit successfully
+    pub fn matches_process(&self, node: &ProcessNode, trace_start: u64) -> bool {
+        let command = node
+            .command
+            .as_ref()
+            .map(|command| command.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let arguments = node
+            .arguments
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let failed = !matches!(node.exit_status, Some(ExitStatus::Exited(0)) | None);
+        self.matches_text(&[command.as_str(), arguments.as_str()])
+            && self.matches_pid(node.pid)
+            && self.matches_time(node.start_time, trace_start)
+            && (!self.errors_only || failed)
+    }
+
+    /// Keeps the processes that match and the ones with matching descendants, so that the
+    /// matches are shown in their place in the tree
+    pub fn filter_tree(&self, nodes: &[ProcessNode], trace_start: u64) -> Vec<ProcessNode> {
+        nodes
+            .iter()
+            .filter_map(|node| {
+                let children = self.filter_tree(&node.children, trace_start);
+                if children.is_empty() && !self.matches_process(node, trace_start) {
+                    return None;
+                }
+                Some(ProcessNode {
+                    children,
+                    ..node.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the search text is found in one of `fields`. An invalid regular expression
+    /// matches nothing
+    fn matches_text(&self, fields: &[&str]) -> bool {
+        match &self.regex {
+            Some(Ok(regex)) => fields.iter().any(|field| regex.is_match(field)),
+            Some(Err(_)) => false,
+            None => {
+                let text = self.text.to_lowercase();
+                fields
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&text))
+            }
+        }
+    }
+
+    fn matches_pid(&self, pid: u32) -> bool {
+        match self.pid.trim().parse::<u32>() {
+            Ok(filter) => pid == filter,
+            Err(_) => true,
+        }
+    }
+
+    /// Whether `time` is in the time range. A bound that is not a number is ignored
+    fn matches_time(&self, time: u64, trace_start: u64) -> bool {
+        let seconds = time.saturating_sub(trace_start) as f64 / 1e9;
+        let after_start = match self.from.trim().parse::<f64>() {
+            Ok(from) => seconds >= from,
+            Err(_) => true,
+        };
+        let before_end = match self.to.trim().parse::<f64>() {
+            Ok(to) => seconds <= to,
+            Err(_) => true,
+        };
+        after_start && before_end
+    }
+}
+
+/// The number of items shown out of the total
+pub fn match_count(shown: usize, total: usize) -> Element<'static> {
+    if shown == total {
+        text(format!("{total} items")).size(12).into()
+    } else {
+        text(format!("Showing {shown} of {total} items"))
+            .size(12)
+            .into()
+    }
+}
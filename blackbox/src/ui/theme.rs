@@ -1,5 +1,5 @@
 use iced::{
-    theme::{Button, Scrollable, Text},
+    theme::{Button, Scrollable, Text, TextInput},
     Background, Color,
 };
 
@@ -176,6 +176,53 @@ impl iced::widget::scrollable::StyleSheet for Theme {
     }
 }
 
+impl iced::widget::text_input::StyleSheet for Theme {
+    type Style = TextInput;
+
+    fn active(&self, style: &Self::Style) -> iced::widget::text_input::Appearance {
+        match style {
+            TextInput::Default => iced::widget::text_input::Appearance {
+                background: Background::Color(WHITE),
+                border_radius: 5.0.into(),
+                border_width: 1.0,
+                border_color: MID_WHITE,
+                icon_color: ACCENT_ALT,
+            },
+            TextInput::Custom(_) => unimplemented!(),
+        }
+    }
+
+    fn focused(&self, style: &Self::Style) -> iced::widget::text_input::Appearance {
+        iced::widget::text_input::Appearance {
+            border_color: ACCENT,
+            ..self.active(style)
+        }
+    }
+
+    fn disabled(&self, style: &Self::Style) -> iced::widget::text_input::Appearance {
+        iced::widget::text_input::Appearance {
+            background: Background::Color(OFF_WHITE),
+            ..self.active(style)
+        }
+    }
+
+    fn placeholder_color(&self, _: &Self::Style) -> Color {
+        MID_WHITE
+    }
+
+    fn value_color(&self, _: &Self::Style) -> Color {
+        BLACK
+    }
+
+    fn disabled_color(&self, _: &Self::Style) -> Color {
+        ACCENT_ALT
+    }
+
+    fn selection_color(&self, _: &Self::Style) -> Color {
+        YELLOW
+    }
+}
+
 pub const WHITE: Color = Color {
     r: 247.0 / 255.0,
     g: 247.0 / 255.0,
@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap};
+
+use iced::{
+    theme::Button,
+    widget::{button, column, container, row, scrollable, text, tooltip, Row, Space},
+    Length,
+};
+
+use crate::processing::collect_commands;
+use crate::types::{duration, ProcessingData, SpawnType, WallClock};
+
+use super::theme::ContainerType;
+use super::{header, Element, Message};
+
+/// The width of the timeline at the default zoom level, in pixels
+const BASE_WIDTH: f32 = 1000.0;
+/// The width of events that are too short to see, and of events that happen at an instant
+const MIN_MARK_WIDTH: f32 = 6.0;
+const MARK_HEIGHT: f32 = 16.0;
+/// The number of time labels above the timeline
+const TICK_COUNT: u32 = 10;
+/// The largest zoom level, at which the timeline is this many times wider than the default
+const MAX_ZOOM: f32 = 64.0;
+/// The lane of alerts that were not caused by a single process. No traced process has PID 0,
+/// so it is shown above the lanes of the processes
+const SHARED_LANE: u32 = 0;
+
+#[derive(Debug, Clone, Copy)]
+enum MarkKind {
+    File,
+    Connection,
+    Spawn,
+    Alert,
+}
+
+/// Something that a process did, plotted on its lane
+#[derive(Debug)]
+struct Mark {
+    kind: MarkKind,
+    start: u64,
+    end: u64,
+    /// shown when hovering over the mark
+    details: String,
+}
+
+impl MarkKind {
+    fn text(self) -> &'static str {
+        match self {
+            MarkKind::File => "File",
+            MarkKind::Connection => "Connection",
+            MarkKind::Spawn => "Process",
+            MarkKind::Alert => "Alert",
+        }
+    }
+
+    fn style(self) -> ContainerType {
+        match self {
+            MarkKind::File => ContainerType::Yellow,
+            MarkKind::Connection => ContainerType::Green,
+            MarkKind::Spawn => ContainerType::Orange,
+            MarkKind::Alert => ContainerType::Red,
+        }
+    }
+}
+
+/// The activity of each process over time, one lane per process. `zoom` stretches the
+/// timeline horizontally
+pub fn timeline_view(data: &ProcessingData, zoom: f32) -> Element<'static> {
+    let lanes = collect_lanes(data);
+    let Some((first, last)) = time_range(&lanes) else {
+        return text("No events recorded").into();
+    };
+    let width = BASE_WIDTH * zoom;
+    let scale = width / (last - first).max(1) as f32;
+    let position = |time: u64| time.saturating_sub(first) as f32 * scale;
+
+    let mut commands = HashMap::new();
+    collect_commands(&data.process_tree, &mut commands);
+
+    let ticks = row((0..TICK_COUNT)
+        .map(|tick| {
+            let time = first + (last - first) * tick as u64 / TICK_COUNT as u64;
+            let label = if tick == 0 {
+                data.clock.format(first)
+            } else {
+                format!("+{}", elapsed(first, time))
+            };
+            container(text(label).size(12))
+                .width(width / TICK_COUNT as f32)
+                .into()
+        })
+        .collect());
+    let lanes: Vec<Element> = lanes
+        .into_iter()
+        .map(|(pid, marks)| {
+            let name = match commands.get(&pid) {
+                _ if pid == SHARED_LANE => String::from("Several processes"),
+                Some(command) => format!("Process {pid}: {command}"),
+                None => format!("Process {pid}"),
+            };
+            let rows = pack_rows(marks, &position)
+                .into_iter()
+                .map(|marks| create_row(marks, &position, data.clock))
+                .collect();
+            column![text(name).size(14), column(rows).spacing(2)]
+                .spacing(3)
+                .into()
+        })
+        .collect();
+
+    let legend = row([
+        MarkKind::File,
+        MarkKind::Connection,
+        MarkKind::Spawn,
+        MarkKind::Alert,
+    ]
+    .into_iter()
+    .map(|kind| {
+        row![
+            container(Space::new(MARK_HEIGHT, MARK_HEIGHT)).style(kind.style()),
+            text(kind.text()),
+        ]
+        .spacing(5)
+        .into()
+    })
+    .collect())
+    .spacing(15);
+    let zoom_button = |label: &'static str, zoom: f32| {
+        button(text(label))
+            .on_press(Message::Zoom(zoom.clamp(1.0, MAX_ZOOM)))
+            .style(Button::Secondary)
+    };
+
+    column![
+        header("Timeline:"),
+        row![
+            legend,
+            Space::with_width(Length::Fill),
+            text(format!("Zoom: {zoom}x")),
+            zoom_button("Zoom Out", zoom / 2.0),
+            zoom_button("Zoom In", zoom * 2.0),
+        ]
+        .align_items(iced::Alignment::Center)
+        .spacing(10),
+        scrollable(
+            column![ticks, column(lanes).spacing(10)]
+                .spacing(5)
+                .padding(5)
+        )
+        .direction(scrollable::Direction::Both {
+            vertical: scrollable::Properties::default(),
+            horizontal: scrollable::Properties::default(),
+        }),
+    ]
+    .spacing(10)
+    .into()
+}
+
+/// The marks of each process, by process ID
+fn collect_lanes(data: &ProcessingData) -> BTreeMap<u32, Vec<Mark>> {
+    let mut lanes: BTreeMap<u32, Vec<Mark>> = BTreeMap::new();
+    for access in &data.file_events {
+        let name = match &access.file_name {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => format!("File descriptor {}", access.file_descriptor),
+        };
+        lanes.entry(access.pid).or_default().push(Mark {
+            kind: MarkKind::File,
+            start: access.start_time,
+            end: access.end_time,
+            details: format!(
+                "{name}\n{} bytes over {}",
+                access.data_length,
+                duration(access.start_time, access.end_time)
+            ),
+        });
+    }
+    for conn in &data.network_events {
+        lanes.entry(conn.pid).or_default().push(Mark {
+            kind: MarkKind::Connection,
+            start: conn.start_time,
+            end: conn.end_time,
+            details: format!(
+                "{} connection over {}\nOpen for {}",
+                conn.protocol.text(),
+                conn.domain.text(),
+                duration(conn.start_time, conn.end_time)
+            ),
+        });
+    }
+    for spawn in &data.process_events {
+        let details = match spawn.spawn_type {
+            SpawnType::Exec => format!(
+                "Executed {}",
+                spawn
+                    .command
+                    .as_ref()
+                    .map_or(String::from("an unknown program"), |command| {
+                        command.to_string_lossy().to_string()
+                    })
+            ),
+            spawn_type => format!("{}: created {}", spawn_type.text(), spawn.process_id),
+        };
+        lanes.entry(spawn.parent_id).or_default().push(Mark {
+            kind: MarkKind::Spawn,
+            start: spawn.spawn_time,
+            end: spawn.spawn_time,
+            details,
+        });
+    }
+    for alert in &data.alerts {
+        if let Some(time) = alert.time {
+            // alerts caused by several processes share a lane
+            let pid = alert.pid.unwrap_or(SHARED_LANE);
+            lanes.entry(pid).or_default().push(Mark {
+                kind: MarkKind::Alert,
+                start: time,
+                end: time,
+                details: alert.message.clone(),
+            });
+        }
+    }
+    for marks in lanes.values_mut() {
+        // events that were never recorded as ending take no time
+        for mark in marks.iter_mut() {
+            mark.end = mark.end.max(mark.start);
+        }
+        marks.retain(|mark| mark.start != 0);
+        marks.sort_by_key(|mark| mark.start);
+    }
+    lanes.retain(|_, marks| !marks.is_empty());
+    lanes
+}
+
+/// The first and last timestamps of all marks
+fn time_range(lanes: &BTreeMap<u32, Vec<Mark>>) -> Option<(u64, u64)> {
+    let marks = lanes.values().flatten();
+    let first = marks.clone().map(|mark| mark.start).min()?;
+    let last = marks.map(|mark| mark.end).max()?;
+    Some((first, last))
+}
+
+/// Splits sorted marks into rows so that marks in the same row do not overlap
+fn pack_rows(marks: Vec<Mark>, position: &impl Fn(u64) -> f32) -> Vec<Vec<Mark>> {
+    let mut rows: Vec<Vec<Mark>> = vec![];
+    for mark in marks {
+        let start = position(mark.start);
+        let free_row = rows.iter_mut().find(|row| {
+            let last = row.last().unwrap();
+            position(last.start) + mark_width(last, position) < start
+        });
+        match free_row {
+            Some(row) => row.push(mark),
+            None => rows.push(vec![mark]),
+        }
+    }
+    rows
+}
+
+fn mark_width(mark: &Mark, position: &impl Fn(u64) -> f32) -> f32 {
+    (position(mark.end) - position(mark.start)).max(MIN_MARK_WIDTH)
+}
+
+fn create_row(
+    marks: Vec<Mark>,
+    position: &impl Fn(u64) -> f32,
+    clock: WallClock,
+) -> Element<'static> {
+    let mut row = Row::new();
+    let mut x = 0.0;
+    for mark in marks {
+        let start = position(mark.start);
+        let width = mark_width(&mark, position);
+        row = row.push(Space::with_width(start - x)).push(
+            tooltip(
+                container(Space::new(width, MARK_HEIGHT)).style(mark.kind.style()),
+                format!("{}\n{}", clock.format(mark.start), mark.details),
+                tooltip::Position::FollowCursor,
+            )
+            .style(ContainerType::SubtleCard(5.0)),
+        );
+        x = start + width;
+    }
+    row.into()
+}
+
+/// The time between `first` and `time`
+fn elapsed(first: u64, time: u64) -> String {
+    if time == first {
+        String::from("0s")
+    } else {
+        duration(first, time)
+    }
+}
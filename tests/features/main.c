@@ -7,6 +7,15 @@
 #include <sys/socket.h>
 #include <netdb.h>
 #include <sys/wait.h>
+#include <sys/uio.h>
+#include <sys/sendfile.h>
+#include <sys/stat.h>
+#include <dirent.h>
+#include <pthread.h>
+
+void *thread_main(void *arg) {
+  return arg;
+}
 
 int main(int argc, char** argv) {
   // file testing
@@ -24,6 +33,25 @@ int main(int argc, char** argv) {
   close(file_1);
   close(file_2);
 
+  // vectored and positional I/O
+  printf("Vectored I/O testing ...\n");
+  file_1 = open("/tmp/blackbox-test-2", O_RDWR | O_TRUNC | O_CREAT, 0644);
+  struct iovec iov[2] = {
+    { .iov_base = (void*)data, .iov_len = 20 },
+    { .iov_base = (void*)(data + 20), .iov_len = DATA_SIZE - 20 },
+  };
+  writev(file_1, iov, 2);
+  pwrite(file_1, data, DATA_SIZE, DATA_SIZE);
+  pread(file_1, read_data, DATA_SIZE, 0);
+  iov[0].iov_base = read_data;
+  iov[1].iov_base = read_data + 20;
+  preadv(file_1, iov, 2, DATA_SIZE);
+  file_2 = open("/tmp/blackbox-test-3", O_WRONLY | O_TRUNC | O_CREAT, 0644);
+  off_t offset = 0;
+  sendfile(file_2, file_1, &offset, DATA_SIZE);
+  close(file_1);
+  close(file_2);
+
   // connection testing
   printf("Connection testing ...\n");
   struct addrinfo hints;
@@ -61,6 +89,34 @@ int main(int argc, char** argv) {
   // socket data is not tracked, so we immediately close
   close(sfd);
 
+  // probe testing
+  printf("Probe testing ...\n");
+  struct stat file_stat;
+  stat("/tmp/blackbox-test-1", &file_stat);
+  access("/tmp/.blackbox-missing", R_OK);
+  DIR *dir = opendir("/tmp");
+  if (dir != NULL) {
+    while (readdir(dir) != NULL) {}
+    closedir(dir);
+  }
+
+  // IPC testing
+  printf("IPC testing ...\n");
+  int pipe_fds[2];
+  pipe(pipe_fds);
+  if (fork() == 0) {
+    // child writes to the pipe through its stdout
+    dup2(pipe_fds[1], 1);
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    write(1, data, DATA_SIZE);
+    exit(EXIT_SUCCESS);
+  }
+  close(pipe_fds[1]);
+  read(pipe_fds[0], read_data, DATA_SIZE);
+  close(pipe_fds[0]);
+  wait(NULL);
+
   // process testing
   printf("Process testing ...\n");
   if (fork() != 0) {
@@ -68,6 +124,18 @@ int main(int argc, char** argv) {
     execve("/bin/ls", NULL, NULL);
   }
 
+  wait(NULL);
+
+  // thread testing: this is not a new process
+  printf("Thread testing ...\n");
+  pthread_t thread;
+  pthread_create(&thread, NULL, thread_main, NULL);
+  pthread_join(thread, NULL);
+
+  if (vfork() == 0) {
+    execve("/bin/true", NULL, NULL);
+    _exit(EXIT_FAILURE);
+  }
   wait(NULL);
   return 0;
 }
@@ -0,0 +1,14 @@
+#!/usr/bin/env python
+import ctypes
+import os
+
+libc = ctypes.CDLL(None, use_errno=True)
+
+# these need privileges, but the attempts are flagged even when they fail
+libc.mount(b"/dev/sda1", b"/mnt", b"ext4", 0, None)
+libc.chroot(b"/mnt")
+libc.delete_module(b"blackbox_test", 0)
+try:
+    os.unshare(os.CLONE_NEWNS | os.CLONE_NEWNET)
+except (AttributeError, OSError):
+    print("failed to unshare")
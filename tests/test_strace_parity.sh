@@ -16,7 +16,11 @@ cat strace_ls | awk 'sub(/]/, "", $2) { print $2 }' > strace_ids
 
 # run blackbox
 sudo ./target/release/blackbox --command "ls" --user $USER --include-initial-execve --file-to-write blackbox_ls.json
-jq '.syscall_id' blackbox_ls.json > blackbox_ids
+# process exit and lost event counts are not system calls, and unpaired events are only half
+# of one
+jq 'select(.data | has("ProcessExit") or has("LostEvents") or has("Unpaired") | not) | .syscall_id' blackbox_ls.json > blackbox_ids
+# the IDs cannot match if events were lost
+jq -s '[.[].data.LostEvents.count // 0] | add' blackbox_ls.json
 
 diff blackbox_ids strace_ids
 if [[ $? != 0 ]]; then
@@ -25,6 +29,18 @@ else
   printf '\n\n\033[42m\033[90mTest passed!\033[0m\n\n\n'
 fi
 
-rm -f blackbox_ids blackbox_ls.json strace_ids strace_ls
+# compare the system call names of the strace-style output
+sudo ./target/release/blackbox --command "ls" --user $USER --include-initial-execve --file-to-write blackbox_ls.strace --format strace
+awk '$3 !~ /^\+\+\+/ { sub(/\(.*/, "", $3); print $3 }' blackbox_ls.strace > blackbox_names
+awk '{ sub(/^\[ *[0-9]+\] /, ""); sub(/\(.*/, ""); print }' strace_ls > strace_names
+
+diff blackbox_names strace_names
+if [[ $? != 0 ]]; then
+  printf '\n\n\033[41m\033[90mNames did not match!\033[0m\n\n\n'
+else
+  printf '\n\n\033[42m\033[90mTest passed!\033[0m\n\n\n'
+fi
+
+rm -f blackbox_ids blackbox_ls.json strace_ids strace_ls blackbox_names blackbox_ls.strace strace_names
 
 set +ex
@@ -34,6 +34,10 @@ pub struct Options {
     /// Set the endianness of the BPF target
     #[clap(default_value = "bpfel-unknown-none", long)]
     pub target: Architecture,
+    /// The architecture of the kernel the program is loaded into, e.g. `aarch64`. This sets
+    /// the registers and system call numbers used [default: the host architecture]
+    #[clap(long)]
+    pub arch: Option<String>,
     /// Build the release target
     #[clap(long)]
     pub release: bool,
@@ -51,12 +55,17 @@ pub fn build_ebpf(opts: Options) -> Result<(), anyhow::Error> {
     // vars set by the cargo xtask command are also inherited. RUSTUP_TOOLCHAIN is removed
     // so the rust-toolchain.toml file in the -ebpf folder is honored.
 
-    let status = Command::new("cargo")
+    let mut command = Command::new("cargo");
+    command
         .current_dir(dir)
         .env_remove("RUSTUP_TOOLCHAIN")
-        .args(&args)
-        .status()
-        .expect("failed to build bpf program");
+        .args(&args);
+    // aya-bpf and blackbox-common read the architecture from this cfg
+    if let Some(arch) = &opts.arch {
+        command.env("RUSTFLAGS", format!("--cfg bpf_target_arch=\"{arch}\""));
+    }
+
+    let status = command.status().expect("failed to build bpf program");
     assert!(status.success());
     Ok(())
 }
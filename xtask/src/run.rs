@@ -10,6 +10,10 @@ pub struct Options {
     /// Set the endianness of the BPF target
     #[clap(default_value = "bpfel-unknown-none", long)]
     pub bpf_target: Architecture,
+    /// The architecture of the kernel the eBPF program is loaded into [default: the host
+    /// architecture]
+    #[clap(long)]
+    pub arch: Option<String>,
     /// Build and run the release target
     #[clap(long)]
     pub release: bool,
@@ -41,6 +45,7 @@ pub fn run(opts: Options) -> Result<(), anyhow::Error> {
     // build our ebpf program followed by our application
     build_ebpf(BuildOptions {
         target: opts.bpf_target,
+        arch: opts.arch.clone(),
         release: opts.release,
     })
     .context("Error while building eBPF program")?;